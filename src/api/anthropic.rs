@@ -0,0 +1,471 @@
+//! Chat completions served by Anthropic's Messages API, translated from and to the OpenAI shapes.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, Result};
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    chat_completion::{
+        AssistantMessage, ChatCompletionChoice, ChatCompletionMessage, ChatCompletionModel,
+        ChatCompletionRequest, ChatCompletionResponse, ChatCompletionUsage, FinishReason, Function,
+        Stop, ToolCall, ToolCallType, ToolChoice,
+    },
+    provider::ChatProvider,
+    IntoRequest, SendAndLog, TIMEOUT,
+};
+
+pub const ANTHROPIC_API_URL: &str = "https://api.anthropic.com/v1";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const DEFAULT_MODEL: &str = "claude-sonnet-4-5";
+const DEFAULT_MAX_TOKENS: usize = 4096;
+
+/// A chat provider backed by Anthropic's Messages API.
+///
+/// Custom model ids are sent as is, the OpenAI models of `ChatCompletionModel` are replaced by
+/// the default model. Options without an Anthropic counterpart (penalties, logit_bias, logprobs,
+/// seed, response_format) are ignored; `n` > 1 is rejected.
+#[derive(Debug, Clone)]
+pub struct AnthropicSdk {
+    base_url: String,
+    api_key: String,
+    client: ClientWithMiddleware,
+    default_model: String,
+    max_tokens: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MessagesRequest {
+    model: String,
+    max_tokens: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    messages: Vec<Message>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop_sequences: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<ToolDefinition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Message {
+    role: Role,
+    content: Vec<ContentBlock>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Role {
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentBlock {
+    Text {
+        text: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    ToolResult {
+        tool_use_id: String,
+        content: String,
+    },
+    /// Blocks we don't translate, e.g. thinking.
+    #[serde(other)]
+    Unsupported,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ToolDefinition {
+    name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    description: String,
+    input_schema: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct MessagesResponse {
+    id: String,
+    model: String,
+    content: Vec<ContentBlock>,
+    #[serde(default)]
+    stop_reason: Option<String>,
+    usage: MessagesUsage,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct MessagesUsage {
+    input_tokens: usize,
+    output_tokens: usize,
+}
+
+impl AnthropicSdk {
+    pub fn new(base_url: impl Into<String>, api_key: impl Into<String>, max_retries: u32) -> Self {
+        Self {
+            base_url: base_url.into(),
+            api_key: api_key.into(),
+            client: crate::build_client(max_retries, &[], &[]),
+            default_model: DEFAULT_MODEL.to_owned(),
+            max_tokens: DEFAULT_MAX_TOKENS,
+        }
+    }
+
+    /// The model used when the request names an OpenAI model.
+    pub fn with_default_model(mut self, model: impl Into<String>) -> Self {
+        self.default_model = model.into();
+        self
+    }
+
+    /// The reply limit used when the request has no max_tokens (Anthropic requires one).
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    pub async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse> {
+        let req = self.translate(req)?;
+        let res = req
+            .into_request(&self.base_url, self.client.clone())
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .timeout(Duration::from_secs(TIMEOUT))
+            .send_and_log()
+            .await?;
+        res.json::<MessagesResponse>().await?.try_into()
+    }
+
+    fn translate(&self, req: ChatCompletionRequest) -> Result<MessagesRequest> {
+        if req.n.is_some_and(|n| n > 1) {
+            bail!("Anthropic returns a single choice, n must be 1");
+        }
+
+        let mut system: Vec<String> = vec![];
+        let mut messages: Vec<Message> = vec![];
+        for message in req.messages {
+            let (role, blocks) = match message {
+                ChatCompletionMessage::System(m) => {
+                    system.push(m.content);
+                    continue;
+                }
+                ChatCompletionMessage::User(m) => {
+                    (Role::User, vec![ContentBlock::Text { text: m.content }])
+                }
+                ChatCompletionMessage::Assistant(m) => {
+                    let mut blocks = vec![];
+                    if let Some(text) = m.content.filter(|t| !t.is_empty()) {
+                        blocks.push(ContentBlock::Text { text });
+                    }
+                    for call in m.tool_calls {
+                        blocks.push(ContentBlock::ToolUse {
+                            input: parse_arguments(&call.function.arguments)?,
+                            id: call.id,
+                            name: call.function.name,
+                        });
+                    }
+                    (Role::Assistant, blocks)
+                }
+                ChatCompletionMessage::Tool(m) => (
+                    Role::User,
+                    vec![ContentBlock::ToolResult {
+                        tool_use_id: m.tool_call_id,
+                        content: m.content,
+                    }],
+                ),
+            };
+            if blocks.is_empty() {
+                continue;
+            }
+            // roles must alternate: tool results and the next user message share one turn
+            match messages.last_mut() {
+                Some(last) if last.role == role => last.content.extend(blocks),
+                _ => messages.push(Message {
+                    role,
+                    content: blocks,
+                }),
+            }
+        }
+
+        let tools = req
+            .tools
+            .into_iter()
+            .map(|t| ToolDefinition {
+                name: t.function.name,
+                description: t.function.description,
+                input_schema: t.function.parameters,
+            })
+            .collect::<Vec<_>>();
+        let tool_choice = match (tools.is_empty(), req.tool_choice) {
+            (true, _) => None,
+            (false, choice) => tool_choice(choice, req.parallel_tool_calls),
+        };
+
+        Ok(MessagesRequest {
+            model: match req.model {
+                ChatCompletionModel::Custom(id) => id,
+                _ => self.default_model.clone(),
+            },
+            max_tokens: req.max_tokens.unwrap_or(self.max_tokens),
+            system: (!system.is_empty()).then(|| system.join("\n\n")),
+            messages,
+            temperature: req.temperature,
+            top_p: req.top_p,
+            stop_sequences: match req.stop {
+                Some(Stop::One(stop)) => vec![stop],
+                Some(Stop::Many(stops)) => stops,
+                None => vec![],
+            },
+            tools,
+            tool_choice,
+            metadata: req.user.map(|user| json!({ "user_id": user })),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl ChatProvider for AnthropicSdk {
+    async fn chat_completion(&self, req: ChatCompletionRequest) -> Result<ChatCompletionResponse> {
+        AnthropicSdk::chat_completion(self, req).await
+    }
+}
+
+impl IntoRequest for MessagesRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/messages");
+        client.post(url).json(&self)
+    }
+}
+
+impl TryFrom<MessagesResponse> for ChatCompletionResponse {
+    type Error = anyhow::Error;
+
+    fn try_from(res: MessagesResponse) -> Result<Self> {
+        let mut text: Option<String> = None;
+        let mut tool_calls = vec![];
+        for block in res.content {
+            match block {
+                ContentBlock::Text { text: t } => text.get_or_insert_with(String::new).push_str(&t),
+                ContentBlock::ToolUse { id, name, input } => tool_calls.push(ToolCall {
+                    id,
+                    typ: ToolCallType::Function,
+                    function: Function {
+                        name,
+                        arguments: input.to_string(),
+                    },
+                }),
+                ContentBlock::ToolResult { .. } => {
+                    return Err(anyhow!("unexpected tool_result block in a response"))
+                }
+                ContentBlock::Unsupported => {}
+            }
+        }
+        let finish_reason = match res.stop_reason.as_deref() {
+            Some("max_tokens") => FinishReason::Length,
+            Some("tool_use") => FinishReason::ToolCalls,
+            Some("refusal") => FinishReason::ContentFilter,
+            _ => FinishReason::Stop,
+        };
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as usize)
+            .unwrap_or_default();
+
+        Ok(ChatCompletionResponse {
+            id: res.id,
+            choices: vec![ChatCompletionChoice {
+                finish_reason,
+                index: 0,
+                message: AssistantMessage {
+                    content: text,
+                    name: None,
+                    tool_calls,
+                },
+                logprobs: None,
+            }],
+            created,
+            model: res.model,
+            system_fingerprint: None,
+            object: "chat.completion".to_owned(),
+            usage: Some(ChatCompletionUsage {
+                completion_tokens: res.usage.output_tokens,
+                prompt_tokens: res.usage.input_tokens,
+                total_tokens: res.usage.input_tokens + res.usage.output_tokens,
+            }),
+        })
+    }
+}
+
+/// The tool call arguments as a JSON object, as `tool_use.input` expects.
+fn parse_arguments(arguments: &str) -> Result<serde_json::Value> {
+    if arguments.trim().is_empty() {
+        return Ok(json!({}));
+    }
+    serde_json::from_str(arguments).map_err(|e| anyhow!("invalid tool call arguments: {e}"))
+}
+
+fn tool_choice(
+    choice: Option<ToolChoice>,
+    parallel_tool_calls: Option<bool>,
+) -> Option<serde_json::Value> {
+    let mut value = match choice {
+        Some(ToolChoice::None) => return Some(json!({ "type": "none" })),
+        None if parallel_tool_calls.is_none() => return None,
+        None | Some(ToolChoice::Auto) => json!({ "type": "auto" }),
+        Some(ToolChoice::Required) => json!({ "type": "any" }),
+        Some(ToolChoice::Function { name }) => json!({ "type": "tool", "name": name }),
+    };
+    if let Some(parallel) = parallel_tool_calls {
+        value["disable_parallel_tool_use"] = json!(!parallel);
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chat_completion::{ChatCompletionRequestBuilder, Tool},
+        stub::{StubResponse, StubServer},
+        ToSchema,
+    };
+    use schemars::JsonSchema;
+
+    #[allow(dead_code)]
+    #[derive(Debug, Clone, Deserialize, JsonSchema)]
+    struct GetWeatherArgs {
+        /// The city to get the weather for.
+        city: String,
+    }
+
+    fn request() -> ChatCompletionRequest {
+        ChatCompletionRequestBuilder::default()
+            .model(ChatCompletionModel::Custom("claude-haiku-4-5".into()))
+            .messages(vec![
+                ChatCompletionMessage::new_system("You are a weather bot.", ""),
+                ChatCompletionMessage::new_user("Weather in Boston and Paris?", ""),
+                serde_json::from_value(json!({
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [
+                        {"id": "toolu_1", "type": "function", "function": {"name": "get_weather", "arguments": "{\"city\":\"Boston\"}"}},
+                        {"id": "toolu_2", "type": "function", "function": {"name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}}
+                    ]
+                }))
+                .unwrap(),
+                ChatCompletionMessage::new_tool("toolu_1", "22"),
+                ChatCompletionMessage::new_tool("toolu_2", "18"),
+            ])
+            .tools(vec![Tool::new_function::<GetWeatherArgs>(
+                "get_weather",
+                "Get the weather for a city",
+            )])
+            .stop("END")
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn anthropic_request_should_translate() {
+        let sdk = AnthropicSdk::new(ANTHROPIC_API_URL, "", 0);
+        let req = serde_json::to_value(sdk.translate(request()).unwrap()).unwrap();
+        assert_eq!(req["model"], "claude-haiku-4-5");
+        assert_eq!(req["max_tokens"], DEFAULT_MAX_TOKENS);
+        assert_eq!(req["system"], "You are a weather bot.");
+        assert_eq!(req["stop_sequences"], json!(["END"]));
+        assert!(req.get("tool_choice").is_none());
+        assert_eq!(req["tools"][0]["name"], "get_weather");
+        assert_eq!(req["tools"][0]["input_schema"], GetWeatherArgs::to_schema());
+
+        let messages = req["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0]["role"], "user");
+        assert_eq!(
+            messages[1]["content"][1],
+            json!({"type": "tool_use", "id": "toolu_2", "name": "get_weather", "input": {"city": "Paris"}})
+        );
+        // both tool results share one user turn
+        assert_eq!(messages[2]["role"], "user");
+        assert_eq!(
+            messages[2]["content"],
+            json!([
+                {"type": "tool_result", "tool_use_id": "toolu_1", "content": "22"},
+                {"type": "tool_result", "tool_use_id": "toolu_2", "content": "18"}
+            ])
+        );
+    }
+
+    #[test]
+    fn anthropic_tool_choice_should_translate() {
+        assert_eq!(tool_choice(None, None), None);
+        assert_eq!(
+            tool_choice(Some(ToolChoice::Required), None),
+            Some(json!({"type": "any"}))
+        );
+        assert_eq!(
+            tool_choice(Some(ToolChoice::function("get_weather")), Some(false)),
+            Some(json!({"type": "tool", "name": "get_weather", "disable_parallel_tool_use": true}))
+        );
+        assert_eq!(
+            tool_choice(Some(ToolChoice::None), None),
+            Some(json!({"type": "none"}))
+        );
+    }
+
+    #[tokio::test]
+    async fn anthropic_chat_completion_should_work() -> Result<()> {
+        let server = StubServer::start(|_| {
+            StubResponse::json(
+                200,
+                json!({
+                    "id": "msg_1",
+                    "type": "message",
+                    "role": "assistant",
+                    "model": "claude-haiku-4-5-20251001",
+                    "content": [
+                        {"type": "text", "text": "Let me check."},
+                        {"type": "tool_use", "id": "toolu_3", "name": "get_weather", "input": {"city": "Rome"}}
+                    ],
+                    "stop_reason": "tool_use",
+                    "usage": {"input_tokens": 120, "output_tokens": 30}
+                }),
+            )
+        })
+        .await;
+        let provider: Box<dyn ChatProvider> =
+            Box::new(AnthropicSdk::new(server.url(), "sk-ant", 0));
+        let res = provider.chat_completion(request()).await?;
+
+        let choice = &res.choices[0];
+        assert_eq!(choice.finish_reason, FinishReason::ToolCalls);
+        assert_eq!(choice.message.content(), Some("Let me check."));
+        let call = &choice.message.tool_calls()[0];
+        assert_eq!(call.id(), "toolu_3");
+        assert_eq!(call.name(), "get_weather");
+        assert_eq!(call.arguments(), r#"{"city":"Rome"}"#);
+        assert_eq!(res.usage.unwrap().total_tokens, 150);
+
+        let sent = &server.requests()[0];
+        assert_eq!(sent.path, "/messages");
+        assert_eq!(sent.header("x-api-key"), Some("sk-ant"));
+        assert_eq!(sent.header("anthropic-version"), Some(ANTHROPIC_VERSION));
+        assert!(sent.header("authorization").is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,1269 @@
+use std::collections::HashMap;
+
+use derive_builder::Builder;
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chat_completion::{ChatCompletionModel, ChatCompletionUsage, Tool, ToolCall},
+    pagination::ListParams,
+    IntoRequest,
+};
+
+/// The Assistants API is in beta, every request needs to opt in with this header.
+pub(crate) const OPENAI_BETA: &str = "OpenAI-Beta";
+pub(crate) const ASSISTANTS_V2: &str = "assistants=v2";
+
+/// Set of 16 key-value pairs that can be attached to an object. Keys can be a maximum of 64
+/// characters long and values can be a maximum of 512 characters long.
+pub type Metadata = HashMap<String, String>;
+
+#[derive(Debug, Serialize, Clone, Builder)]
+#[builder(pattern = "mutable")]
+pub struct CreateAssistantRequest {
+    /// ID of the model to use.
+    #[builder(default)]
+    model: ChatCompletionModel,
+
+    /// The name of the assistant. The maximum length is 256 characters.
+    #[builder(default, setter(strip_option, into))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+
+    /// The description of the assistant. The maximum length is 512 characters.
+    #[builder(default, setter(strip_option, into))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+
+    /// The system instructions that the assistant uses. The maximum length is 256,000 characters.
+    #[builder(default, setter(strip_option, into))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instructions: Option<String>,
+
+    /// A list of tool enabled on the assistant. There can be a maximum of 128 tools per assistant.
+    /// Tools can be of types code_interpreter, file_search, or function.
+    #[builder(default, setter(into))]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<AssistantTool>,
+
+    /// A set of resources that are used by the assistant's tools.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_resources: Option<ToolResources>,
+
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<Metadata>,
+
+    /// What sampling temperature to use, between 0 and 2.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+
+    /// An alternative to sampling with temperature, called nucleus sampling.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+}
+
+#[derive(Debug, Serialize, Clone, Builder)]
+#[builder(pattern = "mutable")]
+pub struct ModifyAssistantRequest {
+    /// The ID of the assistant to modify.
+    #[builder(setter(into))]
+    #[serde(skip)]
+    assistant_id: String,
+
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<ChatCompletionModel>,
+
+    #[builder(default, setter(strip_option, into))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+
+    #[builder(default, setter(strip_option, into))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+
+    #[builder(default, setter(strip_option, into))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instructions: Option<String>,
+
+    /// If set, replaces the tools of the assistant.
+    #[builder(default, setter(strip_option, into))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<AssistantTool>>,
+
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_resources: Option<ToolResources>,
+
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<Metadata>,
+
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetrieveAssistantRequest {
+    assistant_id: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeleteAssistantRequest {
+    assistant_id: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ListAssistantsRequest {
+    params: ListParams,
+}
+
+#[derive(Debug, Serialize, Clone, Default, Builder)]
+#[builder(pattern = "mutable")]
+pub struct CreateThreadRequest {
+    /// A list of messages to start the thread with.
+    #[builder(default, setter(into))]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    messages: Vec<MessageInput>,
+
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_resources: Option<ToolResources>,
+
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<Metadata>,
+}
+
+#[derive(Debug, Serialize, Clone, Builder)]
+#[builder(pattern = "mutable")]
+pub struct ModifyThreadRequest {
+    /// The ID of the thread to modify.
+    #[builder(setter(into))]
+    #[serde(skip)]
+    thread_id: String,
+
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_resources: Option<ToolResources>,
+
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<Metadata>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetrieveThreadRequest {
+    thread_id: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeleteThreadRequest {
+    thread_id: String,
+}
+
+/// A message to add to a thread, either when creating the thread or afterwards.
+#[derive(Debug, Serialize, Clone, Builder)]
+#[builder(pattern = "mutable")]
+pub struct MessageInput {
+    /// The role of the entity that is creating the message.
+    #[builder(default)]
+    role: MessageRole,
+
+    /// The text contents of the message.
+    #[builder(setter(into))]
+    content: String,
+
+    /// A list of files attached to the message, and the tools they should be added to.
+    #[builder(default, setter(into))]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<Attachment>,
+
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<Metadata>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CreateMessageRequest {
+    #[serde(skip)]
+    thread_id: String,
+
+    #[serde(flatten)]
+    message: MessageInput,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ListMessagesRequest {
+    #[serde(skip)]
+    thread_id: String,
+
+    /// Filter messages by the run ID that generated them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    run_id: Option<String>,
+
+    #[serde(flatten)]
+    params: ListParams,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetrieveMessageRequest {
+    thread_id: String,
+    message_id: String,
+}
+
+#[derive(Debug, Serialize, Clone, Builder)]
+#[builder(pattern = "mutable")]
+pub struct CreateRunRequest {
+    /// The ID of the thread to run.
+    #[builder(setter(into))]
+    #[serde(skip)]
+    thread_id: String,
+
+    /// The ID of the assistant to use to execute this run.
+    #[builder(setter(into))]
+    assistant_id: String,
+
+    /// Override the model of the assistant for this run.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<ChatCompletionModel>,
+
+    /// Overrides the instructions of the assistant for this run.
+    #[builder(default, setter(strip_option, into))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instructions: Option<String>,
+
+    /// Appends additional instructions at the end of the instructions for the run.
+    #[builder(default, setter(strip_option, into))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    additional_instructions: Option<String>,
+
+    /// Adds additional messages to the thread before creating the run.
+    #[builder(default, setter(into))]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    additional_messages: Vec<MessageInput>,
+
+    /// Override the tools the assistant can use for this run.
+    #[builder(default, setter(strip_option, into))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<AssistantTool>>,
+
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<Metadata>,
+
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+
+    /// The maximum number of prompt tokens that may be used over the course of the run.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_prompt_tokens: Option<usize>,
+
+    /// The maximum number of completion tokens that may be used over the course of the run.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_completion_tokens: Option<usize>,
+
+    /// Whether to enable parallel function calling during tool use.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parallel_tool_calls: Option<bool>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetrieveRunRequest {
+    thread_id: String,
+    run_id: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct CancelRunRequest {
+    thread_id: String,
+    run_id: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListRunsRequest {
+    thread_id: String,
+    params: ListParams,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SubmitToolOutputsRequest {
+    #[serde(skip)]
+    thread_id: String,
+
+    #[serde(skip)]
+    run_id: String,
+
+    /// A list of tools for which the outputs are being submitted.
+    tool_outputs: Vec<ToolOutput>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct ToolOutput {
+    /// The ID of the tool call in the required_action object within the run object the output is being submitted for.
+    pub tool_call_id: String,
+
+    /// The output of the tool call to be submitted to continue the run.
+    pub output: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListRunStepsRequest {
+    thread_id: String,
+    run_id: String,
+    params: ListParams,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetrieveRunStepRequest {
+    thread_id: String,
+    run_id: String,
+    step_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AssistantTool {
+    CodeInterpreter,
+    FileSearch,
+    /// A function tool, the same as the tools of chat completion.
+    #[serde(untagged)]
+    Function(Tool),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ToolResources {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub code_interpreter: Option<CodeInterpreterResources>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub file_search: Option<FileSearchResources>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CodeInterpreterResources {
+    /// A list of file IDs made available to the code_interpreter tool.
+    #[serde(default)]
+    pub file_ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct FileSearchResources {
+    /// The vector store attached to this assistant or thread.
+    #[serde(default)]
+    pub vector_store_ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Attachment {
+    /// The ID of the file to attach to the message.
+    pub file_id: String,
+
+    /// The tools to add this file to.
+    pub tools: Vec<AssistantTool>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageRole {
+    #[default]
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Assistant {
+    /// The identifier, which can be referenced in API endpoints.
+    pub id: String,
+
+    /// The object type, which is always "assistant".
+    pub object: String,
+
+    /// The Unix timestamp (in seconds) for when the assistant was created.
+    pub created_at: u64,
+
+    #[serde(default)]
+    pub name: Option<String>,
+
+    #[serde(default)]
+    pub description: Option<String>,
+
+    /// ID of the model to use.
+    pub model: String,
+
+    #[serde(default)]
+    pub instructions: Option<String>,
+
+    #[serde(default)]
+    pub tools: Vec<AssistantTool>,
+
+    #[serde(default)]
+    pub tool_resources: Option<ToolResources>,
+
+    #[serde(default)]
+    pub metadata: Metadata,
+
+    #[serde(default)]
+    pub temperature: Option<f32>,
+
+    #[serde(default)]
+    pub top_p: Option<f32>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Thread {
+    pub id: String,
+
+    /// The object type, which is always "thread".
+    pub object: String,
+
+    pub created_at: u64,
+
+    #[serde(default)]
+    pub tool_resources: Option<ToolResources>,
+
+    #[serde(default)]
+    pub metadata: Metadata,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ThreadMessage {
+    pub id: String,
+
+    /// The object type, which is always "thread.message".
+    pub object: String,
+
+    pub created_at: u64,
+
+    /// The thread ID that this message belongs to.
+    pub thread_id: String,
+
+    /// The status of the message, which can be either in_progress, incomplete, or completed.
+    #[serde(default)]
+    pub status: Option<String>,
+
+    /// The entity that produced the message.
+    pub role: MessageRole,
+
+    /// The content of the message in array of text and/or images.
+    pub content: Vec<MessageContent>,
+
+    /// If applicable, the ID of the assistant that authored this message.
+    #[serde(default)]
+    pub assistant_id: Option<String>,
+
+    /// The ID of the run associated with the creation of this message.
+    #[serde(default)]
+    pub run_id: Option<String>,
+
+    #[serde(default)]
+    pub attachments: Option<Vec<Attachment>>,
+
+    #[serde(default)]
+    pub metadata: Metadata,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MessageContent {
+    Text {
+        text: TextContent,
+    },
+    ImageFile {
+        image_file: ImageFile,
+    },
+    ImageUrl {
+        image_url: ImageUrl,
+    },
+    Refusal {
+        refusal: String,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TextContent {
+    /// The data that makes up the text.
+    pub value: String,
+
+    /// File citations and file paths referenced by the text.
+    #[serde(default)]
+    pub annotations: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ImageFile {
+    pub file_id: String,
+    #[serde(default)]
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ImageUrl {
+    pub url: String,
+    #[serde(default)]
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Run {
+    pub id: String,
+
+    /// The object type, which is always "thread.run".
+    pub object: String,
+
+    pub created_at: u64,
+
+    /// The ID of the thread that was executed on as a part of this run.
+    pub thread_id: String,
+
+    /// The ID of the assistant used for execution of this run.
+    pub assistant_id: String,
+
+    /// The status of the run.
+    pub status: RunStatus,
+
+    /// Details on the action required to continue the run. Will be None if no action is required.
+    #[serde(default)]
+    pub required_action: Option<RequiredAction>,
+
+    /// The last error associated with this run. Will be None if there are no errors.
+    #[serde(default)]
+    pub last_error: Option<LastError>,
+
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+
+    #[serde(default)]
+    pub started_at: Option<u64>,
+
+    #[serde(default)]
+    pub cancelled_at: Option<u64>,
+
+    #[serde(default)]
+    pub failed_at: Option<u64>,
+
+    #[serde(default)]
+    pub completed_at: Option<u64>,
+
+    /// The model that the assistant used for this run.
+    pub model: String,
+
+    /// The instructions that the assistant used for this run.
+    #[serde(default)]
+    pub instructions: Option<String>,
+
+    /// The list of tools that the assistant used for this run.
+    #[serde(default)]
+    pub tools: Vec<AssistantTool>,
+
+    #[serde(default)]
+    pub metadata: Metadata,
+
+    /// Usage statistics related to the run. This value will be None if the run is not in a terminal state.
+    #[serde(default)]
+    pub usage: Option<ChatCompletionUsage>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Queued,
+    InProgress,
+    RequiresAction,
+    Cancelling,
+    Cancelled,
+    Failed,
+    Completed,
+    Incomplete,
+    Expired,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RequiredAction {
+    /// For now, this is always submit_tool_outputs.
+    #[serde(rename = "type")]
+    pub typ: String,
+
+    /// Details on the tool outputs needed for this run to continue.
+    pub submit_tool_outputs: SubmitToolOutputs,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SubmitToolOutputs {
+    /// A list of the relevant tool calls.
+    pub tool_calls: Vec<ToolCall>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct LastError {
+    /// One of server_error, rate_limit_exceeded, or invalid_prompt.
+    pub code: String,
+
+    /// A human-readable description of the error.
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RunStep {
+    pub id: String,
+
+    /// The object type, which is always "thread.run.step".
+    pub object: String,
+
+    pub created_at: u64,
+
+    pub assistant_id: String,
+
+    pub thread_id: String,
+
+    pub run_id: String,
+
+    /// The status of the run step, which can be either in_progress, cancelled, failed, completed, or expired.
+    pub status: String,
+
+    /// The details of the run step.
+    pub step_details: RunStepDetails,
+
+    #[serde(default)]
+    pub last_error: Option<LastError>,
+
+    #[serde(default)]
+    pub completed_at: Option<u64>,
+
+    #[serde(default)]
+    pub usage: Option<ChatCompletionUsage>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RunStepDetails {
+    MessageCreation {
+        message_creation: MessageCreation,
+    },
+    /// The tool calls can be of type code_interpreter, file_search or function, they are kept
+    /// as raw json.
+    ToolCalls {
+        tool_calls: Vec<serde_json::Value>,
+    },
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MessageCreation {
+    /// The ID of the message that was created by this run step.
+    pub message_id: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DeletionStatus {
+    pub id: String,
+    pub object: String,
+    pub deleted: bool,
+}
+
+impl CreateAssistantRequest {
+    pub fn new(model: ChatCompletionModel, instructions: impl Into<String>) -> Self {
+        CreateAssistantRequestBuilder::default()
+            .model(model)
+            .instructions(instructions)
+            .build()
+            .unwrap()
+    }
+}
+
+impl RetrieveAssistantRequest {
+    pub fn new(assistant_id: impl Into<String>) -> Self {
+        Self {
+            assistant_id: assistant_id.into(),
+        }
+    }
+}
+
+impl DeleteAssistantRequest {
+    pub fn new(assistant_id: impl Into<String>) -> Self {
+        Self {
+            assistant_id: assistant_id.into(),
+        }
+    }
+}
+
+impl ListAssistantsRequest {
+    pub fn new(params: ListParams) -> Self {
+        Self { params }
+    }
+}
+
+impl CreateThreadRequest {
+    pub fn new(messages: Vec<MessageInput>) -> Self {
+        CreateThreadRequestBuilder::default()
+            .messages(messages)
+            .build()
+            .unwrap()
+    }
+}
+
+impl RetrieveThreadRequest {
+    pub fn new(thread_id: impl Into<String>) -> Self {
+        Self {
+            thread_id: thread_id.into(),
+        }
+    }
+}
+
+impl DeleteThreadRequest {
+    pub fn new(thread_id: impl Into<String>) -> Self {
+        Self {
+            thread_id: thread_id.into(),
+        }
+    }
+}
+
+impl MessageInput {
+    pub fn user(content: impl Into<String>) -> Self {
+        MessageInputBuilder::default()
+            .content(content)
+            .build()
+            .unwrap()
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        MessageInputBuilder::default()
+            .role(MessageRole::Assistant)
+            .content(content)
+            .build()
+            .unwrap()
+    }
+}
+
+impl CreateMessageRequest {
+    pub fn new(thread_id: impl Into<String>, message: MessageInput) -> Self {
+        Self {
+            thread_id: thread_id.into(),
+            message,
+        }
+    }
+}
+
+impl ListMessagesRequest {
+    pub fn new(thread_id: impl Into<String>, params: ListParams) -> Self {
+        Self {
+            thread_id: thread_id.into(),
+            run_id: None,
+            params,
+        }
+    }
+
+    /// Only list the messages generated by the given run.
+    pub fn with_run_id(mut self, run_id: impl Into<String>) -> Self {
+        self.run_id = Some(run_id.into());
+        self
+    }
+}
+
+impl RetrieveMessageRequest {
+    pub fn new(thread_id: impl Into<String>, message_id: impl Into<String>) -> Self {
+        Self {
+            thread_id: thread_id.into(),
+            message_id: message_id.into(),
+        }
+    }
+}
+
+impl CreateRunRequest {
+    pub fn new(thread_id: impl Into<String>, assistant_id: impl Into<String>) -> Self {
+        CreateRunRequestBuilder::default()
+            .thread_id(thread_id)
+            .assistant_id(assistant_id)
+            .build()
+            .unwrap()
+    }
+}
+
+impl RetrieveRunRequest {
+    pub fn new(thread_id: impl Into<String>, run_id: impl Into<String>) -> Self {
+        Self {
+            thread_id: thread_id.into(),
+            run_id: run_id.into(),
+        }
+    }
+}
+
+impl CancelRunRequest {
+    pub fn new(thread_id: impl Into<String>, run_id: impl Into<String>) -> Self {
+        Self {
+            thread_id: thread_id.into(),
+            run_id: run_id.into(),
+        }
+    }
+}
+
+impl ListRunsRequest {
+    pub fn new(thread_id: impl Into<String>, params: ListParams) -> Self {
+        Self {
+            thread_id: thread_id.into(),
+            params,
+        }
+    }
+}
+
+impl SubmitToolOutputsRequest {
+    pub fn new(
+        thread_id: impl Into<String>,
+        run_id: impl Into<String>,
+        tool_outputs: Vec<ToolOutput>,
+    ) -> Self {
+        Self {
+            thread_id: thread_id.into(),
+            run_id: run_id.into(),
+            tool_outputs,
+        }
+    }
+}
+
+impl ToolOutput {
+    pub fn new(tool_call_id: impl Into<String>, output: impl Into<String>) -> Self {
+        Self {
+            tool_call_id: tool_call_id.into(),
+            output: output.into(),
+        }
+    }
+}
+
+impl ListRunStepsRequest {
+    pub fn new(
+        thread_id: impl Into<String>,
+        run_id: impl Into<String>,
+        params: ListParams,
+    ) -> Self {
+        Self {
+            thread_id: thread_id.into(),
+            run_id: run_id.into(),
+            params,
+        }
+    }
+}
+
+impl RetrieveRunStepRequest {
+    pub fn new(
+        thread_id: impl Into<String>,
+        run_id: impl Into<String>,
+        step_id: impl Into<String>,
+    ) -> Self {
+        Self {
+            thread_id: thread_id.into(),
+            run_id: run_id.into(),
+            step_id: step_id.into(),
+        }
+    }
+}
+
+impl From<Tool> for AssistantTool {
+    fn from(tool: Tool) -> Self {
+        Self::Function(tool)
+    }
+}
+
+impl ThreadMessage {
+    /// The concatenated text contents of the message.
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|c| match c {
+                MessageContent::Text { text } => Some(text.value.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl Run {
+    /// The tool calls the run is waiting for, empty if no action is required.
+    pub fn required_tool_calls(&self) -> &[ToolCall] {
+        self.required_action
+            .as_ref()
+            .map(|action| action.submit_tool_outputs.tool_calls.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+impl RunStatus {
+    /// Whether the run is still being processed by the server, i.e. it's neither finished nor
+    /// waiting for tool outputs.
+    pub fn is_pending(&self) -> bool {
+        matches!(self, Self::Queued | Self::InProgress | Self::Cancelling)
+    }
+}
+
+impl IntoRequest for CreateAssistantRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/assistants");
+        client
+            .post(url)
+            .header(OPENAI_BETA, ASSISTANTS_V2)
+            .json(&self)
+    }
+}
+
+impl IntoRequest for RetrieveAssistantRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/assistants/{}", self.assistant_id);
+        client.get(url).header(OPENAI_BETA, ASSISTANTS_V2)
+    }
+}
+
+impl IntoRequest for ModifyAssistantRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/assistants/{}", self.assistant_id);
+        client
+            .post(url)
+            .header(OPENAI_BETA, ASSISTANTS_V2)
+            .json(&self)
+    }
+}
+
+impl IntoRequest for DeleteAssistantRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/assistants/{}", self.assistant_id);
+        client.delete(url).header(OPENAI_BETA, ASSISTANTS_V2)
+    }
+}
+
+impl IntoRequest for ListAssistantsRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/assistants");
+        client
+            .get(url)
+            .header(OPENAI_BETA, ASSISTANTS_V2)
+            .query(&self.params)
+    }
+}
+
+impl IntoRequest for CreateThreadRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/threads");
+        client
+            .post(url)
+            .header(OPENAI_BETA, ASSISTANTS_V2)
+            .json(&self)
+    }
+}
+
+impl IntoRequest for RetrieveThreadRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/threads/{}", self.thread_id);
+        client.get(url).header(OPENAI_BETA, ASSISTANTS_V2)
+    }
+}
+
+impl IntoRequest for ModifyThreadRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/threads/{}", self.thread_id);
+        client
+            .post(url)
+            .header(OPENAI_BETA, ASSISTANTS_V2)
+            .json(&self)
+    }
+}
+
+impl IntoRequest for DeleteThreadRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/threads/{}", self.thread_id);
+        client.delete(url).header(OPENAI_BETA, ASSISTANTS_V2)
+    }
+}
+
+impl IntoRequest for CreateMessageRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/threads/{}/messages", self.thread_id);
+        client
+            .post(url)
+            .header(OPENAI_BETA, ASSISTANTS_V2)
+            .json(&self)
+    }
+}
+
+impl IntoRequest for ListMessagesRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/threads/{}/messages", self.thread_id);
+        client
+            .get(url)
+            .header(OPENAI_BETA, ASSISTANTS_V2)
+            .query(&self)
+    }
+}
+
+impl IntoRequest for RetrieveMessageRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!(
+            "{base_url}/threads/{}/messages/{}",
+            self.thread_id, self.message_id
+        );
+        client.get(url).header(OPENAI_BETA, ASSISTANTS_V2)
+    }
+}
+
+impl IntoRequest for CreateRunRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/threads/{}/runs", self.thread_id);
+        client
+            .post(url)
+            .header(OPENAI_BETA, ASSISTANTS_V2)
+            .json(&self)
+    }
+}
+
+impl IntoRequest for RetrieveRunRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/threads/{}/runs/{}", self.thread_id, self.run_id);
+        client.get(url).header(OPENAI_BETA, ASSISTANTS_V2)
+    }
+}
+
+impl IntoRequest for CancelRunRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!(
+            "{base_url}/threads/{}/runs/{}/cancel",
+            self.thread_id, self.run_id
+        );
+        client.post(url).header(OPENAI_BETA, ASSISTANTS_V2)
+    }
+}
+
+impl IntoRequest for ListRunsRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/threads/{}/runs", self.thread_id);
+        client
+            .get(url)
+            .header(OPENAI_BETA, ASSISTANTS_V2)
+            .query(&self.params)
+    }
+}
+
+impl IntoRequest for SubmitToolOutputsRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!(
+            "{base_url}/threads/{}/runs/{}/submit_tool_outputs",
+            self.thread_id, self.run_id
+        );
+        client
+            .post(url)
+            .header(OPENAI_BETA, ASSISTANTS_V2)
+            .json(&self)
+    }
+}
+
+impl IntoRequest for ListRunStepsRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!(
+            "{base_url}/threads/{}/runs/{}/steps",
+            self.thread_id, self.run_id
+        );
+        client
+            .get(url)
+            .header(OPENAI_BETA, ASSISTANTS_V2)
+            .query(&self.params)
+    }
+}
+
+impl IntoRequest for RetrieveRunStepRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!(
+            "{base_url}/threads/{}/runs/{}/steps/{}",
+            self.thread_id, self.run_id, self.step_id
+        );
+        client.get(url).header(OPENAI_BETA, ASSISTANTS_V2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{poll::PollConfig, LlmSdk, SDK};
+
+    use super::*;
+    use anyhow::Result;
+    use schemars::JsonSchema;
+    use serde_json::json;
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize, JsonSchema)]
+    struct GetWeatherArgs {
+        city: String,
+    }
+
+    #[test]
+    fn create_assistant_request_should_serialize() -> Result<()> {
+        let req = CreateAssistantRequestBuilder::default()
+            .name("Weather bot")
+            .instructions("You tell the weather.")
+            .tools(vec![
+                AssistantTool::CodeInterpreter,
+                Tool::new_function::<GetWeatherArgs>("get_weather", "Get the weather of a city.")
+                    .into(),
+            ])
+            .build()?;
+        let json = serde_json::to_value(req)?;
+        assert_eq!(json["model"], "gpt-3.5-turbo");
+        assert_eq!(json["tools"][0], json!({"type": "code_interpreter"}));
+        assert_eq!(json["tools"][1]["type"], "function");
+        assert_eq!(json["tools"][1]["function"]["name"], "get_weather");
+        Ok(())
+    }
+
+    #[test]
+    fn assistant_tools_should_deserialize() -> Result<()> {
+        let tools: Vec<AssistantTool> = serde_json::from_value(json!([
+            {"type": "code_interpreter"},
+            {"type": "file_search", "file_search": {"max_num_results": 20}},
+            {
+                "type": "function",
+                "function": {
+                    "name": "get_weather",
+                    "description": "Get the weather of a city.",
+                    "parameters": {"type": "object", "properties": {}}
+                }
+            }
+        ]))?;
+        assert!(matches!(tools[0], AssistantTool::CodeInterpreter));
+        assert!(matches!(tools[1], AssistantTool::FileSearch));
+        assert!(matches!(tools[2], AssistantTool::Function(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn run_requiring_action_should_deserialize() -> Result<()> {
+        let run: Run = serde_json::from_value(json!({
+            "id": "run_abc123",
+            "object": "thread.run",
+            "created_at": 1699075072,
+            "assistant_id": "asst_abc123",
+            "thread_id": "thread_abc123",
+            "status": "requires_action",
+            "required_action": {
+                "type": "submit_tool_outputs",
+                "submit_tool_outputs": {
+                    "tool_calls": [{
+                        "id": "call_abc123",
+                        "type": "function",
+                        "function": {"name": "get_weather", "arguments": "{\"city\":\"Boston\"}"}
+                    }]
+                }
+            },
+            "last_error": null,
+            "model": "gpt-4o",
+            "instructions": "You tell the weather.",
+            "tools": [],
+            "metadata": {},
+            "usage": null
+        }))?;
+        assert_eq!(run.status, RunStatus::RequiresAction);
+        assert!(!run.status.is_pending());
+        let calls = run.required_tool_calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id(), "call_abc123");
+        assert_eq!(calls[0].name(), "get_weather");
+        Ok(())
+    }
+
+    #[test]
+    fn thread_message_text_should_work() -> Result<()> {
+        let message: ThreadMessage = serde_json::from_value(json!({
+            "id": "msg_abc123",
+            "object": "thread.message",
+            "created_at": 1699017614,
+            "thread_id": "thread_abc123",
+            "role": "assistant",
+            "content": [
+                {"type": "text", "text": {"value": "It is sunny.", "annotations": []}},
+                {"type": "image_file", "image_file": {"file_id": "file-abc123"}}
+            ],
+            "assistant_id": "asst_abc123",
+            "run_id": "run_abc123",
+            "attachments": [],
+            "metadata": {}
+        }))?;
+        assert_eq!(message.role, MessageRole::Assistant);
+        assert_eq!(message.text(), "It is sunny.");
+        Ok(())
+    }
+
+    #[test]
+    fn list_messages_request_should_build_query() -> Result<()> {
+        let sdk = LlmSdk::new("https://api.openai.com/v1", "", 0);
+        let req = ListMessagesRequest::new("thread_abc123", ListParams::after("msg_1"))
+            .with_run_id("run_abc123");
+        let req = sdk.prepare_request(req).build()?;
+        assert_eq!(
+            req.url().as_str(),
+            "https://api.openai.com/v1/threads/thread_abc123/messages?run_id=run_abc123&after=msg_1"
+        );
+        assert_eq!(req.headers()["OpenAI-Beta"], "assistants=v2");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn run_to_completion_should_call_tools() -> Result<()> {
+        let req = CreateAssistantRequestBuilder::default()
+            .instructions("You tell the weather, always use the get_weather function.")
+            .tools(vec![Tool::new_function::<GetWeatherArgs>(
+                "get_weather",
+                "Get the weather of a city.",
+            )
+            .into()])
+            .build()?;
+        let assistant = SDK.create_assistant(req).await?;
+        let thread = SDK
+            .create_thread(CreateThreadRequest::new(vec![MessageInput::user(
+                "What is the weather like in Boston?",
+            )]))
+            .await?;
+        let run = SDK
+            .create_run(CreateRunRequest::new(&thread.id, &assistant.id))
+            .await?;
+
+        let config = PollConfig {
+            initial_delay: Duration::from_millis(500),
+            timeout: Some(Duration::from_secs(60)),
+            ..PollConfig::for_runs()
+        };
+        let mut called = Vec::new();
+        let run = SDK
+            .run_to_completion(run, config, |call| {
+                called.push(call.name().to_owned());
+                async { Ok("22 degrees and sunny".to_owned()) }
+            })
+            .await?;
+        assert_eq!(run.status, RunStatus::Completed);
+        assert_eq!(called, vec!["get_weather"]);
+
+        let messages = SDK
+            .list_messages(ListMessagesRequest::new(&thread.id, ListParams::default()))
+            .await?;
+        assert_eq!(messages.data[0].role, MessageRole::Assistant);
+
+        SDK.delete_thread(&thread.id).await?;
+        SDK.delete_assistant(&assistant.id).await?;
+        Ok(())
+    }
+}
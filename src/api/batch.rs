@@ -0,0 +1,592 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use derive_builder::Builder;
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+pub use crate::poll::PollConfig;
+
+use crate::{
+    chat_completion::{ChatCompletionRequest, ChatCompletionResponse},
+    create_embedding::{CreateEmbeddingRequest, CreateEmbeddingResponse},
+    pagination::ListParams,
+    IntoRequest,
+};
+
+/// A request that can be sent as a line of a batch input file.
+pub trait BatchRequestItem: Serialize {
+    /// The endpoint the request is sent to, e.g. "/v1/chat/completions".
+    const ENDPOINT: BatchEndpoint;
+
+    /// The response body of a successful request.
+    type Response: DeserializeOwned;
+}
+
+/// The requests of a batch, each of them keyed by a unique custom_id.
+#[derive(Debug, Clone)]
+pub struct BatchInput<R> {
+    items: Vec<(String, R)>,
+    ids: HashSet<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchInputLine<'a, R> {
+    custom_id: &'a str,
+    method: &'static str,
+    url: BatchEndpoint,
+    body: &'a R,
+}
+
+#[derive(Debug, Serialize, Clone, Builder)]
+#[builder(pattern = "mutable")]
+pub struct CreateBatchRequest {
+    /// The ID of an uploaded file that contains requests for the new batch.
+    #[builder(setter(into))]
+    input_file_id: String,
+
+    /// The endpoint to be used for all requests in the batch.
+    endpoint: BatchEndpoint,
+
+    /// The time frame within which the batch should be processed. Currently only 24h is supported.
+    #[builder(default)]
+    completion_window: BatchCompletionWindow,
+
+    /// Optional custom metadata for the batch.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetrieveBatchRequest {
+    batch_id: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct CancelBatchRequest {
+    batch_id: String,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ListBatchesRequest {
+    #[serde(flatten)]
+    params: ListParams,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum BatchEndpoint {
+    #[serde(rename = "/v1/chat/completions")]
+    ChatCompletions,
+
+    #[serde(rename = "/v1/embeddings")]
+    Embeddings,
+
+    #[serde(rename = "/v1/completions")]
+    Completions,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BatchCompletionWindow {
+    #[default]
+    #[serde(rename = "24h")]
+    Hours24,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Batch {
+    pub id: String,
+
+    /// The object type, which is always "batch".
+    pub object: String,
+
+    /// The OpenAI API endpoint used by the batch.
+    pub endpoint: BatchEndpoint,
+
+    #[serde(default)]
+    pub errors: Option<BatchErrors>,
+
+    /// The ID of the input file for the batch.
+    pub input_file_id: String,
+
+    /// The time frame within which the batch should be processed.
+    pub completion_window: BatchCompletionWindow,
+
+    /// The current status of the batch.
+    pub status: BatchStatus,
+
+    /// The ID of the file containing the outputs of successfully executed requests.
+    #[serde(default)]
+    pub output_file_id: Option<String>,
+
+    /// The ID of the file containing the outputs of requests with errors.
+    #[serde(default)]
+    pub error_file_id: Option<String>,
+
+    /// The Unix timestamp (in seconds) for when the batch was created.
+    pub created_at: u64,
+
+    #[serde(default)]
+    pub in_progress_at: Option<u64>,
+
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+
+    #[serde(default)]
+    pub finalizing_at: Option<u64>,
+
+    #[serde(default)]
+    pub completed_at: Option<u64>,
+
+    #[serde(default)]
+    pub failed_at: Option<u64>,
+
+    #[serde(default)]
+    pub expired_at: Option<u64>,
+
+    #[serde(default)]
+    pub cancelling_at: Option<u64>,
+
+    #[serde(default)]
+    pub cancelled_at: Option<u64>,
+
+    /// The request counts for different statuses within the batch.
+    #[serde(default)]
+    pub request_counts: Option<BatchRequestCounts>,
+
+    #[serde(default)]
+    pub metadata: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    /// The input file is being validated before the batch can begin.
+    Validating,
+    /// The input file has failed the validation process.
+    Failed,
+    /// The input file was successfully validated and the batch is currently being run.
+    InProgress,
+    /// The batch has completed and the results are being prepared.
+    Finalizing,
+    /// The batch has been completed and the results are ready.
+    Completed,
+    /// The batch was not able to be completed within the 24-hour time window.
+    Expired,
+    /// The batch is being cancelled (may take up to 10 minutes).
+    Cancelling,
+    /// The batch was cancelled.
+    Cancelled,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BatchErrors {
+    pub object: String,
+    pub data: Vec<BatchError>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BatchError {
+    /// An error code identifying the error type.
+    pub code: String,
+
+    /// A human-readable message providing more details about the error.
+    pub message: String,
+
+    /// The name of the parameter that caused the error, if applicable.
+    #[serde(default)]
+    pub param: Option<String>,
+
+    /// The line number of the input file where the error occurred, if applicable.
+    #[serde(default)]
+    pub line: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BatchRequestCounts {
+    /// Total number of requests in the batch.
+    pub total: usize,
+
+    /// Number of requests that have been completed successfully.
+    pub completed: usize,
+
+    /// Number of requests that have failed.
+    pub failed: usize,
+}
+
+/// The parsed output and error files of a batch, keyed by custom_id.
+#[derive(Debug, Clone)]
+pub struct BatchOutput<T> {
+    pub responses: HashMap<String, T>,
+    pub errors: HashMap<String, BatchRequestError>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BatchRequestError {
+    /// The HTTP status code of the failed request, if it reached the endpoint.
+    #[serde(default)]
+    pub status_code: Option<u16>,
+
+    #[serde(default)]
+    pub code: Option<String>,
+
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchOutputLine {
+    custom_id: String,
+    #[serde(default)]
+    response: Option<BatchOutputResponse>,
+    #[serde(default)]
+    error: Option<BatchRequestError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchOutputResponse {
+    status_code: u16,
+    body: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorBody {
+    error: BatchRequestError,
+}
+
+impl BatchRequestItem for ChatCompletionRequest {
+    const ENDPOINT: BatchEndpoint = BatchEndpoint::ChatCompletions;
+    type Response = ChatCompletionResponse;
+}
+
+impl BatchRequestItem for CreateEmbeddingRequest {
+    const ENDPOINT: BatchEndpoint = BatchEndpoint::Embeddings;
+    type Response = CreateEmbeddingResponse;
+}
+
+impl<R: BatchRequestItem> BatchInput<R> {
+    pub fn new() -> Self {
+        Self {
+            items: Vec::new(),
+            ids: HashSet::new(),
+        }
+    }
+
+    /// A batch of the requests, failing on the first duplicated custom_id like `add`.
+    pub fn try_from_iter(iter: impl IntoIterator<Item = (String, R)>) -> Result<Self> {
+        let mut input = Self::new();
+        for (custom_id, req) in iter {
+            input.add(custom_id, req)?;
+        }
+        Ok(input)
+    }
+
+    /// Add a request to the batch. The custom_id is used to match the output to the request,
+    /// so it must be unique within the batch.
+    pub fn add(&mut self, custom_id: impl Into<String>, req: R) -> Result<&mut Self> {
+        let custom_id = custom_id.into();
+        if !self.ids.insert(custom_id.clone()) {
+            return Err(anyhow!("duplicated custom_id: {}", custom_id));
+        }
+        self.items.push((custom_id, req));
+        Ok(self)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Serialize the requests into the JSONL format expected by the Batch API.
+    pub fn to_jsonl(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        for (custom_id, body) in &self.items {
+            let line = BatchInputLine {
+                custom_id,
+                method: "POST",
+                url: R::ENDPOINT,
+                body,
+            };
+            serde_json::to_writer(&mut buf, &line)?;
+            buf.push(b'\n');
+        }
+        Ok(buf)
+    }
+}
+
+impl<R: BatchRequestItem> Default for BatchInput<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CreateBatchRequest {
+    pub fn new(input_file_id: impl Into<String>, endpoint: BatchEndpoint) -> Self {
+        CreateBatchRequestBuilder::default()
+            .input_file_id(input_file_id)
+            .endpoint(endpoint)
+            .build()
+            .unwrap()
+    }
+}
+
+impl RetrieveBatchRequest {
+    pub fn new(batch_id: impl Into<String>) -> Self {
+        Self {
+            batch_id: batch_id.into(),
+        }
+    }
+}
+
+impl CancelBatchRequest {
+    pub fn new(batch_id: impl Into<String>) -> Self {
+        Self {
+            batch_id: batch_id.into(),
+        }
+    }
+}
+
+impl ListBatchesRequest {
+    pub fn new(params: ListParams) -> Self {
+        Self { params }
+    }
+}
+
+impl BatchStatus {
+    /// Whether the batch will not change its status anymore.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            Self::Failed | Self::Completed | Self::Expired | Self::Cancelled
+        )
+    }
+}
+
+impl<T: DeserializeOwned> BatchOutput<T> {
+    /// Parse the content of the output and error files of a batch.
+    pub fn parse(output: &[u8], errors: &[u8]) -> Result<Self> {
+        let mut ret = Self {
+            responses: HashMap::new(),
+            errors: HashMap::new(),
+        };
+        for line in output
+            .split(|b| *b == b'\n')
+            .chain(errors.split(|b| *b == b'\n'))
+        {
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            let line: BatchOutputLine = serde_json::from_slice(line)?;
+            match (line.response, line.error) {
+                (_, Some(error)) => {
+                    ret.errors.insert(line.custom_id, error);
+                }
+                (Some(res), None) if (200..300).contains(&res.status_code) => {
+                    ret.responses
+                        .insert(line.custom_id, serde_json::from_value(res.body)?);
+                }
+                (Some(res), None) => {
+                    let mut error = serde_json::from_value::<ErrorBody>(res.body)
+                        .map(|body| body.error)
+                        .unwrap_or_else(|_| BatchRequestError {
+                            status_code: None,
+                            code: None,
+                            message: "unknown error".to_owned(),
+                        });
+                    error.status_code = Some(res.status_code);
+                    ret.errors.insert(line.custom_id, error);
+                }
+                (None, None) => {
+                    return Err(anyhow!(
+                        "batch output for {} has neither response nor error",
+                        line.custom_id
+                    ))
+                }
+            }
+        }
+        Ok(ret)
+    }
+}
+
+impl IntoRequest for CreateBatchRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/batches");
+        client.post(url).json(&self)
+    }
+}
+
+impl IntoRequest for RetrieveBatchRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/batches/{}", self.batch_id);
+        client.get(url)
+    }
+}
+
+impl IntoRequest for CancelBatchRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/batches/{}/cancel", self.batch_id);
+        client.post(url)
+    }
+}
+
+impl IntoRequest for ListBatchesRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/batches");
+        client.get(url).query(&self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chat_completion::{ChatCompletionMessage, ChatCompletionRequestBuilder},
+        SDK,
+    };
+
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn batch_input_should_serialize_to_jsonl() -> Result<()> {
+        let mut input = BatchInput::new();
+        input.add("request-1", CreateEmbeddingRequest::new("hello"))?;
+        input.add("request-2", CreateEmbeddingRequest::new("world"))?;
+        assert!(input
+            .add("request-1", CreateEmbeddingRequest::new("again"))
+            .is_err());
+        assert!(BatchInput::try_from_iter([
+            ("a".to_owned(), CreateEmbeddingRequest::new("hello")),
+            ("a".to_owned(), CreateEmbeddingRequest::new("world")),
+        ])
+        .is_err());
+
+        let jsonl = String::from_utf8(input.to_jsonl()?)?;
+        let lines: Vec<serde_json::Value> = jsonl
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(
+            lines,
+            vec![
+                json!({
+                    "custom_id": "request-1",
+                    "method": "POST",
+                    "url": "/v1/embeddings",
+                    "body": {"input": "hello", "model": "text-embedding-ada-002"}
+                }),
+                json!({
+                    "custom_id": "request-2",
+                    "method": "POST",
+                    "url": "/v1/embeddings",
+                    "body": {"input": "world", "model": "text-embedding-ada-002"}
+                }),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn batch_output_should_parse() -> Result<()> {
+        let output = json!({
+            "id": "batch_req_1",
+            "custom_id": "request-1",
+            "response": {
+                "status_code": 200,
+                "request_id": "req_1",
+                "body": {
+                    "id": "chatcmpl-1",
+                    "object": "chat.completion",
+                    "created": 1711475054,
+                    "model": "gpt-3.5-turbo",
+                    "choices": [{
+                        "index": 0,
+                        "message": {"role": "assistant", "content": "Hello."},
+                        "finish_reason": "stop"
+                    }],
+                    "usage": {"prompt_tokens": 22, "completion_tokens": 2, "total_tokens": 24},
+                    "system_fingerprint": "fp_3bc1b5746c"
+                }
+            },
+            "error": null
+        });
+        let failed = json!({
+            "id": "batch_req_2",
+            "custom_id": "request-2",
+            "response": {
+                "status_code": 400,
+                "request_id": "req_2",
+                "body": {"error": {"message": "bad request", "type": "invalid_request_error", "code": null}}
+            },
+            "error": null
+        });
+        let expired = json!({
+            "id": "batch_req_3",
+            "custom_id": "request-3",
+            "response": null,
+            "error": {"code": "batch_expired", "message": "This request could not be executed before the completion window expired."}
+        });
+        let output = format!("{output}\n{failed}\n");
+        let errors = format!("{expired}\n");
+
+        let ret =
+            BatchOutput::<ChatCompletionResponse>::parse(output.as_bytes(), errors.as_bytes())?;
+        assert_eq!(ret.responses.len(), 1);
+        assert_eq!(ret.responses["request-1"].id, "chatcmpl-1");
+        assert_eq!(ret.errors.len(), 2);
+        assert_eq!(ret.errors["request-2"].status_code, Some(400));
+        assert_eq!(ret.errors["request-2"].message, "bad request");
+        assert_eq!(
+            ret.errors["request-3"].code.as_deref(),
+            Some("batch_expired")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn batch_should_deserialize() -> Result<()> {
+        let batch: Batch = serde_json::from_value(json!({
+            "id": "batch_abc123",
+            "object": "batch",
+            "endpoint": "/v1/chat/completions",
+            "errors": null,
+            "input_file_id": "file-abc123",
+            "completion_window": "24h",
+            "status": "completed",
+            "output_file_id": "file-cvaTdG",
+            "error_file_id": "file-HOWS94",
+            "created_at": 1711471533,
+            "in_progress_at": 1711471538,
+            "expires_at": 1711557933,
+            "finalizing_at": 1711493133,
+            "completed_at": 1711493163,
+            "failed_at": null,
+            "expired_at": null,
+            "cancelling_at": null,
+            "cancelled_at": null,
+            "request_counts": {"total": 100, "completed": 95, "failed": 5},
+            "metadata": {"customer_id": "user_123456789"}
+        }))?;
+        assert_eq!(batch.status, BatchStatus::Completed);
+        assert!(batch.status.is_terminal());
+        assert_eq!(batch.request_counts.unwrap().failed, 5);
+        Ok(())
+    }
+
+    #[ignore = "batches may take up to 24 hours to complete"]
+    #[tokio::test]
+    async fn run_batch_should_work() -> Result<()> {
+        let mut input = BatchInput::new();
+        for (i, question) in ["Where are you?", "Who are you?"].iter().enumerate() {
+            let req = ChatCompletionRequestBuilder::default()
+                .messages(vec![ChatCompletionMessage::new_user(*question, "")])
+                .build()?;
+            input.add(format!("request-{i}"), req)?;
+        }
+        let output = SDK.run_batch(input, PollConfig::default()).await?;
+        assert_eq!(output.responses.len(), 2);
+        assert!(output.errors.is_empty());
+        Ok(())
+    }
+}
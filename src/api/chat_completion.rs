@@ -1,50 +1,72 @@
+use std::collections::HashMap;
+
 use derive_builder::Builder;
 use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 
+#[cfg(not(feature = "tokenizer"))]
+use crate::rate_limiter::estimate_text_tokens;
+#[cfg(feature = "tokenizer")]
+use crate::tokenizer::{Encoding, Tokenizer};
 use crate::{IntoRequest, ToSchema};
 
 #[derive(Debug, Serialize, Clone, Builder)]
+#[builder(build_fn(validate = "Self::validate"))]
 pub struct ChatCompletionRequest {
     /// A list of messages comprising the conversation so far.
     #[builder(setter(into))]
-    messages: Vec<ChatCompletionMessage>,
+    pub(crate) messages: Vec<ChatCompletionMessage>,
 
     /// ID of the model to use. See the model endpoint compatibility table for details
     /// on which models work with the Chat API.
     #[builder(default)]
-    model: ChatCompletionModel,
+    pub(crate) model: ChatCompletionModel,
 
     /// Number between -2.0 and 2.0. Positive values penalize new tokens based on their
     /// existing frequency in the text so far, decreasing the model's likelihood to
     /// repeat the same line verbatim.
     #[builder(default, setter(strip_option))]
     #[serde(skip_serializing_if = "Option::is_none")]
-    frequency_penalty: Option<f32>,
-    // Modify the likelihood of specified tokens appearing in the completion.
-    //
-    // Accepts a JSON object that maps tokens (specified by their token ID in the tokenizer)
-    // to an associated bias value from -100 to 100. Mathematically, the bias is added to
-    // the logits generated by the model prior to sampling. The exact effect will vary per model,
-    // but values between -1 and 1 should decrease or increase likelihood of selection; values
-    // like -100 or 100 should result in a ban or exclusive selection of the relevant token.
-    // pub logit_bias: Option<f32>,
+    pub(crate) frequency_penalty: Option<f32>,
+
+    /// Modify the likelihood of specified tokens appearing in the completion.
+    ///
+    /// Maps tokens (specified by their token ID in the tokenizer) to an associated bias value from -100 to 100.
+    /// Mathematically, the bias is added to the logits generated by the model prior to sampling. The exact effect
+    /// will vary per model, but values between -1 and 1 should decrease or increase likelihood of selection; values
+    /// like -100 or 100 should result in a ban or exclusive selection of the relevant token.
+    #[builder(default, setter(into))]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub(crate) logit_bias: HashMap<u32, i8>,
+
+    /// Whether to return log probabilities of the output tokens or not. If true, returns the log probabilities
+    /// of each output token returned in the content of message.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) logprobs: Option<bool>,
+
+    /// An integer between 0 and 20 specifying the number of most likely tokens to return at each token position,
+    /// each with an associated log probability. logprobs must be set to true if this parameter is used.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) top_logprobs: Option<u8>,
+
     /// The maximum number of tokens to generate in the chat completion.
     ///
     /// The total length of input tokens and generated tokens is limited by the model's context length.
     #[builder(default, setter(strip_option))]
     #[serde(skip_serializing_if = "Option::is_none")]
-    max_tokens: Option<usize>,
+    pub(crate) max_tokens: Option<usize>,
 
     /// How many chat completion choices to generate for each input message. Note that you will be charged based on the number of generated tokens across all of the choices. Keep n as 1 to minimize costs.
     #[builder(default, setter(strip_option))]
     #[serde(skip_serializing_if = "Option::is_none")]
-    n: Option<usize>,
+    pub(crate) n: Option<usize>,
 
     /// Number between -2.0 and 2.0. Positive values penalize new tokens based on whether they appear in the text so far, increasing the model's likelihood to talk about new topics.
     #[builder(default, setter(strip_option))]
     #[serde(skip_serializing_if = "Option::is_none")]
-    presence_penalty: Option<f32>,
+    pub(crate) presence_penalty: Option<f32>,
 
     /// An object specifying the format that the model must output.
     ///
@@ -53,76 +75,102 @@ pub struct ChatCompletionRequest {
     /// Important: when using JSON mode, you must also instruct the model to produce JSON yourself via a system or user message. Without this, the model may generate an unending stream of whitespace until the generation reaches the token limit, resulting in a long-running and seemingly "stuck" request. Also note that the message content may be partially cut off if finish_reason="length", which indicates the generation exceeded max_tokens or the conversation exceeded the max context length.
     #[builder(default, setter(strip_option))]
     #[serde(skip_serializing_if = "Option::is_none")]
-    response_format: Option<ChatResponseFormatObject>,
+    pub(crate) response_format: Option<ChatResponseFormatObject>,
 
     /// This feature is in Beta. If specified, our system will make a best effort to sample deterministically, such that repeated requests with the same seed and parameters should return the same result. Determinism is not guaranteed, and you should refer to the system_fingerprint response parameter to monitor changes in the backend.
     #[builder(default, setter(strip_option))]
     #[serde(skip_serializing_if = "Option::is_none")]
-    seed: Option<usize>,
+    pub(crate) seed: Option<usize>,
 
     /// Up to 4 sequences where the API will stop generating further tokens.
-    /// TODO: make this as an enum
-    #[builder(default, setter(strip_option))]
+    #[builder(default, setter(strip_option, into))]
     #[serde(skip_serializing_if = "Option::is_none")]
-    stop: Option<String>,
+    pub(crate) stop: Option<Stop>,
 
     /// If set, partial message deltas will be sent, like in ChatGPT. Tokens will be sent as data-only server-sent events as they become available, with the stream terminated by a data: [DONE]
     #[builder(default, setter(strip_option))]
     #[serde(skip_serializing_if = "Option::is_none")]
-    stream: Option<bool>,
+    pub(crate) stream: Option<bool>,
 
     /// What sampling temperature to use, between 0 and 2. Higher values like 0.8 will make the output more random, while lower values like 0.2 will make it more focused and deterministic.
     ///
     /// We generally recommend altering this or top_p but not both.
     #[builder(default, setter(strip_option))]
     #[serde(skip_serializing_if = "Option::is_none")]
-    temperature: Option<f32>,
+    pub(crate) temperature: Option<f32>,
 
     /// An alternative to sampling with temperature, called nucleus sampling, where the model considers the results of the tokens with top_p probability mass. So 0.1 means only the tokens comprising the top 10% probability mass are considered.
     ///
     /// We generally recommend altering this or temperature but not both.
     #[builder(default, setter(strip_option))]
     #[serde(skip_serializing_if = "Option::is_none")]
-    top_p: Option<f32>,
+    pub(crate) top_p: Option<f32>,
 
     /// A list of tools the model may call. Currently, only functions are supported as a tool. Use this to provide a list of functions the model may generate JSON inputs for.
     #[builder(default, setter(into))]
     #[serde(skip_serializing_if = "Vec::is_empty")]
-    tools: Vec<Tool>,
+    pub(crate) tools: Vec<Tool>,
 
     /// Controls which (if any) function is called by the model. none means the model will not call a function and instead generates a message. auto means the model can pick between generating a message or calling a function. Specifying a particular function via {"type: "function", "function": {"name": "my_function"}} forces the model to call that function.
     #[builder(default, setter(strip_option))]
     #[serde(skip_serializing_if = "Option::is_none")]
-    tool_choice: Option<ToolChoice>,
+    pub(crate) tool_choice: Option<ToolChoice>,
+
+    /// Whether to enable parallel function calling during tool use.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) parallel_tool_calls: Option<bool>,
 
     /// A unique identifier representing your end-user, which can help OpenAI to monitor and detect abuse.
     #[builder(default, setter(strip_option, into))]
     #[serde(skip_serializing_if = "Option::is_none")]
-    user: Option<String>,
+    pub(crate) user: Option<String>,
 }
 
-#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize)]
-#[serde(rename_all = "snake_case")]
+/// One or many (up to 4) stop sequences.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum Stop {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
 pub enum ToolChoice {
+    /// The model will not call any tool and instead generates a message.
     #[default]
     None,
+    /// The model can pick between generating a message or calling one or more tools.
     Auto,
-    // TODO: we nned something like this: #[serde(tag = "type", content = "function")]
-    Function {
-        name: String,
-    },
+    /// The model must call one or more tools.
+    Required,
+    /// Forces the model to call the named function.
+    Function { name: String },
 }
 
-#[derive(Debug, Clone, Serialize)]
+/// The `{"type": "function", "function": {"name": ...}}` shape of `ToolChoice::Function`.
+#[derive(Serialize)]
+struct NamedToolChoice<'a> {
+    #[serde(rename = "type")]
+    typ: ToolType,
+    function: FunctionName<'a>,
+}
+
+#[derive(Serialize)]
+struct FunctionName<'a> {
+    name: &'a str,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tool {
     /// The type of the tool. Currently, only function is supported.
     #[serde(rename = "type")]
-    typ: ToolType,
+    pub(crate) typ: ToolType,
 
-    function: FunctionInfo,
+    pub(crate) function: FunctionInfo,
 }
 
-#[derive(Debug, Copy, Default, Clone, Serialize)]
+#[derive(Debug, Copy, Default, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ToolType {
     #[default]
@@ -146,24 +194,25 @@ impl Tool {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FunctionInfo {
     /// A description of what the function does, used by the model to choose when and how to call the function.
-    description: String,
+    #[serde(default)]
+    pub(crate) description: String,
 
     /// The name of the function to be called. Must be a-z, A-Z, 0-9, or contain underscores and dashes, with a maximum length of 64.
-    name: String,
+    pub(crate) name: String,
 
     /// The parameters the functions accepts, described as a JSON Schema object. See the guide for examples, and the JSON Schema reference for documentation about the format.
     ///
     /// To describe a function that accepts no parameters, provide the value {"type": "object", "properties": {}}.
-    parameters: serde_json::Value,
+    pub(crate) parameters: serde_json::Value,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct ChatResponseFormatObject {
     #[serde(rename = "type")]
-    typ: ChatResponseFormat,
+    pub(crate) typ: ChatResponseFormat,
 }
 
 #[derive(Debug, Default, PartialEq, Eq, Copy, Clone, Serialize)]
@@ -174,7 +223,7 @@ pub enum ChatResponseFormat {
     Text,
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Clone, Deserialize)]
 // #[serde(rename_all = "snake_case", tag = "role")] 表示生成的json结构为例如: {"role": "system", "content": "...", ...}, role就不需要重复写了
 #[serde(rename_all = "snake_case", tag = "role")]
 pub enum ChatCompletionMessage {
@@ -188,55 +237,55 @@ pub enum ChatCompletionMessage {
     Tool(ToolMessage),
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Clone, Deserialize)]
 pub struct SystemMessage {
     /// The contents of the system message.
-    content: String,
+    pub(crate) content: String,
 
     /// An optional name for the participant. Provides the model information to
     /// differentiate between participants of the same role.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub(crate) name: Option<String>,
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Clone, Deserialize)]
 pub struct UserMessage {
     /// The contents of the user message.
-    content: String,
+    pub(crate) content: String,
 
     /// An optional name for the participant. Provides the model information to differentiate between participants of the same role.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub(crate) name: Option<String>,
 }
 
 #[derive(Debug, Serialize, Clone, Deserialize)]
 pub struct AssistantMessage {
     /// The contents of the assistant message.
     #[serde(default)]
-    content: Option<String>,
+    pub(crate) content: Option<String>,
 
     /// An optional name for the participant. Provides the model information to
     /// differentiate between participants of the same role.
     #[serde(skip_serializing_if = "Option::is_none", default)]
-    name: Option<String>,
+    pub(crate) name: Option<String>,
 
     /// The tool calls generated by the model, such as function calls.
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
-    tool_calls: Vec<ToolCall>,
+    pub(crate) tool_calls: Vec<ToolCall>,
 }
 
 #[derive(Debug, Serialize, Clone, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub struct ToolCall {
     /// The ID of the tool call.
-    id: String,
+    pub(crate) id: String,
 
     /// The type of the tool. Currently, only function is supported.
-    #[serde(rename = "type")]
-    typ: ToolCallType,
+    #[serde(rename = "type", default)]
+    pub(crate) typ: ToolCallType,
 
     /// The function that the model called.
-    function: Function,
+    pub(crate) function: Function,
 }
 
 #[derive(Debug, Default, Copy, Deserialize, Serialize, Clone)]
@@ -249,29 +298,30 @@ pub enum ToolCallType {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Function {
     /// The name of the function to call.
-    name: String,
+    pub(crate) name: String,
 
     /// The arguments to call the function with, as generated by the model in JSON format.
     /// Note that the model does not always generate valid JSON, and may hallucinate parameters
     /// not defined by your function schema. Validate the arguments in your code before calling your function.
-    arguments: String,
+    pub(crate) arguments: String,
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Clone, Deserialize)]
 pub struct ToolMessage {
     /// The contents of the tool message.
-    content: String,
+    pub(crate) content: String,
 
     /// Tool call that this message is responding to.
-    tool_call_id: String,
+    pub(crate) tool_call_id: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
 pub enum ChatCompletionModel {
     #[default]
     #[serde(rename = "gpt-3.5-turbo")]
     Gpt3Turbo,
 
+    /// Only served by the legacy completions endpoint, use `completion::CompletionModel` with `LlmSdk::completion` instead.
     #[serde(rename = "gpt-3.5-turbo-instruct")]
     Gpt3TurboInstruct,
 
@@ -280,11 +330,16 @@ pub enum ChatCompletionModel {
 
     #[serde(rename = "gpt-4-1106-vision-preview")]
     Gpt4TurboVision,
+
+    /// Any other model id, e.g. "gpt-4o". Use `LlmSdk::list_models` to see what is available.
+    #[serde(untagged)]
+    Custom(String),
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct ChatCompletionResponse {
     /// A unique identifier for the chat completion.
+    #[serde(default)]
     pub id: String,
 
     /// A list of chat completion choices. Can be more than one if n is greater than 1.
@@ -299,13 +354,16 @@ pub struct ChatCompletionResponse {
     /// This fingerprint represents the backend configuration that the model runs with.
     ///
     /// Can be used in conjunction with the seed request parameter to understand when backend changes have been made that might impact determinism.
+    #[serde(default)]
     pub system_fingerprint: Option<String>,
 
     /// The object type, which is always chat.completion.
+    #[serde(default)]
     pub object: String,
 
-    /// Usage statistics for the completion request.
-    pub usage: ChatCompletionUsage,
+    /// Usage statistics for the completion request. Some OpenAI-compatible servers (e.g. llama.cpp) omit it.
+    #[serde(default)]
+    pub usage: Option<ChatCompletionUsage>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -323,6 +381,7 @@ pub struct ChatCompletionUsage {
 #[derive(Debug, Deserialize, Clone)]
 pub struct ChatCompletionChoice {
     /// The reason the model stopped generating tokens. This will be stop if the model hit a natural stop point or a provided stop sequence, length if the maximum number of tokens specified in the request was reached, content_filter if content was omitted due to a flag from our content filters, tool_calls if the model called a tool, or function_call (deprecated) if the model called a function.
+    #[serde(default, deserialize_with = "null_as_default")]
     pub finish_reason: FinishReason,
 
     /// The index of the choice in the list of choices.
@@ -330,9 +389,52 @@ pub struct ChatCompletionChoice {
 
     /// A chat completion message generated by the model.
     pub message: AssistantMessage,
+
+    /// Log probability information for the choice, if logprobs was requested.
+    #[serde(default)]
+    pub logprobs: Option<ChoiceLogprobs>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ChoiceLogprobs {
+    /// Log probability information for each message content token.
+    #[serde(default)]
+    pub content: Option<Vec<TokenLogprob>>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TokenLogprob {
+    /// The token.
+    pub token: String,
+
+    /// The log probability of this token.
+    pub logprob: f64,
+
+    /// The UTF-8 bytes of the token. Useful when characters are represented by multiple tokens
+    /// and their byte representations must be combined. None if the token has no bytes representation.
+    #[serde(default)]
+    pub bytes: Option<Vec<u8>>,
+
+    /// The most likely tokens at this position, with their log probabilities. May contain fewer
+    /// than top_logprobs entries.
+    #[serde(default)]
+    pub top_logprobs: Vec<TopLogprob>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TopLogprob {
+    /// The token.
+    pub token: String,
+
+    /// The log probability of this token.
+    pub logprob: f64,
+
+    /// The UTF-8 bytes of the token.
+    #[serde(default)]
+    pub bytes: Option<Vec<u8>>,
 }
 
-#[derive(Debug, Default, Deserialize, PartialEq, Eq, Copy, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Copy, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum FinishReason {
     #[default]
@@ -340,6 +442,221 @@ pub enum FinishReason {
     Length,
     ContentFilter,
     ToolCalls,
+    /// A reason this SDK doesn't know, e.g. sent by an OpenAI-compatible local server.
+    #[serde(other)]
+    Other,
+}
+
+impl ChatCompletionRequestBuilder {
+    fn validate(&self) -> Result<(), String> {
+        if let Some(Some(stop)) = &self.stop {
+            stop.validate()?;
+        }
+        if let Some(logit_bias) = &self.logit_bias {
+            if let Some((token, bias)) = logit_bias.iter().find(|(_, b)| !(-100..=100).contains(*b))
+            {
+                return Err(format!(
+                    "logit_bias for token {token} must be between -100 and 100, got {bias}"
+                ));
+            }
+        }
+        if let Some(Some(top_logprobs)) = self.top_logprobs {
+            if top_logprobs > 20 {
+                return Err(format!(
+                    "top_logprobs must be between 0 and 20, got {top_logprobs}"
+                ));
+            }
+            if self.logprobs != Some(Some(true)) {
+                return Err("top_logprobs requires logprobs to be true".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ChatCompletionRequest {
+    /// The tokens the request counts against a tokens-per-minute limit: the prompt plus max_tokens per choice.
+    pub(crate) fn estimated_tokens(&self) -> usize {
+        #[cfg(feature = "tokenizer")]
+        let prompt = self.estimate_prompt_tokens();
+        #[cfg(not(feature = "tokenizer"))]
+        let prompt = self
+            .messages
+            .iter()
+            .map(|m| estimate_text_tokens(m.content().unwrap_or_default()) + 3)
+            .sum::<usize>()
+            + 3;
+        prompt + self.max_tokens.unwrap_or_default() * self.n.unwrap_or(1)
+    }
+}
+
+#[cfg(feature = "tokenizer")]
+impl ChatCompletionRequest {
+    /// Estimate the prompt tokens of the request with the model's tokenizer, following OpenAI's
+    /// counting guide: 3 tokens of overhead per message, 1 per name, 3 to prime the reply, plus the
+    /// tool definitions as the model sees them.
+    pub fn estimate_prompt_tokens(&self) -> usize {
+        let tokenizer = Encoding::for_model(&self.model).tokenizer();
+        let messages: usize = self
+            .messages
+            .iter()
+            .map(|m| m.estimate_tokens(tokenizer))
+            .sum();
+        messages + 3 + estimate_tools_tokens(&self.tools, tokenizer)
+    }
+}
+
+/// Tool definitions are rendered to the model as a typescript-like namespace, this mirrors the
+/// per-function, per-property and per-enum overheads measured for the gpt-3.5 / gpt-4 / gpt-4o families.
+#[cfg(feature = "tokenizer")]
+fn estimate_tools_tokens(tools: &[Tool], tokenizer: &Tokenizer) -> usize {
+    const FUNC_INIT: isize = 7;
+    const PROP_INIT: isize = 3;
+    const PROP_KEY: isize = 3;
+    const ENUM_INIT: isize = -3;
+    const ENUM_ITEM: isize = 3;
+    const FUNC_END: isize = 12;
+
+    if tools.is_empty() {
+        return 0;
+    }
+    let count = |text: &str| tokenizer.count_tokens(text) as isize;
+    let mut tokens = FUNC_END;
+    for tool in tools {
+        let f = &tool.function;
+        tokens += FUNC_INIT;
+        tokens += count(&format!(
+            "{}:{}",
+            f.name,
+            f.description.trim_end_matches('.')
+        ));
+        let Some(properties) = f.parameters["properties"].as_object() else {
+            continue;
+        };
+        if properties.is_empty() {
+            continue;
+        }
+        tokens += PROP_INIT;
+        for (name, prop) in properties {
+            tokens += PROP_KEY;
+            if let Some(items) = prop["enum"].as_array() {
+                tokens += ENUM_INIT;
+                for item in items {
+                    tokens += ENUM_ITEM;
+                    tokens += match item.as_str() {
+                        Some(item) => count(item),
+                        None => count(&item.to_string()),
+                    };
+                }
+            }
+            let typ = prop["type"].as_str().unwrap_or_default();
+            let description = prop["description"].as_str().unwrap_or_default();
+            tokens += count(&format!(
+                "{name}:{typ}:{}",
+                description.trim_end_matches('.')
+            ));
+        }
+    }
+    tokens.max(0) as usize
+}
+
+impl Stop {
+    /// The API accepts at most 4 stop sequences.
+    pub const MAX: usize = 4;
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        match self {
+            Stop::Many(stops) if stops.is_empty() => {
+                Err("stop must contain at least one sequence".to_string())
+            }
+            Stop::Many(stops) if stops.len() > Self::MAX => Err(format!(
+                "at most {} stop sequences, got {}",
+                Self::MAX,
+                stops.len()
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<String> for Stop {
+    fn from(value: String) -> Self {
+        Self::One(value)
+    }
+}
+
+impl From<&str> for Stop {
+    fn from(value: &str) -> Self {
+        Self::One(value.to_owned())
+    }
+}
+
+impl From<Vec<String>> for Stop {
+    fn from(value: Vec<String>) -> Self {
+        Self::Many(value)
+    }
+}
+
+impl From<Vec<&str>> for Stop {
+    fn from(value: Vec<&str>) -> Self {
+        Self::Many(value.into_iter().map(|s| s.to_owned()).collect())
+    }
+}
+
+impl ToolChoice {
+    pub fn function(name: impl Into<String>) -> Self {
+        Self::Function { name: name.into() }
+    }
+}
+
+impl Serialize for ToolChoice {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ToolChoice::None => serializer.serialize_str("none"),
+            ToolChoice::Auto => serializer.serialize_str("auto"),
+            ToolChoice::Required => serializer.serialize_str("required"),
+            ToolChoice::Function { name } => NamedToolChoice {
+                typ: ToolType::Function,
+                function: FunctionName { name },
+            }
+            .serialize(serializer),
+        }
+    }
+}
+
+impl ChoiceLogprobs {
+    /// The per-token log probabilities of the message content.
+    pub fn tokens(&self) -> &[TokenLogprob] {
+        self.content.as_deref().unwrap_or_default()
+    }
+
+    /// The probability of the whole content, i.e. exp of the summed token log probabilities.
+    pub fn probability(&self) -> f64 {
+        self.tokens().iter().map(|t| t.logprob).sum::<f64>().exp()
+    }
+}
+
+impl TokenLogprob {
+    /// The linear probability of this token.
+    pub fn probability(&self) -> f64 {
+        self.logprob.exp()
+    }
+}
+
+impl TopLogprob {
+    /// The linear probability of this token.
+    pub fn probability(&self) -> f64 {
+        self.logprob.exp()
+    }
+}
+
+/// Treat an explicit null like a missing field, as some OpenAI-compatible servers send them.
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
 }
 
 impl IntoRequest for ChatCompletionRequest {
@@ -358,6 +675,89 @@ impl SystemMessage {
     }
 }
 
+impl AssistantMessage {
+    /// A plain text assistant message, e.g. to replay a conversation or provide few-shot examples.
+    pub fn new(content: impl Into<String>) -> Self {
+        Self {
+            content: Some(content.into()),
+            name: None,
+            tool_calls: vec![],
+        }
+    }
+
+    /// The contents of the assistant message, None if the model called tools instead.
+    pub fn content(&self) -> Option<&str> {
+        self.content.as_deref()
+    }
+
+    /// The tool calls generated by the model.
+    pub fn tool_calls(&self) -> &[ToolCall] {
+        &self.tool_calls
+    }
+}
+
+impl ToolCall {
+    /// The ID of the tool call, to be referenced by the ToolMessage answering it.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The name of the function to call.
+    pub fn name(&self) -> &str {
+        &self.function.name
+    }
+
+    /// The arguments to call the function with, in JSON format.
+    pub fn arguments(&self) -> &str {
+        &self.function.arguments
+    }
+}
+
+impl ToolMessage {
+    /// Tool call that this message is responding to.
+    pub fn tool_call_id(&self) -> &str {
+        &self.tool_call_id
+    }
+}
+
+impl From<AssistantMessage> for ChatCompletionMessage {
+    fn from(message: AssistantMessage) -> Self {
+        ChatCompletionMessage::Assistant(message)
+    }
+}
+
+impl ChatCompletionModel {
+    /// The context window (prompt + completion) of the model, in tokens.
+    ///
+    /// Custom models are matched by their id prefix. Unknown ones get 128k, the window of most
+    /// current models: set `ChatSession::with_token_budget` for models with a smaller one.
+    pub fn context_window(&self) -> usize {
+        match self {
+            ChatCompletionModel::Gpt3Turbo => 16_385,
+            ChatCompletionModel::Gpt3TurboInstruct => 4_096,
+            ChatCompletionModel::Gpt4Turbo | ChatCompletionModel::Gpt4TurboVision => 128_000,
+            ChatCompletionModel::Custom(id) => match id.as_str() {
+                id if id.starts_with("gpt-5") => 400_000,
+                id if id.starts_with("gpt-4.1") => 1_047_576,
+                id if id.starts_with("o1-mini") || id.starts_with("o1-preview") => 128_000,
+                id if id.starts_with("o1") || id.starts_with("o3") || id.starts_with("o4") => {
+                    200_000
+                }
+                id if id.starts_with("gpt-4-32k") => 32_768,
+                id if id == "gpt-4"
+                    || id.starts_with("gpt-4-0613")
+                    || id.starts_with("gpt-4-0314") =>
+                {
+                    8_192
+                }
+                id if id.starts_with("gpt-3.5-turbo-instruct") => 4_096,
+                id if id.starts_with("gpt-3.5-turbo") => 16_385,
+                _ => 128_000,
+            },
+        }
+    }
+}
+
 impl ChatCompletionMessage {
     pub fn new_system(content: impl Into<String>, name: &str) -> Self {
         ChatCompletionMessage::System(SystemMessage {
@@ -373,6 +773,60 @@ impl ChatCompletionMessage {
         })
     }
 
+    pub fn new_assistant(content: impl Into<String>) -> Self {
+        ChatCompletionMessage::Assistant(AssistantMessage::new(content))
+    }
+
+    /// The result of a tool call, to be sent back to the model.
+    pub fn new_tool(tool_call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        ChatCompletionMessage::Tool(ToolMessage {
+            content: content.into(),
+            tool_call_id: tool_call_id.into(),
+        })
+    }
+
+    /// The tokens this message adds to a prompt, including the chat format overhead.
+    #[cfg(feature = "tokenizer")]
+    pub fn estimate_tokens(&self, tokenizer: &Tokenizer) -> usize {
+        const TOKENS_PER_MESSAGE: usize = 3;
+        const TOKENS_PER_NAME: usize = 1;
+
+        let (role, name) = match self {
+            ChatCompletionMessage::System(m) => ("system", m.name.as_deref()),
+            ChatCompletionMessage::User(m) => ("user", m.name.as_deref()),
+            ChatCompletionMessage::Assistant(m) => ("assistant", m.name.as_deref()),
+            ChatCompletionMessage::Tool(_) => ("tool", None),
+        };
+        let mut tokens = TOKENS_PER_MESSAGE + tokenizer.count_tokens(role);
+        tokens += tokenizer.count_tokens(self.content().unwrap_or_default());
+        if let Some(name) = name {
+            tokens += TOKENS_PER_NAME + tokenizer.count_tokens(name);
+        }
+        match self {
+            ChatCompletionMessage::Assistant(m) => {
+                for call in &m.tool_calls {
+                    tokens += tokenizer.count_tokens(&call.function.name)
+                        + tokenizer.count_tokens(&call.function.arguments);
+                }
+            }
+            ChatCompletionMessage::Tool(m) => {
+                tokens += tokenizer.count_tokens(&m.tool_call_id);
+            }
+            _ => {}
+        }
+        tokens
+    }
+
+    /// The text content of the message, if any.
+    pub fn content(&self) -> Option<&str> {
+        match self {
+            ChatCompletionMessage::System(m) => Some(&m.content),
+            ChatCompletionMessage::User(m) => Some(&m.content),
+            ChatCompletionMessage::Assistant(m) => m.content.as_deref(),
+            ChatCompletionMessage::Tool(m) => Some(&m.content),
+        }
+    }
+
     fn get_name(name: &str) -> Option<String> {
         if name.is_empty() {
             None
@@ -431,7 +885,6 @@ mod tests {
     }
 
     #[test]
-    #[ignore]
     fn chat_completion_request_tool_choice_function_serilize_should_work() {
         let req = ChatCompletionRequestBuilder::default()
             .tool_choice(ToolChoice::Function {
@@ -446,6 +899,7 @@ mod tests {
             json,
             serde_json::json!({
                 "messages": [],
+                "model": "gpt-3.5-turbo",
                 "tool_choice": {
                     "type": "function",
                     "function":  {
@@ -475,6 +929,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn chat_completion_request_tool_choice_required_serilize_should_work() {
+        let req = ChatCompletionRequestBuilder::default()
+            .tool_choice(ToolChoice::Required)
+            .parallel_tool_calls(false)
+            .messages(vec![])
+            .build()
+            .unwrap();
+
+        let json = serde_json::to_value(req).unwrap();
+        assert_eq!(json["tool_choice"], "required");
+        assert_eq!(json["parallel_tool_calls"], false);
+    }
+
+    #[test]
+    fn chat_completion_request_stop_should_work() {
+        let build = |stop: Stop| {
+            ChatCompletionRequestBuilder::default()
+                .messages(vec![])
+                .stop(stop)
+                .build()
+        };
+        let req = build("\n".into()).unwrap();
+        assert_eq!(serde_json::to_value(req).unwrap()["stop"], "\n");
+        let req = build(vec!["\n", "END"].into()).unwrap();
+        assert_eq!(
+            serde_json::to_value(req).unwrap()["stop"],
+            serde_json::json!(["\n", "END"])
+        );
+        assert!(build(vec!["a", "b", "c", "d", "e"].into()).is_err());
+        assert!(build(Vec::<String>::new().into()).is_err());
+    }
+
     #[test]
     fn chat_completion_request_serilize_should_work() {
         let req = gen_simple_completion_request();
@@ -530,6 +1017,150 @@ mod tests {
         );
     }
 
+    #[test]
+    fn chat_completion_message_should_round_trip() {
+        let json = serde_json::json!([
+            {"role": "system", "content": "You are a weather bot."},
+            {"role": "user", "content": "What is the weather like in Boston?", "name": "user1"},
+            {
+                "role": "assistant",
+                "content": null,
+                "tool_calls": [{
+                    "id": "call_1",
+                    "type": "function",
+                    "function": {"name": "get_weather_forecast", "arguments": "{\"city\":\"Boston\"}"}
+                }]
+            },
+            {"role": "tool", "content": "22", "tool_call_id": "call_1"},
+            {"role": "assistant", "content": "It is 22 degrees in Boston."}
+        ]);
+        let messages: Vec<ChatCompletionMessage> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(messages.len(), 5);
+        assert!(matches!(messages[2], ChatCompletionMessage::Assistant(_)));
+        assert_eq!(messages[4].content(), Some("It is 22 degrees in Boston."));
+        assert_eq!(serde_json::to_value(&messages).unwrap(), json);
+    }
+
+    #[test]
+    fn chat_completion_request_with_logprobs_should_serialize() {
+        let req = ChatCompletionRequestBuilder::default()
+            .messages(vec![ChatCompletionMessage::new_user(
+                "Positive or negative?",
+                "",
+            )])
+            .logit_bias(HashMap::from([(35127, 100), (43324, 100)]))
+            .logprobs(true)
+            .top_logprobs(2)
+            .max_tokens(1)
+            .build()
+            .unwrap();
+        let json = serde_json::to_value(req).unwrap();
+        assert_eq!(
+            json["logit_bias"],
+            serde_json::json!({"35127": 100, "43324": 100})
+        );
+        assert_eq!(json["logprobs"], true);
+        assert_eq!(json["top_logprobs"], 2);
+    }
+
+    #[test]
+    fn chat_completion_request_should_validate_logprobs() {
+        let builder = || {
+            let mut builder = ChatCompletionRequestBuilder::default();
+            builder.messages(vec![]);
+            builder
+        };
+        assert!(builder()
+            .logit_bias(HashMap::from([(1, -101)]))
+            .build()
+            .is_err());
+        assert!(builder().top_logprobs(2).build().is_err());
+        assert!(builder().logprobs(true).top_logprobs(21).build().is_err());
+        assert!(builder().logprobs(true).top_logprobs(20).build().is_ok());
+    }
+
+    #[test]
+    fn chat_completion_choice_logprobs_should_deserialize() {
+        let choice: ChatCompletionChoice = serde_json::from_value(serde_json::json!({
+            "index": 0,
+            "finish_reason": "length",
+            "message": {"role": "assistant", "content": "positive"},
+            "logprobs": {
+                "content": [{
+                    "token": "positive",
+                    "logprob": -0.1,
+                    "bytes": [112, 111, 115, 105, 116, 105, 118, 101],
+                    "top_logprobs": [
+                        {"token": "positive", "logprob": -0.1, "bytes": [112, 111, 115, 105, 116, 105, 118, 101]},
+                        {"token": "negative", "logprob": -2.4, "bytes": null}
+                    ]
+                }]
+            }
+        }))
+        .unwrap();
+        let logprobs = choice.logprobs.unwrap();
+        let token = &logprobs.tokens()[0];
+        assert_eq!(token.bytes.as_deref(), Some("positive".as_bytes()));
+        assert_eq!(token.top_logprobs[1].token, "negative");
+        assert!((logprobs.probability() - (-0.1f64).exp()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn chat_completion_response_from_local_servers_should_deserialize() {
+        // llama.cpp: no system_fingerprint, no usage, its own finish reason
+        let res: ChatCompletionResponse = serde_json::from_value(serde_json::json!({
+            "id": "chatcmpl-local",
+            "object": "chat.completion",
+            "created": 1700000000,
+            "model": "qwen2.5-7b-instruct",
+            "choices": [
+                {"index": 0, "finish_reason": "eos", "message": {"role": "assistant", "content": "hi"}},
+                {"index": 1, "finish_reason": null, "message": {"role": "assistant", "content": "hey"}}
+            ]
+        }))
+        .unwrap();
+        assert!(res.usage.is_none());
+        assert!(res.system_fingerprint.is_none());
+        assert_eq!(res.choices[0].finish_reason, FinishReason::Other);
+        assert_eq!(res.choices[1].finish_reason, FinishReason::Stop);
+    }
+
+    #[test]
+    fn chat_completion_model_context_window_should_match_model_family() {
+        let window = |id: &str| ChatCompletionModel::Custom(id.to_owned()).context_window();
+        assert_eq!(window("gpt-4"), 8_192);
+        assert_eq!(window("gpt-4-32k-0613"), 32_768);
+        assert_eq!(window("gpt-4o-mini"), 128_000);
+        assert_eq!(window("gpt-4.1-nano"), 1_047_576);
+        assert_eq!(window("o1-mini"), 128_000);
+        assert_eq!(window("o1"), 200_000);
+        assert_eq!(window("o3-mini"), 200_000);
+        assert_eq!(window("o4-mini"), 200_000);
+        assert_eq!(window("gpt-5-mini"), 400_000);
+        assert_eq!(window("some-new-model"), 128_000);
+    }
+
+    #[cfg(feature = "tokenizer")]
+    #[test]
+    fn chat_completion_request_should_estimate_prompt_tokens() {
+        let req = ChatCompletionRequestBuilder::default()
+            .messages(vec![
+                ChatCompletionMessage::new_system("You are a helpful assistant.", ""),
+                ChatCompletionMessage::new_user("Hello!", ""),
+            ])
+            .build()
+            .unwrap();
+        assert_eq!(req.estimate_prompt_tokens(), 19);
+
+        let req = gen_tool_completion_request();
+        let messages = req
+            .messages
+            .iter()
+            .map(|m| m.estimate_tokens(crate::tokenizer::Encoding::Cl100kBase.tokenizer()))
+            .sum::<usize>();
+        assert!(req.estimate_prompt_tokens() > messages + 3 + 2 * 7 + 12);
+    }
+
     #[tokio::test]
     async fn simple_chat_completion_should_work() -> anyhow::Result<()> {
         let req = gen_simple_completion_request();
@@ -0,0 +1,395 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chat_completion::{
+        AssistantMessage, ChatCompletionMessage, ChatCompletionModel, ChatCompletionRequest,
+        ChatCompletionRequestBuilder, ChatCompletionResponse, Tool,
+    },
+    LlmSdk,
+};
+
+#[cfg(feature = "tokenizer")]
+use crate::tokenizer::Encoding;
+
+const DEFAULT_REPLY_TOKENS: usize = 1024;
+
+const SUMMARIZE_PROMPT: &str = "Summarize the following conversation between a user and an \
+assistant. Keep every fact, decision and open question that later turns may rely on. Answer \
+with the summary only.";
+
+/// A conversation with a model: the system prompt, the history and the tools, kept within
+/// the model's context window.
+///
+/// The session serializes to JSON so it can be persisted between requests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatSession {
+    model: ChatCompletionModel,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+
+    /// Summary of the turns dropped by `summarize`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    summary: Option<String>,
+
+    #[serde(default)]
+    history: Vec<ChatCompletionMessage>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<Tool>,
+
+    /// Overrides the model's context window.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    token_budget: Option<usize>,
+
+    /// Tokens kept free in the context window for the reply.
+    #[serde(default = "default_reply_tokens")]
+    reply_tokens: usize,
+}
+
+impl ChatSession {
+    pub fn new(model: ChatCompletionModel) -> Self {
+        Self {
+            model,
+            system: None,
+            summary: None,
+            history: vec![],
+            tools: vec![],
+            token_budget: None,
+            reply_tokens: DEFAULT_REPLY_TOKENS,
+        }
+    }
+
+    pub fn with_system(mut self, system: impl Into<String>) -> Self {
+        self.system = Some(system.into());
+        self
+    }
+
+    pub fn with_tools(mut self, tools: Vec<Tool>) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    /// Limit the whole context (prompt + reply) to this many tokens instead of the model's context window.
+    pub fn with_token_budget(mut self, tokens: usize) -> Self {
+        self.token_budget = Some(tokens);
+        self
+    }
+
+    /// Keep this many tokens of the context window free for the reply (1024 by default).
+    pub fn with_reply_tokens(mut self, tokens: usize) -> Self {
+        self.reply_tokens = tokens;
+        self
+    }
+
+    pub fn model(&self) -> &ChatCompletionModel {
+        &self.model
+    }
+
+    /// The conversation so far, without the system prompt.
+    pub fn history(&self) -> &[ChatCompletionMessage] {
+        &self.history
+    }
+
+    /// The messages to send: system prompt, summary of dropped turns, then the history.
+    pub fn messages(&self) -> Vec<ChatCompletionMessage> {
+        let mut messages = Vec::with_capacity(self.history.len() + 2);
+        if let Some(system) = &self.system {
+            messages.push(ChatCompletionMessage::new_system(system, ""));
+        }
+        if let Some(summary) = &self.summary {
+            messages.push(ChatCompletionMessage::new_system(
+                format!("Summary of the earlier conversation:\n{summary}"),
+                "",
+            ));
+        }
+        messages.extend(self.history.iter().cloned());
+        messages
+    }
+
+    pub fn push(&mut self, message: impl Into<ChatCompletionMessage>) {
+        self.history.push(message.into());
+    }
+
+    pub fn push_user(&mut self, content: impl Into<String>) {
+        self.push(ChatCompletionMessage::new_user(content, ""));
+    }
+
+    /// Append the reply of the model (first choice), returning it.
+    pub fn push_response(&mut self, res: &ChatCompletionResponse) -> Option<&AssistantMessage> {
+        let message = res.choices.first()?.message.clone();
+        self.push(message);
+        match self.history.last() {
+            Some(ChatCompletionMessage::Assistant(m)) => Some(m),
+            _ => None,
+        }
+    }
+
+    /// Append the result of a tool call requested by the last assistant message.
+    pub fn push_tool_result(
+        &mut self,
+        tool_call_id: impl Into<String>,
+        content: impl Into<String>,
+    ) {
+        self.push(ChatCompletionMessage::new_tool(tool_call_id, content));
+    }
+
+    /// The number of tokens available for the prompt.
+    pub fn prompt_budget(&self) -> usize {
+        self.token_budget
+            .unwrap_or_else(|| self.model.context_window())
+            .saturating_sub(self.reply_tokens)
+    }
+
+    /// An estimate of the prompt size in tokens, counted with the model's tokenizer when the
+    /// `tokenizer` feature is enabled and at about 4 characters per token otherwise.
+    pub fn estimated_tokens(&self) -> usize {
+        estimate_prompt_tokens(self)
+    }
+
+    /// Drop the oldest turns until the prompt fits the budget, returning the number of messages removed.
+    ///
+    /// Whole turns are dropped (a user message and everything up to the next one), so tool results are
+    /// never separated from the assistant message that requested them. The last turn is always kept.
+    pub fn trim(&mut self) -> usize {
+        let budget = self.prompt_budget();
+        let mut total = self.estimated_tokens();
+        if total <= budget {
+            return 0;
+        }
+        // each message is estimated once, dropping a turn subtracts its messages
+        let tokens = estimate_history_tokens(self);
+        let mut removed = 0;
+        while total > budget {
+            let Some(next) = self.next_turn(removed + 1) else {
+                break;
+            };
+            total -= tokens[removed..next].iter().sum::<usize>();
+            removed = next;
+        }
+        self.history.drain(..removed);
+        removed
+    }
+
+    /// Replace all but the last `keep` messages (rounded to whole turns) with a summary written by the model.
+    pub async fn summarize(&mut self, sdk: &LlmSdk, keep: usize) -> Result<()> {
+        // keep whole turns: move the split back to the start of the turn it falls into
+        let split = match self.history.len().checked_sub(keep) {
+            Some(from) if keep > 0 => (0..=from)
+                .rev()
+                .find(|i| matches!(self.history[*i], ChatCompletionMessage::User(_)))
+                .unwrap_or(0),
+            Some(from) => from,
+            None => 0,
+        };
+        if split == 0 {
+            return Ok(());
+        }
+
+        let mut transcript = String::new();
+        if let Some(summary) = &self.summary {
+            transcript.push_str(&format!("(earlier) {summary}\n"));
+        }
+        for message in &self.history[..split] {
+            transcript.push_str(&render(message));
+            transcript.push('\n');
+        }
+        let req = ChatCompletionRequestBuilder::default()
+            .model(self.model.clone())
+            .messages(vec![
+                ChatCompletionMessage::new_system(SUMMARIZE_PROMPT, ""),
+                ChatCompletionMessage::new_user(transcript, ""),
+            ])
+            .build()?;
+        let res = sdk.chat_completion(req).await?;
+        let summary = res
+            .choices
+            .first()
+            .and_then(|c| c.message.content())
+            .ok_or_else(|| anyhow!("model returned no summary"))?;
+
+        self.summary = Some(summary.to_owned());
+        self.history.drain(..split);
+        Ok(())
+    }
+
+    /// A request builder with the model, tools and messages of the session, to add further options.
+    pub fn request(&self) -> ChatCompletionRequestBuilder {
+        let mut builder = ChatCompletionRequestBuilder::default();
+        builder
+            .model(self.model.clone())
+            .messages(self.messages())
+            .tools(self.tools.clone());
+        builder
+    }
+
+    /// Trim the history, send it and append the reply to the session.
+    pub async fn send(&mut self, sdk: &LlmSdk) -> Result<ChatCompletionResponse> {
+        self.trim();
+        let req: ChatCompletionRequest = self.request().build()?;
+        let res = sdk.chat_completion(req).await?;
+        self.push_response(&res);
+        Ok(res)
+    }
+
+    /// Index of the first user message at or after `from`.
+    fn next_turn(&self, from: usize) -> Option<usize> {
+        (from..self.history.len())
+            .find(|i| matches!(self.history[*i], ChatCompletionMessage::User(_)))
+    }
+}
+
+fn default_reply_tokens() -> usize {
+    DEFAULT_REPLY_TOKENS
+}
+
+#[cfg(feature = "tokenizer")]
+fn estimate_prompt_tokens(session: &ChatSession) -> usize {
+    session
+        .request()
+        .build()
+        .map(|req| req.estimate_prompt_tokens())
+        .unwrap_or_default()
+}
+
+#[cfg(not(feature = "tokenizer"))]
+fn estimate_prompt_tokens(session: &ChatSession) -> usize {
+    session.messages().iter().map(estimate_tokens).sum()
+}
+
+/// The tokens of each history message, counted the same way as `estimate_prompt_tokens`.
+#[cfg(feature = "tokenizer")]
+fn estimate_history_tokens(session: &ChatSession) -> Vec<usize> {
+    let tokenizer = Encoding::for_model(&session.model).tokenizer();
+    session
+        .history
+        .iter()
+        .map(|m| m.estimate_tokens(tokenizer))
+        .collect()
+}
+
+#[cfg(not(feature = "tokenizer"))]
+fn estimate_history_tokens(session: &ChatSession) -> Vec<usize> {
+    session.history.iter().map(estimate_tokens).collect()
+}
+
+/// About 4 characters per token, plus the per-message overhead of the chat format.
+#[cfg(not(feature = "tokenizer"))]
+fn estimate_tokens(message: &ChatCompletionMessage) -> usize {
+    let mut chars = message.content().map(|c| c.len()).unwrap_or_default();
+    if let ChatCompletionMessage::Assistant(m) = message {
+        chars += m
+            .tool_calls()
+            .iter()
+            .map(|c| c.name().len() + c.arguments().len())
+            .sum::<usize>();
+    }
+    chars.div_ceil(4) + 4
+}
+
+fn render(message: &ChatCompletionMessage) -> String {
+    let role = match message {
+        ChatCompletionMessage::System(_) => "system",
+        ChatCompletionMessage::User(_) => "user",
+        ChatCompletionMessage::Assistant(_) => "assistant",
+        ChatCompletionMessage::Tool(_) => "tool",
+    };
+    let mut s = format!("{role}: {}", message.content().unwrap_or_default());
+    if let ChatCompletionMessage::Assistant(m) = message {
+        for call in m.tool_calls() {
+            s.push_str(&format!(" [called {}({})]", call.name(), call.arguments()));
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SDK;
+    use serde_json::json;
+
+    fn session() -> ChatSession {
+        let mut session = ChatSession::new(ChatCompletionModel::Gpt3Turbo)
+            .with_system("You are a weather bot.")
+            .with_token_budget(200)
+            .with_reply_tokens(100);
+        session.push_user("What is the weather like in Boston?");
+        session.push(
+            serde_json::from_value::<ChatCompletionMessage>(json!({
+                "role": "assistant",
+                "content": null,
+                "tool_calls": [{
+                    "id": "call_1",
+                    "type": "function",
+                    "function": {"name": "get_weather_forecast", "arguments": "{\"city\":\"Boston\"}"}
+                }]
+            }))
+            .unwrap(),
+        );
+        session.push_tool_result("call_1", "22");
+        session.push(AssistantMessage::new("It is 22 degrees in Boston."));
+        session
+    }
+
+    #[test]
+    fn chat_session_should_keep_history() {
+        let session = session();
+        let messages = session.messages();
+        assert_eq!(messages.len(), 5);
+        assert_eq!(messages[0].content(), Some("You are a weather bot."));
+        assert!(matches!(messages[3], ChatCompletionMessage::Tool(_)));
+        assert!(session.estimated_tokens() < session.prompt_budget());
+    }
+
+    #[test]
+    fn chat_session_should_trim_whole_turns() {
+        let mut session = session();
+        session.push_user("lorem ipsum dolor ".repeat(60));
+        assert!(session.estimated_tokens() > session.prompt_budget());
+
+        assert_eq!(session.trim(), 4);
+        assert_eq!(session.history().len(), 1);
+        assert!(matches!(
+            session.history()[0],
+            ChatCompletionMessage::User(_)
+        ));
+
+        // the last turn is kept even if it does not fit
+        session.push_user("y".repeat(1000));
+        session.trim();
+        assert_eq!(session.history().len(), 1);
+    }
+
+    #[test]
+    fn chat_session_should_round_trip_json() {
+        let session = session();
+        let json = serde_json::to_value(&session).unwrap();
+        assert_eq!(json["model"], "gpt-3.5-turbo");
+        assert_eq!(json["history"].as_array().unwrap().len(), 4);
+
+        let restored: ChatSession = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&restored).unwrap(), json);
+
+        let minimal: ChatSession = serde_json::from_value(json!({"model": "gpt-4o-mini"})).unwrap();
+        assert_eq!(minimal.prompt_budget(), 128_000 - DEFAULT_REPLY_TOKENS);
+    }
+
+    #[tokio::test]
+    async fn chat_session_should_work() -> anyhow::Result<()> {
+        let mut session =
+            ChatSession::new(ChatCompletionModel::Gpt3Turbo).with_system("Answer in one word.");
+        session.push_user("What is the capital of France?");
+        session.send(&SDK).await?;
+        session.push_user("And of Germany?");
+        let res = session.send(&SDK).await?;
+        assert_eq!(session.history().len(), 4);
+        assert!(res.choices[0]
+            .message
+            .content()
+            .unwrap_or_default()
+            .contains("Berlin"));
+        Ok(())
+    }
+}
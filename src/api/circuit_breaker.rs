@@ -0,0 +1,417 @@
+//! Fail fast on endpoints that keep failing instead of piling up retries and timeouts on them.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use reqwest::{Method, StatusCode, Url};
+use tokio::time::Instant;
+
+/// Beyond this many circuits, the closed circuits without recent failures are forgotten.
+const MAX_CIRCUITS: usize = 1024;
+
+/// A circuit per endpoint (method, host and route, with the ids in the path replaced by `{id}`,
+/// so all the files share a circuit). It opens after `failure_threshold` failed
+/// attempts within `window`, then requests to the endpoint fail with [`CircuitOpenError`] without
+/// being sent. After `open_duration` one probe request is let through: its success closes the
+/// circuit, its failure opens it again.
+///
+/// Failures are transport errors, timeouts and 408/5xx responses. A 429 is not a failure: the
+/// upstream is up, and the key pool or rate limiter deal with it.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: usize,
+    window: Duration,
+    open_duration: Duration,
+    circuits: Mutex<HashMap<String, Circuit>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are sent.
+    Closed,
+    /// Requests fail fast.
+    Open,
+    /// A probe request decides whether the circuit closes or opens again.
+    HalfOpen,
+}
+
+/// The error of a request that wasn't sent because the circuit of its endpoint is open.
+#[derive(Debug, Clone)]
+pub struct CircuitOpenError {
+    pub endpoint: String,
+    /// When the next probe request will be let through.
+    pub retry_in: Duration,
+}
+
+#[derive(Debug)]
+struct Circuit {
+    state: CircuitState,
+    failures: VecDeque<Instant>,
+    opened_at: Instant,
+    probing: bool,
+}
+
+/// An attempt let through by the breaker. Report its outcome with `finish`, dropping it (e.g. when
+/// the request is cancelled) lets another probe through.
+pub(crate) struct Attempt {
+    breaker: Arc<CircuitBreaker>,
+    endpoint: String,
+    probe: bool,
+    finished: bool,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            window: Duration::from_secs(60),
+            open_duration: Duration::from_secs(30),
+            circuits: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl CircuitBreaker {
+    /// 5 failures within 60 seconds open a circuit for 30 seconds.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_failure_threshold(mut self, failures: usize) -> Self {
+        self.failure_threshold = failures.max(1);
+        self
+    }
+
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// How long an open circuit fails fast before probing the endpoint.
+    pub fn with_open_duration(mut self, duration: Duration) -> Self {
+        self.open_duration = duration;
+        self
+    }
+
+    pub fn into_shared(self) -> Arc<Self> {
+        Arc::new(self)
+    }
+
+    /// The state of the endpoint's circuit, e.g. "POST api.openai.com/v1/chat/completions" or
+    /// "GET api.openai.com/v1/files/{id}".
+    pub fn state(&self, endpoint: &str) -> CircuitState {
+        let circuits = self.circuits.lock().unwrap();
+        circuits
+            .get(endpoint)
+            .map(|c| c.state)
+            .unwrap_or(CircuitState::Closed)
+    }
+
+    /// The endpoints seen so far with the state of their circuit.
+    pub fn states(&self) -> Vec<(String, CircuitState)> {
+        let circuits = self.circuits.lock().unwrap();
+        let mut states: Vec<_> = circuits
+            .iter()
+            .map(|(endpoint, c)| (endpoint.clone(), c.state))
+            .collect();
+        states.sort_by(|a, b| a.0.cmp(&b.0));
+        states
+    }
+
+    /// Let an attempt to the endpoint through, unless its circuit is open.
+    pub(crate) fn try_attempt(
+        self: &Arc<Self>,
+        endpoint: &str,
+    ) -> Result<Attempt, CircuitOpenError> {
+        let now = Instant::now();
+        let mut circuits = self.circuits.lock().unwrap();
+        if circuits.len() >= MAX_CIRCUITS && !circuits.contains_key(endpoint) {
+            circuits.retain(|_, c| c.state != CircuitState::Closed || !c.failures.is_empty());
+        }
+        let circuit = circuits
+            .entry(endpoint.to_owned())
+            .or_insert_with(|| Circuit {
+                state: CircuitState::Closed,
+                failures: VecDeque::new(),
+                opened_at: now,
+                probing: false,
+            });
+        if circuit.state == CircuitState::Open {
+            let elapsed = now.saturating_duration_since(circuit.opened_at);
+            if elapsed < self.open_duration {
+                return Err(CircuitOpenError {
+                    endpoint: endpoint.to_owned(),
+                    retry_in: self.open_duration - elapsed,
+                });
+            }
+            circuit.state = CircuitState::HalfOpen;
+            circuit.probing = false;
+            tracing::info!(endpoint, "circuit half-open");
+        }
+        let probe = circuit.state == CircuitState::HalfOpen;
+        if probe {
+            if circuit.probing {
+                return Err(CircuitOpenError {
+                    endpoint: endpoint.to_owned(),
+                    retry_in: Duration::ZERO,
+                });
+            }
+            circuit.probing = true;
+        }
+        Ok(Attempt {
+            breaker: self.clone(),
+            endpoint: endpoint.to_owned(),
+            probe,
+            finished: false,
+        })
+    }
+
+    fn record(&self, endpoint: &str, probe: bool, success: bool) {
+        let now = Instant::now();
+        let mut circuits = self.circuits.lock().unwrap();
+        let Some(circuit) = circuits.get_mut(endpoint) else {
+            return;
+        };
+        if probe {
+            circuit.probing = false;
+        }
+        match (circuit.state, success) {
+            (CircuitState::HalfOpen, true) if probe => {
+                circuit.state = CircuitState::Closed;
+                circuit.failures.clear();
+                tracing::info!(endpoint, "circuit closed");
+            }
+            (CircuitState::HalfOpen, false) if probe => {
+                circuit.state = CircuitState::Open;
+                circuit.opened_at = now;
+                tracing::warn!(endpoint, "circuit opened again, probe failed");
+            }
+            (CircuitState::Closed, false) => {
+                circuit.failures.push_back(now);
+                while circuit
+                    .failures
+                    .front()
+                    .is_some_and(|t| now.saturating_duration_since(*t) > self.window)
+                {
+                    circuit.failures.pop_front();
+                }
+                if circuit.failures.len() >= self.failure_threshold {
+                    circuit.state = CircuitState::Open;
+                    circuit.opened_at = now;
+                    circuit.failures.clear();
+                    tracing::warn!(
+                        endpoint,
+                        failures = self.failure_threshold,
+                        "circuit opened"
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The endpoint of a request, as circuits are keyed.
+pub(crate) fn endpoint(method: &Method, url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    let route = route(url.path());
+    match url.port() {
+        Some(port) => format!("{method} {host}:{port}{route}"),
+        None => format!("{method} {host}{route}"),
+    }
+}
+
+/// The path with its ids replaced by `{id}`.
+pub(crate) fn route(path: &str) -> String {
+    path.split('/')
+        .map(|segment| if is_id(segment) { "{id}" } else { segment })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Route segments are lowercase words, ids (and model names) have digits or uppercase letters.
+fn is_id(segment: &str) -> bool {
+    let version = segment
+        .strip_prefix('v')
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()));
+    !version
+        && segment
+            .bytes()
+            .any(|b| b.is_ascii_digit() || b.is_ascii_uppercase())
+}
+
+impl Attempt {
+    /// Report the outcome of the attempt: a response with `status`, or a transport error.
+    pub(crate) fn finish(mut self, status: Option<StatusCode>) {
+        self.finished = true;
+        let success =
+            status.is_some_and(|s| !(s.is_server_error() || s == StatusCode::REQUEST_TIMEOUT));
+        self.breaker.record(&self.endpoint, self.probe, success);
+    }
+}
+
+impl Drop for Attempt {
+    fn drop(&mut self) {
+        if self.probe && !self.finished {
+            let mut circuits = self.breaker.circuits.lock().unwrap();
+            if let Some(circuit) = circuits.get_mut(&self.endpoint) {
+                circuit.probing = false;
+            }
+        }
+    }
+}
+
+impl fmt::Display for CircuitOpenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "circuit open for {}, retry in {:?}",
+            self.endpoint, self.retry_in
+        )
+    }
+}
+
+impl std::error::Error for CircuitOpenError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        stub::{StubResponse, StubServer},
+        LlmSdk,
+    };
+    use serde_json::json;
+
+    const ENDPOINT: &str = "POST api.example.com/v1/chat/completions";
+
+    fn fail(breaker: &Arc<CircuitBreaker>) {
+        breaker
+            .try_attempt(ENDPOINT)
+            .unwrap()
+            .finish(Some(StatusCode::SERVICE_UNAVAILABLE));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn circuit_breaker_should_open_half_open_and_close() {
+        let breaker = CircuitBreaker::new()
+            .with_failure_threshold(3)
+            .with_window(Duration::from_secs(10))
+            .with_open_duration(Duration::from_secs(30))
+            .into_shared();
+
+        // failures outside the window don't count
+        fail(&breaker);
+        fail(&breaker);
+        tokio::time::advance(Duration::from_secs(11)).await;
+        fail(&breaker);
+        // a 429 isn't a failure
+        breaker
+            .try_attempt(ENDPOINT)
+            .unwrap()
+            .finish(Some(StatusCode::TOO_MANY_REQUESTS));
+        assert_eq!(breaker.state(ENDPOINT), CircuitState::Closed);
+        fail(&breaker);
+        fail(&breaker);
+        assert_eq!(breaker.state(ENDPOINT), CircuitState::Open);
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        let err = breaker.try_attempt(ENDPOINT).err().unwrap();
+        assert_eq!(err.retry_in, Duration::from_secs(20));
+
+        // one probe at a time, its failure opens the circuit again
+        tokio::time::advance(Duration::from_secs(20)).await;
+        let probe = breaker.try_attempt(ENDPOINT).unwrap();
+        assert_eq!(breaker.state(ENDPOINT), CircuitState::HalfOpen);
+        assert!(breaker.try_attempt(ENDPOINT).is_err());
+        probe.finish(None);
+        assert_eq!(breaker.state(ENDPOINT), CircuitState::Open);
+
+        // a cancelled probe lets another one through, whose success closes the circuit
+        tokio::time::advance(Duration::from_secs(30)).await;
+        drop(breaker.try_attempt(ENDPOINT).unwrap());
+        let probe = breaker.try_attempt(ENDPOINT).unwrap();
+        probe.finish(Some(StatusCode::OK));
+        assert_eq!(breaker.state(ENDPOINT), CircuitState::Closed);
+        assert_eq!(
+            breaker.states(),
+            vec![(ENDPOINT.to_owned(), CircuitState::Closed)]
+        );
+    }
+
+    #[test]
+    fn endpoint_should_template_ids() {
+        let endpoint = |method: Method, url: &str| endpoint(&method, &Url::parse(url).unwrap());
+        assert_eq!(
+            endpoint(
+                Method::GET,
+                "https://api.openai.com/v1/files/file-AbC123/content"
+            ),
+            "GET api.openai.com/v1/files/{id}/content"
+        );
+        assert_eq!(
+            endpoint(
+                Method::POST,
+                "https://api.openai.com/v1/threads/thread_abc1/runs/run_xyz2/cancel"
+            ),
+            "POST api.openai.com/v1/threads/{id}/runs/{id}/cancel"
+        );
+        assert_eq!(
+            endpoint(
+                Method::POST,
+                "http://localhost:8080/v1/fine_tuning/jobs?limit=2"
+            ),
+            "POST localhost:8080/v1/fine_tuning/jobs"
+        );
+    }
+
+    #[test]
+    fn circuit_breaker_should_forget_idle_circuits() {
+        let breaker = CircuitBreaker::new().into_shared();
+        fail(&breaker);
+        for i in 0..MAX_CIRCUITS {
+            breaker
+                .try_attempt(&format!("GET api.example.com/{i}"))
+                .unwrap()
+                .finish(Some(StatusCode::OK));
+        }
+        // the circuit with a failure is kept
+        let states = breaker.states();
+        assert_eq!(states.len(), 2);
+        assert!(states.iter().any(|(e, _)| e == ENDPOINT));
+    }
+
+    #[tokio::test]
+    async fn sdk_should_fail_fast_when_circuit_is_open() {
+        let server =
+            StubServer::start(|_| StubResponse::json(503, json!({"error": "overloaded"}))).await;
+        let breaker = CircuitBreaker::new()
+            .with_failure_threshold(1)
+            .into_shared();
+        let sdk = LlmSdk::new(server.url(), "token", 3).with_circuit_breaker(breaker.clone());
+
+        // each attempt counts: the retries of the first request stop once the circuit opens
+        let err = sdk.list_models().await.unwrap_err();
+        assert!(err.downcast_ref::<CircuitOpenError>().is_some(), "{err}");
+        assert_eq!(server.requests().len(), 1);
+
+        let err = sdk.list_models().await.unwrap_err();
+        let err = err.downcast_ref::<CircuitOpenError>().unwrap();
+        assert!(
+            err.endpoint.starts_with("GET 127.0.0.1:"),
+            "{}",
+            err.endpoint
+        );
+        assert!(err.endpoint.ends_with("/models"), "{}", err.endpoint);
+        assert_eq!(server.requests().len(), 1);
+        assert_eq!(breaker.state(&err.endpoint), CircuitState::Open);
+
+        // an SDK without the breaker isn't affected
+        let sdk = LlmSdk::new(server.url(), "token", 0);
+        let err = sdk.list_models().await.unwrap_err();
+        assert!(err.downcast_ref::<crate::meta::ApiError>().is_some());
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+
+use derive_builder::Builder;
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chat_completion::{ChatCompletionUsage, FinishReason, Stop},
+    rate_limiter::estimate_text_tokens,
+    IntoRequest,
+};
+
+/// The legacy text completions endpoint. Only instruct and base models (gpt-3.5-turbo-instruct,
+/// davinci-002, babbage-002) are served here, chat models must go through `chat_completion`.
+#[derive(Debug, Serialize, Clone, Builder)]
+#[builder(pattern = "mutable", build_fn(validate = "Self::validate"))]
+pub struct CompletionRequest {
+    /// ID of the model to use.
+    #[builder(default)]
+    pub(crate) model: CompletionModel,
+
+    /// The prompt(s) to generate completions for, encoded as a string or array of strings.
+    #[builder(setter(into))]
+    pub(crate) prompt: CompletionPrompt,
+
+    /// The suffix that comes after a completion of inserted text. Used for fill-in-the-middle.
+    #[builder(default, setter(strip_option, into))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suffix: Option<String>,
+
+    /// The maximum number of tokens that can be generated in the completion. Defaults to 16.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) max_tokens: Option<usize>,
+
+    /// What sampling temperature to use, between 0 and 2.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) temperature: Option<f32>,
+
+    /// An alternative to sampling with temperature, called nucleus sampling.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) top_p: Option<f32>,
+
+    /// How many completions to generate for each prompt.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    n: Option<usize>,
+
+    /// Generates best_of completions server-side and returns the "best" (the one with the highest log probability per token).
+    ///
+    /// When used with n, best_of controls the number of candidate completions and n specifies how many to return – best_of must be greater than n.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    best_of: Option<usize>,
+
+    /// Include the log probabilities on the logprobs most likely output tokens, as well the chosen tokens. The maximum value for logprobs is 5.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logprobs: Option<u8>,
+
+    /// Echo back the prompt in addition to the completion.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    echo: Option<bool>,
+
+    /// Up to 4 sequences where the API will stop generating further tokens. The returned text will not contain the stop sequence.
+    #[builder(default, setter(strip_option, into))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Stop>,
+
+    /// Number between -2.0 and 2.0. Positive values penalize new tokens based on whether they appear in the text so far.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+
+    /// Number between -2.0 and 2.0. Positive values penalize new tokens based on their existing frequency in the text so far.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+
+    /// If specified, our system will make a best effort to sample deterministically.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) seed: Option<usize>,
+
+    /// A unique identifier representing your end-user, which can help OpenAI to monitor and detect abuse.
+    #[builder(default, setter(strip_option, into))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<String>,
+
+    /// Set by `LlmSdk::completion_stream`, tokens are then sent back as server-sent events.
+    #[builder(setter(skip))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum CompletionPrompt {
+    String(String),
+    StringArray(Vec<String>),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub enum CompletionModel {
+    #[default]
+    #[serde(rename = "gpt-3.5-turbo-instruct")]
+    Gpt3TurboInstruct,
+
+    #[serde(rename = "davinci-002")]
+    Davinci002,
+
+    #[serde(rename = "babbage-002")]
+    Babbage002,
+
+    /// Any other model id, e.g. a fine-tuned davinci-002.
+    #[serde(untagged)]
+    Custom(String),
+}
+
+/// A completion response, or one chunk of it when streaming.
+#[derive(Debug, Deserialize, Clone)]
+pub struct CompletionResponse {
+    /// A unique identifier for the completion. All chunks of a stream share the same id.
+    pub id: String,
+
+    /// The object type, which is always "text_completion".
+    pub object: String,
+
+    /// The Unix timestamp (in seconds) of when the completion was created.
+    pub created: usize,
+
+    /// The model used for completion.
+    pub model: String,
+
+    /// This fingerprint represents the backend configuration that the model runs with.
+    #[serde(default)]
+    pub system_fingerprint: Option<String>,
+
+    /// The list of completion choices the model generated for the input prompt.
+    pub choices: Vec<CompletionChoice>,
+
+    /// Usage statistics for the completion request. Not sent with streamed chunks.
+    #[serde(default)]
+    pub usage: Option<ChatCompletionUsage>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CompletionChoice {
+    /// The generated text (or the delta of it when streaming).
+    pub text: String,
+
+    /// The index of the choice in the list of choices.
+    pub index: usize,
+
+    /// The log probabilities of the generated tokens, if requested.
+    #[serde(default)]
+    pub logprobs: Option<CompletionLogprobs>,
+
+    /// The reason the model stopped generating tokens. None for stream chunks until the last one.
+    #[serde(default)]
+    pub finish_reason: Option<FinishReason>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct CompletionLogprobs {
+    /// The generated tokens.
+    #[serde(default)]
+    pub tokens: Vec<String>,
+
+    /// The log probability of each token. The first token of an echoed prompt has none.
+    #[serde(default)]
+    pub token_logprobs: Vec<Option<f64>>,
+
+    /// The most likely alternatives for each token, with their log probabilities.
+    #[serde(default)]
+    pub top_logprobs: Vec<Option<HashMap<String, f64>>>,
+
+    /// The character offset of each token in the text.
+    #[serde(default)]
+    pub text_offset: Vec<usize>,
+}
+
+impl CompletionRequest {
+    pub fn new(model: CompletionModel, prompt: impl Into<CompletionPrompt>) -> Self {
+        CompletionRequestBuilder::default()
+            .model(model)
+            .prompt(prompt)
+            .build()
+            .unwrap()
+    }
+
+    /// The tokens the request counts against a tokens-per-minute limit: the prompts plus max_tokens
+    /// (16 by default) per generated completion.
+    pub(crate) fn estimated_tokens(&self) -> usize {
+        let prompts = match &self.prompt {
+            CompletionPrompt::String(s) => std::slice::from_ref(s),
+            CompletionPrompt::StringArray(v) => v.as_slice(),
+        };
+        let prompt: usize = prompts.iter().map(|p| estimate_text_tokens(p)).sum();
+        let completions = self.best_of.or(self.n).unwrap_or(1) * prompts.len();
+        prompt + self.max_tokens.unwrap_or(16) * completions
+    }
+
+    pub(crate) fn model(&self) -> &CompletionModel {
+        &self.model
+    }
+
+    pub(crate) fn into_stream(mut self) -> Self {
+        self.stream = Some(true);
+        self
+    }
+}
+
+impl CompletionRequestBuilder {
+    fn validate(&self) -> Result<(), String> {
+        if let Some(Some(stop)) = &self.stop {
+            stop.validate()?;
+        }
+        if let Some(Some(logprobs)) = self.logprobs {
+            if logprobs > 5 {
+                return Err(format!("logprobs must be at most 5, got {logprobs}"));
+            }
+        }
+        if let Some(Some(best_of)) = self.best_of {
+            let n = self.n.flatten().unwrap_or(1);
+            if best_of < n {
+                return Err(format!("best_of ({best_of}) must not be less than n ({n})"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl CompletionResponse {
+    /// The text of the first choice.
+    pub fn text(&self) -> &str {
+        self.choices.first().map(|c| c.text.as_str()).unwrap_or("")
+    }
+}
+
+impl From<String> for CompletionPrompt {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<&str> for CompletionPrompt {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_owned())
+    }
+}
+
+impl From<Vec<String>> for CompletionPrompt {
+    fn from(value: Vec<String>) -> Self {
+        Self::StringArray(value)
+    }
+}
+
+impl IntoRequest for CompletionRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/completions");
+        client.post(url).json(&self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SDK;
+    use anyhow::Result;
+    use futures::TryStreamExt;
+    use serde_json::json;
+
+    #[test]
+    fn completion_request_should_serialize() -> Result<()> {
+        let req = CompletionRequestBuilder::default()
+            .prompt("def add(a, b):\n")
+            .suffix("\n\nprint(add(1, 2))")
+            .max_tokens(32)
+            .stop(vec!["\n\n"])
+            .build()?;
+        assert_eq!(
+            serde_json::to_value(req.into_stream())?,
+            json!({
+                "model": "gpt-3.5-turbo-instruct",
+                "prompt": "def add(a, b):\n",
+                "suffix": "\n\nprint(add(1, 2))",
+                "max_tokens": 32,
+                "stop": ["\n\n"],
+                "stream": true,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn completion_request_should_be_validated() {
+        let stop: Vec<String> = (0..5).map(|i| i.to_string()).collect();
+        assert!(CompletionRequestBuilder::default()
+            .prompt("hi")
+            .stop(stop)
+            .build()
+            .is_err());
+        assert!(CompletionRequestBuilder::default()
+            .prompt("hi")
+            .logprobs(6)
+            .build()
+            .is_err());
+        assert!(CompletionRequestBuilder::default()
+            .prompt("hi")
+            .n(3)
+            .best_of(2)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn completion_chunk_should_deserialize() -> Result<()> {
+        let chunk: CompletionResponse = serde_json::from_value(json!({
+            "id": "cmpl-abc123",
+            "object": "text_completion",
+            "created": 1700000000,
+            "model": "gpt-3.5-turbo-instruct",
+            "choices": [{
+                "text": " return",
+                "index": 0,
+                "logprobs": {
+                    "tokens": [" return"],
+                    "token_logprobs": [-0.01],
+                    "top_logprobs": [{" return": -0.01}],
+                    "text_offset": [15]
+                },
+                "finish_reason": null
+            }]
+        }))?;
+        assert_eq!(chunk.text(), " return");
+        assert!(chunk.usage.is_none());
+        assert!(chunk.choices[0].finish_reason.is_none());
+        assert_eq!(chunk.choices[0].logprobs.as_ref().unwrap().tokens.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn completion_stream_should_work() -> Result<()> {
+        let req = CompletionRequestBuilder::default()
+            .prompt("Say this is a test")
+            .max_tokens(16)
+            .temperature(0.0)
+            .build()?;
+        let chunks: Vec<_> = SDK.completion_stream(req).await?.try_collect().await?;
+        let text: String = chunks.iter().map(|c| c.text()).collect();
+        assert!(text.to_lowercase().contains("test"));
+        Ok(())
+    }
+}
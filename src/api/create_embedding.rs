@@ -2,27 +2,27 @@ use derive_builder::Builder;
 use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
 use serde::{Deserialize, Serialize};
 
-use crate::IntoRequest;
+use crate::{rate_limiter::estimate_text_tokens, IntoRequest};
 
 #[derive(Debug, Serialize, Clone, Builder)]
 #[builder(pattern = "mutable")]
 pub struct CreateEmbeddingRequest {
     /// Input text to embed, encoded as a string or array of tokens. To embed multiple inputs in a single request, pass an array of strings or array of token arrays. The input must not exceed the max input tokens for the model (8192 tokens for text-embedding-ada-002), cannot be an empty string,
     #[builder(setter(into))]
-    input: EmbeddingInput,
+    pub(crate) input: EmbeddingInput,
 
     /// ID of the model to use. You can use the List models API to see all of your available models
     #[builder(default)]
-    model: EmbeddingModel,
+    pub(crate) model: EmbeddingModel,
 
     #[builder(default, setter(strip_option))]
     #[serde(skip_serializing_if = "Option::is_none")] // 如果为None, 序列化的时候就不序列化它
-    encoding_format: Option<EmbeddingEncodingFormat>,
+    pub(crate) encoding_format: Option<EmbeddingEncodingFormat>,
 
     #[builder(default, setter(strip_option, into))]
     // setter(strip_option, into) 设置的时候去掉Option, into 就是如果传了 &str, 就自动执行它的into函数, 变成String
     #[serde(skip_serializing_if = "Option::is_none")] // 如果为None, 序列化的时候就不序列化它
-    user: Option<String>,
+    pub(crate) user: Option<String>,
 }
 
 // currently we don't support array of integers, or array of array of integers.
@@ -43,7 +43,7 @@ pub enum EmbeddingEncodingFormat {
     Base64,
 }
 
-#[derive(Debug, Default, Clone, Copy, Deserialize, PartialEq, Eq, Serialize)]
+#[derive(Debug, Default, Clone, Deserialize, PartialEq, Eq, Serialize)]
 pub enum EmbeddingModel {
     #[default]
     #[serde(rename = "text-embedding-ada-002")]
@@ -51,6 +51,10 @@ pub enum EmbeddingModel {
 
     #[serde(rename = "text-embedding-ada-002-v2")]
     TextEmbeddingAda002V2,
+
+    /// Any other model id, e.g. "text-embedding-3-small".
+    #[serde(untagged)]
+    Custom(String),
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -93,6 +97,14 @@ impl CreateEmbeddingRequest {
             .build()
             .unwrap()
     }
+
+    /// The tokens the request counts against a tokens-per-minute limit.
+    pub(crate) fn estimated_tokens(&self) -> usize {
+        match &self.input {
+            EmbeddingInput::String(s) => estimate_text_tokens(s),
+            EmbeddingInput::StringArray(v) => v.iter().map(|s| estimate_text_tokens(s)).sum(),
+        }
+    }
 }
 
 impl From<String> for EmbeddingInput {
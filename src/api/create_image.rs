@@ -1,8 +1,16 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bytes::Bytes;
 use derive_builder::Builder;
 use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
 use serde::{Deserialize, Serialize};
 
-use crate::IntoRequest;
+use crate::{IntoRequest, LlmSdk, TIMEOUT};
 
 #[derive(Debug, Serialize, Clone, Builder)]
 #[builder(pattern = "mutable")]
@@ -59,13 +67,24 @@ impl CreateImageRequest {
             .build()
             .unwrap()
     }
+
+    pub(crate) fn model(&self) -> &ImageModel {
+        &self.model
+    }
 }
 
-#[derive(Debug, Default, Serialize, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub enum ImageModel {
     #[default]
     #[serde(rename = "dall-e-3")]
     DallE3,
+
+    #[serde(rename = "dall-e-2")]
+    DallE2,
+
+    /// Any other model id.
+    #[serde(untagged)]
+    Custom(String),
 }
 
 #[derive(Debug, Default, Serialize, Clone, Copy, PartialEq, Eq)]
@@ -120,7 +139,74 @@ pub struct ImageObject {
     pub url: Option<String>,
 
     /// The prompt that was used to generate the image, if there was any revision to the prompt.
-    pub revised_prompt: String,
+    /// dall-e-2 never revises the prompt, so it is absent for those responses.
+    #[serde(default)]
+    pub revised_prompt: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Webp,
+    Gif,
+}
+
+impl ImageObject {
+    /// Get the raw image data. If the image was returned as b64_json, it is decoded locally,
+    /// otherwise it is downloaded from the url, without the API key and within the sdk's timeout.
+    pub async fn bytes(&self, sdk: &LlmSdk) -> Result<Bytes> {
+        if let Some(b64) = &self.b64_json {
+            return Ok(STANDARD.decode(b64)?.into());
+        }
+        let Some(url) = &self.url else {
+            return Err(anyhow!("image object has neither b64_json nor url"));
+        };
+        let res = sdk
+            .download_client
+            .get(url)
+            .timeout(Duration::from_secs(TIMEOUT))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(res.bytes().await?)
+    }
+
+    /// Save the image to the given path. If the path has no extension, the one matching the
+    /// detected image format is appended. Returns the path the image was written to.
+    pub async fn save_to(&self, sdk: &LlmSdk, path: impl AsRef<Path>) -> Result<PathBuf> {
+        let data = self.bytes(sdk).await?;
+        let mut path = path.as_ref().to_path_buf();
+        if path.extension().is_none() {
+            let format = ImageFormat::detect(&data)
+                .ok_or_else(|| anyhow!("unknown image format for {}", path.display()))?;
+            path.set_extension(format.extension());
+        }
+        tokio::fs::write(&path, data).await?;
+        Ok(path)
+    }
+}
+
+impl ImageFormat {
+    /// Detect the image format from the magic bytes at the start of the data.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        match data {
+            [0x89, b'P', b'N', b'G', ..] => Some(Self::Png),
+            [0xff, 0xd8, 0xff, ..] => Some(Self::Jpeg),
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some(Self::Webp),
+            [b'G', b'I', b'F', b'8', ..] => Some(Self::Gif),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            Self::Webp => "webp",
+            Self::Gif => "gif",
+        }
+    }
 }
 
 impl IntoRequest for CreateImageRequest {
@@ -132,12 +218,12 @@ impl IntoRequest for CreateImageRequest {
 
 #[cfg(test)]
 mod tests {
-    use std::fs;
-
-    use crate::SDK;
+    use crate::{
+        stub::{StubResponse, StubServer},
+        SDK,
+    };
 
     use super::*;
-    use anyhow::Result;
     use serde_json::json;
 
     #[test]
@@ -180,15 +266,73 @@ mod tests {
         assert_eq!(res.data.len(), 1);
         let image = &res.data[0];
         assert!(image.url.is_some());
-        assert!(image.b64_json.is_some());
+        assert!(image.revised_prompt.is_some());
         println!("image: {:?}", image);
-        fs::write(
-            "/tmp/caterpillar.png",
-            reqwest::get(image.url.as_ref().unwrap())
-                .await?
-                .bytes()
-                .await?,
-        )?;
+        let path = image.save_to(&SDK, "/tmp/caterpillar").await?;
+        assert_eq!(path, PathBuf::from("/tmp/caterpillar.png"));
         Ok(())
     }
+
+    #[test]
+    fn image_object_without_revised_prompt_should_deserialize() -> Result<()> {
+        let image: ImageObject = serde_json::from_value(json!({
+            "url": "https://example.com/image.png",
+        }))?;
+        assert_eq!(image.url.as_deref(), Some("https://example.com/image.png"));
+        assert_eq!(image.revised_prompt, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn image_object_b64_json_should_save() -> Result<()> {
+        let sdk = LlmSdk::new("http://localhost", "", 0);
+        let png = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+        let image = ImageObject {
+            b64_json: Some(STANDARD.encode(png)),
+            url: None,
+            revised_prompt: None,
+        };
+        assert_eq!(image.bytes(&sdk).await?.as_ref(), &png);
+
+        let path = std::env::temp_dir().join("llm-sdk-b64-image");
+        let path = image.save_to(&sdk, path).await?;
+        assert_eq!(path.extension().unwrap(), "png");
+        assert_eq!(std::fs::read(&path)?, png);
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn image_object_url_should_download_without_credentials() -> Result<()> {
+        let png = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+        let cdn = StubServer::start(move |_| StubResponse {
+            status: 200,
+            headers: vec![("content-type".into(), "image/png".into())],
+            body: png.to_vec(),
+        })
+        .await;
+        let sdk = LlmSdk::new("http://localhost", "sk-test", 0);
+        let image = ImageObject {
+            b64_json: None,
+            url: Some(format!("{}/image.png", cdn.url())),
+            revised_prompt: None,
+        };
+        assert_eq!(image.bytes(&sdk).await?.as_ref(), &png);
+        assert_eq!(cdn.requests()[0].header("authorization"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn image_format_should_detect() {
+        assert_eq!(
+            ImageFormat::detect(b"\xff\xd8\xff\xe0"),
+            Some(ImageFormat::Jpeg)
+        );
+        assert_eq!(
+            ImageFormat::detect(b"RIFF\0\0\0\0WEBPVP8 "),
+            Some(ImageFormat::Webp)
+        );
+        assert_eq!(ImageFormat::detect(b"GIF89a"), Some(ImageFormat::Gif));
+        assert_eq!(ImageFormat::detect(b"hello"), None);
+    }
 }
@@ -0,0 +1,354 @@
+use std::path::Path;
+
+use anyhow::Result;
+use derive_builder::Builder;
+use reqwest::{
+    multipart::{Form, Part},
+    Body,
+};
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use tokio_util::io::ReaderStream;
+
+use crate::{pagination::ListParams, IntoRequest};
+
+#[derive(Debug)]
+pub struct UploadFileRequest {
+    /// The file object (not file name) to be uploaded.
+    file: FileData,
+
+    /// The name of the file, the extension is used by the API to validate the content.
+    filename: String,
+
+    /// The intended purpose of the uploaded file.
+    purpose: FilePurpose,
+}
+
+/// The content of a file to upload, either in memory or streamed from disk.
+#[derive(Debug)]
+pub enum FileData {
+    Bytes(Vec<u8>),
+    File { file: tokio::fs::File, len: u64 },
+}
+
+#[derive(Debug, Serialize, Clone, Default, Builder)]
+#[builder(pattern = "mutable")]
+pub struct ListFilesRequest {
+    /// Only return files with the given purpose.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    purpose: Option<FilePurpose>,
+
+    #[builder(default)]
+    #[serde(flatten)]
+    params: ListParams,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetrieveFileRequest {
+    file_id: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeleteFileRequest {
+    file_id: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileContentRequest {
+    file_id: String,
+}
+
+#[derive(
+    Debug, Serialize, Deserialize, EnumString, Display, Clone, Copy, PartialEq, Eq, Default,
+)]
+pub enum FilePurpose {
+    #[default]
+    #[serde(rename = "assistants")]
+    #[strum(serialize = "assistants")]
+    Assistants,
+
+    #[serde(rename = "assistants_output")]
+    #[strum(serialize = "assistants_output")]
+    AssistantsOutput,
+
+    #[serde(rename = "batch")]
+    #[strum(serialize = "batch")]
+    Batch,
+
+    #[serde(rename = "batch_output")]
+    #[strum(serialize = "batch_output")]
+    BatchOutput,
+
+    #[serde(rename = "fine-tune")]
+    #[strum(serialize = "fine-tune")]
+    FineTune,
+
+    #[serde(rename = "fine-tune-results")]
+    #[strum(serialize = "fine-tune-results")]
+    FineTuneResults,
+
+    #[serde(rename = "vision")]
+    #[strum(serialize = "vision")]
+    Vision,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FileObject {
+    /// The file identifier, which can be referenced in the API endpoints.
+    pub id: String,
+
+    /// The size of the file, in bytes.
+    pub bytes: u64,
+
+    /// The Unix timestamp (in seconds) for when the file was created.
+    pub created_at: u64,
+
+    /// The name of the file.
+    pub filename: String,
+
+    /// The object type, which is always "file".
+    pub object: String,
+
+    /// The intended purpose of the file.
+    pub purpose: FilePurpose,
+
+    /// Deprecated. The current status of the file, which can be either uploaded, processed, or error.
+    #[serde(default)]
+    pub status: Option<String>,
+
+    /// Deprecated. For details on why a fine-tuning training file failed validation, see the error field on fine_tuning.job.
+    #[serde(default)]
+    pub status_details: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DeleteFileResponse {
+    /// The ID of the deleted file.
+    pub id: String,
+
+    /// The object type, which is always "file".
+    pub object: String,
+
+    /// Whether the file was deleted.
+    pub deleted: bool,
+}
+
+impl UploadFileRequest {
+    pub fn new(data: Vec<u8>, filename: impl Into<String>, purpose: FilePurpose) -> Self {
+        Self {
+            file: FileData::Bytes(data),
+            filename: filename.into(),
+            purpose,
+        }
+    }
+
+    /// Upload a file from disk. The file is streamed rather than read into memory, and the file
+    /// name is taken from the path.
+    pub async fn from_path(path: impl AsRef<Path>, purpose: FilePurpose) -> Result<Self> {
+        let path = path.as_ref();
+        let file = tokio::fs::File::open(path).await?;
+        let len = file.metadata().await?.len();
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "file".to_owned());
+        Ok(Self {
+            file: FileData::File { file, len },
+            filename,
+            purpose,
+        })
+    }
+
+    fn into_form(self) -> Form {
+        let part = match self.file {
+            FileData::Bytes(data) => Part::bytes(data),
+            FileData::File { file, len } => {
+                Part::stream_with_length(Body::wrap_stream(ReaderStream::new(file)), len)
+            }
+        };
+        let part = part.file_name(self.filename);
+        Form::new()
+            .part("file", part)
+            .text("purpose", self.purpose.to_string())
+    }
+}
+
+impl ListFilesRequest {
+    pub fn new(params: ListParams) -> Self {
+        Self {
+            purpose: None,
+            params,
+        }
+    }
+}
+
+impl RetrieveFileRequest {
+    pub fn new(file_id: impl Into<String>) -> Self {
+        Self {
+            file_id: file_id.into(),
+        }
+    }
+}
+
+impl DeleteFileRequest {
+    pub fn new(file_id: impl Into<String>) -> Self {
+        Self {
+            file_id: file_id.into(),
+        }
+    }
+}
+
+impl FileContentRequest {
+    pub fn new(file_id: impl Into<String>) -> Self {
+        Self {
+            file_id: file_id.into(),
+        }
+    }
+}
+
+impl IntoRequest for UploadFileRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/files");
+        client.post(url).multipart(self.into_form())
+    }
+}
+
+impl IntoRequest for ListFilesRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/files");
+        client.get(url).query(&self)
+    }
+}
+
+impl IntoRequest for RetrieveFileRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/files/{}", self.file_id);
+        client.get(url)
+    }
+}
+
+impl IntoRequest for DeleteFileRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/files/{}", self.file_id);
+        client.delete(url)
+    }
+}
+
+impl IntoRequest for FileContentRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/files/{}/content", self.file_id);
+        client.get(url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{pagination::ListParamsBuilder, LlmSdk, SDK};
+
+    use super::*;
+    use futures::TryStreamExt;
+    use serde_json::json;
+
+    #[test]
+    fn list_files_request_should_serialize() -> Result<()> {
+        let req = ListFilesRequestBuilder::default()
+            .purpose(FilePurpose::FineTune)
+            .params(
+                ListParamsBuilder::default()
+                    .limit(10)
+                    .after("file-abc")
+                    .build()?,
+            )
+            .build()?;
+        assert_eq!(
+            serde_json::to_value(req)?,
+            json!({
+                "purpose": "fine-tune",
+                "limit": 10,
+                "after": "file-abc",
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn list_files_request_should_build_query() -> Result<()> {
+        let req = ListFilesRequestBuilder::default()
+            .purpose(FilePurpose::Batch)
+            .params(ListParamsBuilder::default().limit(2).build()?)
+            .build()?;
+        let sdk = LlmSdk::new("https://api.openai.com/v1", "", 0);
+        let req = sdk.prepare_request(req).build()?;
+        assert_eq!(
+            req.url().as_str(),
+            "https://api.openai.com/v1/files?purpose=batch&limit=2"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn file_object_should_deserialize() -> Result<()> {
+        let file: FileObject = serde_json::from_value(json!({
+            "id": "file-abc123",
+            "object": "file",
+            "bytes": 120000,
+            "created_at": 1677610602,
+            "filename": "mydata.jsonl",
+            "purpose": "fine-tune",
+        }))?;
+        assert_eq!(file.purpose, FilePurpose::FineTune);
+        assert_eq!(file.bytes, 120000);
+        assert_eq!(file.status, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn file_lifecycle_should_work() -> Result<()> {
+        let content = serde_json::to_vec(&json!({
+            "custom_id": "request-1",
+            "method": "POST",
+            "url": "/v1/chat/completions",
+            "body": {
+                "model": "gpt-3.5-turbo",
+                "messages": [{"role": "user", "content": "Hello world!"}]
+            }
+        }))?;
+        let req = UploadFileRequest::new(content.clone(), "batch.jsonl", FilePurpose::Batch);
+        let file = SDK.upload_file(req).await?;
+        assert_eq!(file.filename, "batch.jsonl");
+        assert_eq!(file.purpose, FilePurpose::Batch);
+
+        let ret = SDK.retrieve_file(&file.id).await?;
+        assert_eq!(ret.bytes, content.len() as u64);
+
+        let req = ListFilesRequestBuilder::default()
+            .purpose(FilePurpose::Batch)
+            .build()?;
+        let files = SDK.list_files(req).await?;
+        assert!(files.data.iter().any(|f| f.id == file.id));
+
+        let data: Vec<_> = SDK
+            .file_content_stream(&file.id)
+            .await?
+            .try_collect()
+            .await?;
+        assert_eq!(data.concat(), content);
+
+        let res = SDK.delete_file(&file.id).await?;
+        assert!(res.deleted);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_file_from_path_should_work() -> Result<()> {
+        let req =
+            UploadFileRequest::from_path("fixtures/test.mp3", FilePurpose::Assistants).await?;
+        let file = SDK.upload_file(req).await?;
+        assert_eq!(file.filename, "test.mp3");
+        assert_eq!(file.bytes, std::fs::metadata("fixtures/test.mp3")?.len());
+        SDK.delete_file(&file.id).await?;
+        Ok(())
+    }
+}
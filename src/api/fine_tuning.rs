@@ -0,0 +1,606 @@
+use std::{collections::HashMap, fmt, io::Write};
+
+use anyhow::{anyhow, Result};
+use derive_builder::Builder;
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    chat_completion::{ChatCompletionMessage, ChatCompletionModel},
+    pagination::ListParams,
+    IntoRequest,
+};
+
+#[derive(Debug, Serialize, Clone, Builder)]
+#[builder(pattern = "mutable")]
+pub struct CreateFineTuningJobRequest {
+    /// The name of the model to fine-tune.
+    #[builder(default)]
+    model: ChatCompletionModel,
+
+    /// The ID of an uploaded file that contains training data. The file must be uploaded with
+    /// the purpose fine-tune, and formatted as JSONL with a chat conversation per line.
+    #[builder(setter(into))]
+    training_file: String,
+
+    /// The ID of an uploaded file that contains validation data. The same data should not be
+    /// present in both train and validation files.
+    #[builder(default, setter(strip_option, into))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    validation_file: Option<String>,
+
+    /// The hyperparameters used for the fine-tuning job.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hyperparameters: Option<Hyperparameters>,
+
+    /// A string of up to 18 characters that will be added to your fine-tuned model name.
+    #[builder(default, setter(strip_option, into))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suffix: Option<String>,
+
+    /// The seed controls the reproducibility of the job. If a seed is not specified, one will be generated for you.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Builder)]
+#[builder(pattern = "mutable")]
+pub struct Hyperparameters {
+    /// The number of epochs to train the model for. An epoch refers to one full cycle through the training dataset.
+    #[builder(default, setter(strip_option, into))]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub n_epochs: Option<Hyperparameter<u32>>,
+
+    /// Number of examples in each batch. A larger batch size means that model parameters are
+    /// updated less frequently, but with lower variance.
+    #[builder(default, setter(strip_option, into))]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub batch_size: Option<Hyperparameter<u32>>,
+
+    /// Scaling factor for the learning rate. A smaller learning rate may be useful to avoid overfitting.
+    #[builder(default, setter(strip_option, into))]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub learning_rate_multiplier: Option<Hyperparameter<f64>>,
+}
+
+/// A hyperparameter is either chosen by OpenAI ("auto") or set explicitly.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Hyperparameter<T> {
+    #[default]
+    Auto,
+    Value(T),
+}
+
+#[derive(Debug, Clone)]
+pub struct RetrieveFineTuningJobRequest {
+    job_id: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct CancelFineTuningJobRequest {
+    job_id: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ListFineTuningJobsRequest {
+    params: ListParams,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListFineTuningEventsRequest {
+    job_id: String,
+    params: ListParams,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListFineTuningCheckpointsRequest {
+    job_id: String,
+    params: ListParams,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FineTuningJob {
+    /// The object identifier, which can be referenced in the API endpoints.
+    pub id: String,
+
+    /// The object type, which is always "fine_tuning.job".
+    pub object: String,
+
+    /// The Unix timestamp (in seconds) for when the fine-tuning job was created.
+    pub created_at: u64,
+
+    /// The Unix timestamp (in seconds) for when the fine-tuning job was finished.
+    #[serde(default)]
+    pub finished_at: Option<u64>,
+
+    /// The base model that is being fine-tuned.
+    pub model: String,
+
+    /// The name of the fine-tuned model that is being created. Null if the job is still running.
+    #[serde(default)]
+    pub fine_tuned_model: Option<String>,
+
+    /// The organization that owns the fine-tuning job.
+    pub organization_id: String,
+
+    /// The current status of the fine-tuning job.
+    pub status: FineTuningJobStatus,
+
+    /// The hyperparameters used for the fine-tuning job.
+    pub hyperparameters: Hyperparameters,
+
+    /// The file ID used for training.
+    pub training_file: String,
+
+    /// The file ID used for validation.
+    #[serde(default)]
+    pub validation_file: Option<String>,
+
+    /// The compiled results file ID(s) for the fine-tuning job.
+    #[serde(default)]
+    pub result_files: Vec<String>,
+
+    /// The total number of billable tokens processed by this fine-tuning job. Null if the job is still running.
+    #[serde(default)]
+    pub trained_tokens: Option<u64>,
+
+    /// For fine-tuning jobs that have failed, this will contain more information on the cause of the failure.
+    #[serde(default)]
+    pub error: Option<FineTuningJobError>,
+
+    /// The seed used for the fine-tuning job.
+    #[serde(default)]
+    pub seed: Option<u64>,
+
+    /// The Unix timestamp (in seconds) for when the fine-tuning job is estimated to finish.
+    #[serde(default)]
+    pub estimated_finish: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FineTuningJobStatus {
+    ValidatingFiles,
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FineTuningJobError {
+    /// A machine-readable error code.
+    pub code: String,
+
+    /// A human-readable error message.
+    pub message: String,
+
+    /// The parameter that was invalid, usually training_file or validation_file.
+    #[serde(default)]
+    pub param: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FineTuningJobEvent {
+    pub id: String,
+
+    /// The object type, which is always "fine_tuning.job.event".
+    pub object: String,
+
+    pub created_at: u64,
+
+    /// The log level of the event: info, warn or error.
+    pub level: String,
+
+    pub message: String,
+
+    /// The type of event: message or metrics.
+    #[serde(default, rename = "type")]
+    pub typ: Option<String>,
+
+    /// The data associated with the event, e.g. the metrics of a step.
+    #[serde(default)]
+    pub data: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FineTuningJobCheckpoint {
+    /// The checkpoint identifier, which can be referenced in the API endpoints.
+    pub id: String,
+
+    /// The object type, which is always "fine_tuning.job.checkpoint".
+    pub object: String,
+
+    /// The Unix timestamp (in seconds) for when the checkpoint was created.
+    pub created_at: u64,
+
+    /// The name of the fine-tuned checkpoint model that is created.
+    pub fine_tuned_model_checkpoint: String,
+
+    /// The step number that the checkpoint was created at.
+    pub step_number: u64,
+
+    /// Metrics at the step number during the fine-tuning job.
+    pub metrics: HashMap<String, f64>,
+
+    /// The name of the fine-tuning job that this checkpoint was created from.
+    pub fine_tuning_job_id: String,
+}
+
+#[derive(Debug, Serialize)]
+struct TrainingExample<'a> {
+    messages: &'a [ChatCompletionMessage],
+}
+
+impl CreateFineTuningJobRequest {
+    pub fn new(model: ChatCompletionModel, training_file: impl Into<String>) -> Self {
+        CreateFineTuningJobRequestBuilder::default()
+            .model(model)
+            .training_file(training_file)
+            .build()
+            .unwrap()
+    }
+}
+
+impl RetrieveFineTuningJobRequest {
+    pub fn new(job_id: impl Into<String>) -> Self {
+        Self {
+            job_id: job_id.into(),
+        }
+    }
+}
+
+impl CancelFineTuningJobRequest {
+    pub fn new(job_id: impl Into<String>) -> Self {
+        Self {
+            job_id: job_id.into(),
+        }
+    }
+}
+
+impl ListFineTuningJobsRequest {
+    pub fn new(params: ListParams) -> Self {
+        Self { params }
+    }
+}
+
+impl ListFineTuningEventsRequest {
+    pub fn new(job_id: impl Into<String>, params: ListParams) -> Self {
+        Self {
+            job_id: job_id.into(),
+            params,
+        }
+    }
+}
+
+impl ListFineTuningCheckpointsRequest {
+    pub fn new(job_id: impl Into<String>, params: ListParams) -> Self {
+        Self {
+            job_id: job_id.into(),
+            params,
+        }
+    }
+}
+
+impl FineTuningJobStatus {
+    /// Whether the job will not change its status anymore.
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Succeeded | Self::Failed | Self::Cancelled)
+    }
+}
+
+impl<T> From<T> for Hyperparameter<T> {
+    fn from(value: T) -> Self {
+        Self::Value(value)
+    }
+}
+
+impl<T: Serialize> Serialize for Hyperparameter<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Auto => serializer.serialize_str("auto"),
+            Self::Value(v) => v.serialize(serializer),
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Hyperparameter<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr<T> {
+            Str(String),
+            Value(T),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Str(s) if s == "auto" => Ok(Self::Auto),
+            Repr::Str(s) => Err(de::Error::invalid_value(
+                de::Unexpected::Str(&s),
+                &"\"auto\" or a number",
+            )),
+            Repr::Value(v) => Ok(Self::Value(v)),
+        }
+    }
+}
+
+/// The reason a training example is rejected by `validate_training_example`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrainingExampleError {
+    Empty,
+    MissingAssistantMessage,
+    EmptyContent(usize),
+    UnexpectedToolMessage(usize),
+}
+
+impl fmt::Display for TrainingExampleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "example has no messages"),
+            Self::MissingAssistantMessage => write!(f, "example has no assistant message"),
+            Self::EmptyContent(i) => write!(f, "message {i} has no content"),
+            Self::UnexpectedToolMessage(i) => {
+                write!(f, "tool message {i} does not follow an assistant tool call")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TrainingExampleError {}
+
+/// Check that a conversation can be used as a chat fine-tuning example: it must have at least
+/// one assistant message, every message must have content (or tool calls for the assistant),
+/// and tool messages must answer a preceding assistant tool call.
+pub fn validate_training_example(
+    messages: &[ChatCompletionMessage],
+) -> Result<(), TrainingExampleError> {
+    if messages.is_empty() {
+        return Err(TrainingExampleError::Empty);
+    }
+
+    let mut has_assistant = false;
+    let mut tool_call_ids: Vec<&str> = Vec::new();
+    for (i, message) in messages.iter().enumerate() {
+        match message {
+            ChatCompletionMessage::Assistant(m) => {
+                has_assistant = true;
+                if m.content().unwrap_or_default().is_empty() && m.tool_calls().is_empty() {
+                    return Err(TrainingExampleError::EmptyContent(i));
+                }
+                tool_call_ids = m.tool_calls().iter().map(|c| c.id()).collect();
+            }
+            ChatCompletionMessage::Tool(m) => {
+                if !tool_call_ids.contains(&m.tool_call_id()) {
+                    return Err(TrainingExampleError::UnexpectedToolMessage(i));
+                }
+            }
+            _ => {
+                if message.content().unwrap_or_default().is_empty() {
+                    return Err(TrainingExampleError::EmptyContent(i));
+                }
+                tool_call_ids.clear();
+            }
+        }
+    }
+
+    if !has_assistant {
+        return Err(TrainingExampleError::MissingAssistantMessage);
+    }
+    Ok(())
+}
+
+/// Validate the conversations and write them as chat fine-tuning JSONL, one
+/// `{"messages": [...]}` object per line.
+pub fn write_training_jsonl(
+    examples: &[Vec<ChatCompletionMessage>],
+    mut writer: impl Write,
+) -> Result<()> {
+    for (i, messages) in examples.iter().enumerate() {
+        validate_training_example(messages)
+            .map_err(|e| anyhow!("invalid training example {}: {}", i, e))?;
+        serde_json::to_writer(&mut writer, &TrainingExample { messages })?;
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Read chat fine-tuning JSONL back into conversations.
+pub fn read_training_jsonl(data: &[u8]) -> Result<Vec<Vec<ChatCompletionMessage>>> {
+    #[derive(Deserialize)]
+    struct Example {
+        messages: Vec<ChatCompletionMessage>,
+    }
+
+    data.split(|b| *b == b'\n')
+        .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+        .map(|line| Ok(serde_json::from_slice::<Example>(line)?.messages))
+        .collect()
+}
+
+impl IntoRequest for CreateFineTuningJobRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/fine_tuning/jobs");
+        client.post(url).json(&self)
+    }
+}
+
+impl IntoRequest for ListFineTuningJobsRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/fine_tuning/jobs");
+        client.get(url).query(&self.params)
+    }
+}
+
+impl IntoRequest for RetrieveFineTuningJobRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/fine_tuning/jobs/{}", self.job_id);
+        client.get(url)
+    }
+}
+
+impl IntoRequest for CancelFineTuningJobRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/fine_tuning/jobs/{}/cancel", self.job_id);
+        client.post(url)
+    }
+}
+
+impl IntoRequest for ListFineTuningEventsRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/fine_tuning/jobs/{}/events", self.job_id);
+        client.get(url).query(&self.params)
+    }
+}
+
+impl IntoRequest for ListFineTuningCheckpointsRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/fine_tuning/jobs/{}/checkpoints", self.job_id);
+        client.get(url).query(&self.params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{pagination::ListParamsBuilder, SDK};
+
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn create_fine_tuning_job_request_should_serialize() -> Result<()> {
+        let req = CreateFineTuningJobRequestBuilder::default()
+            .training_file("file-abc123")
+            .hyperparameters(
+                HyperparametersBuilder::default()
+                    .n_epochs(3)
+                    .batch_size(Hyperparameter::Auto)
+                    .build()?,
+            )
+            .suffix("custom-model-name")
+            .build()?;
+        assert_eq!(
+            serde_json::to_value(req)?,
+            json!({
+                "model": "gpt-3.5-turbo",
+                "training_file": "file-abc123",
+                "hyperparameters": {"n_epochs": 3, "batch_size": "auto"},
+                "suffix": "custom-model-name",
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn fine_tuning_job_should_deserialize() -> Result<()> {
+        let job: FineTuningJob = serde_json::from_value(json!({
+            "object": "fine_tuning.job",
+            "id": "ftjob-abc123",
+            "model": "gpt-3.5-turbo-0125",
+            "created_at": 1614807352,
+            "fine_tuned_model": null,
+            "organization_id": "org-123",
+            "result_files": [],
+            "status": "queued",
+            "validation_file": null,
+            "training_file": "file-abc123",
+            "hyperparameters": {
+                "n_epochs": "auto",
+                "batch_size": 4,
+                "learning_rate_multiplier": 1.8
+            }
+        }))?;
+        assert_eq!(job.status, FineTuningJobStatus::Queued);
+        assert!(!job.status.is_terminal());
+        assert_eq!(job.hyperparameters.n_epochs, Some(Hyperparameter::Auto));
+        assert_eq!(
+            job.hyperparameters.batch_size,
+            Some(Hyperparameter::Value(4))
+        );
+        assert_eq!(
+            job.hyperparameters.learning_rate_multiplier,
+            Some(Hyperparameter::Value(1.8))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn list_events_request_should_build_query() -> Result<()> {
+        let sdk = crate::LlmSdk::new("https://api.openai.com/v1", "", 0);
+        let params = ListParamsBuilder::default()
+            .limit(2)
+            .after("ftevent-1")
+            .build()?;
+        let req = sdk
+            .prepare_request(ListFineTuningEventsRequest::new("ftjob-abc123", params))
+            .build()?;
+        assert_eq!(
+            req.url().as_str(),
+            "https://api.openai.com/v1/fine_tuning/jobs/ftjob-abc123/events?limit=2&after=ftevent-1"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn training_jsonl_should_round_trip() -> Result<()> {
+        let examples = vec![
+            vec![
+                ChatCompletionMessage::new_system("Marv is a sarcastic chatbot.", ""),
+                ChatCompletionMessage::new_user("What's the capital of France?", ""),
+                serde_json::from_value(json!({
+                    "role": "assistant",
+                    "content": "Paris, as if everyone doesn't know that already."
+                }))?,
+            ];
+            2
+        ];
+        let mut buf = Vec::new();
+        write_training_jsonl(&examples, &mut buf)?;
+        let text = String::from_utf8(buf.clone())?;
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.starts_with(r#"{"messages":[{"role":"system""#));
+
+        let ret = read_training_jsonl(&buf)?;
+        assert_eq!(ret.len(), 2);
+        assert_eq!(
+            serde_json::to_value(&ret)?,
+            serde_json::to_value(&examples)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_training_example_should_be_rejected() -> Result<()> {
+        let no_assistant = vec![ChatCompletionMessage::new_user("Hello", "")];
+        assert_eq!(
+            validate_training_example(&no_assistant),
+            Err(TrainingExampleError::MissingAssistantMessage)
+        );
+
+        let orphan_tool: Vec<ChatCompletionMessage> = serde_json::from_value(json!([
+            {"role": "user", "content": "What is the weather like in Boston?"},
+            {"role": "tool", "content": "22", "tool_call_id": "call_1"},
+            {"role": "assistant", "content": "22 degrees."}
+        ]))?;
+        assert_eq!(
+            validate_training_example(&orphan_tool),
+            Err(TrainingExampleError::UnexpectedToolMessage(1))
+        );
+
+        assert!(write_training_jsonl(&[vec![]], Vec::new()).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_fine_tuning_jobs_should_work() -> Result<()> {
+        let params = ListParamsBuilder::default().limit(1).build()?;
+        let res = SDK
+            .list_fine_tuning_jobs(ListFineTuningJobsRequest::new(params))
+            .await?;
+        assert_eq!(res.object, "list");
+        assert!(res.data.len() <= 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,690 @@
+//! Chat completions and embeddings served by Google's Gemini API, translated from and to the
+//! OpenAI shapes.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    chat_completion::{
+        AssistantMessage, ChatCompletionChoice, ChatCompletionMessage, ChatCompletionModel,
+        ChatCompletionRequest, ChatCompletionResponse, ChatCompletionUsage, ChatResponseFormat,
+        FinishReason, Function, Stop, ToolCall, ToolCallType, ToolChoice,
+    },
+    create_embedding::{
+        CreateEmbeddingRequest, CreateEmbeddingResponse, Embedding, EmbeddingInput, EmbeddingModel,
+        EmbeddingObject, EmbeddingUsage,
+    },
+    provider::{ChatProvider, EmbeddingProvider},
+    IntoRequest, SendAndLog, TIMEOUT,
+};
+
+pub const GEMINI_API_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+const DEFAULT_MODEL: &str = "gemini-2.5-flash";
+const DEFAULT_EMBEDDING_MODEL: &str = "gemini-embedding-001";
+
+/// A chat and embedding provider backed by the Gemini API, authenticated with an API key.
+///
+/// Custom model ids are sent as is, the OpenAI models are replaced by the default models.
+/// Options without a Gemini counterpart (logit_bias, logprobs, user) are ignored.
+#[derive(Debug, Clone)]
+pub struct GeminiSdk {
+    base_url: String,
+    api_key: String,
+    client: ClientWithMiddleware,
+    default_model: String,
+    default_embedding_model: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerateContentRequest {
+    #[serde(skip)]
+    model: String,
+    contents: Vec<Content>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<Content>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<GeminiTool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_config: Option<serde_json::Value>,
+    generation_config: GenerationConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Content {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    role: Option<String>,
+    #[serde(default)]
+    parts: Vec<Part>,
+}
+
+/// One of the fields is set. Kinds of parts not used here (inline data, executable code, ...)
+/// deserialize with none set, thought summaries with `thought` set, and both are skipped.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Part {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    function_call: Option<FunctionCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    function_response: Option<FunctionResponse>,
+    #[serde(default, skip_serializing)]
+    thought: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FunctionCall {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    name: String,
+    #[serde(default)]
+    args: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FunctionResponse {
+    name: String,
+    response: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiTool {
+    function_declarations: Vec<FunctionDeclaration>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct FunctionDeclaration {
+    name: String,
+    description: String,
+    parameters: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerationConfig {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop_sequences: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    candidate_count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_mime_type: Option<&'static str>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerateContentResponse {
+    #[serde(default)]
+    candidates: Vec<Candidate>,
+    #[serde(default)]
+    usage_metadata: UsageMetadata,
+    #[serde(default)]
+    model_version: Option<String>,
+    #[serde(default)]
+    response_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Candidate {
+    #[serde(default)]
+    content: Option<Content>,
+    #[serde(default)]
+    finish_reason: Option<String>,
+    #[serde(default)]
+    index: usize,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageMetadata {
+    #[serde(default)]
+    prompt_token_count: usize,
+    #[serde(default)]
+    candidates_token_count: usize,
+    #[serde(default)]
+    total_token_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct BatchEmbedContentsRequest {
+    #[serde(skip)]
+    model: String,
+    requests: Vec<EmbedContentRequest>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct EmbedContentRequest {
+    model: String,
+    content: Content,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct BatchEmbedContentsResponse {
+    embeddings: Vec<ContentEmbedding>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ContentEmbedding {
+    values: Vec<f64>,
+}
+
+impl GeminiSdk {
+    pub fn new(base_url: impl Into<String>, api_key: impl Into<String>, max_retries: u32) -> Self {
+        Self {
+            base_url: base_url.into(),
+            api_key: api_key.into(),
+            client: crate::build_client(max_retries, &[], &[]),
+            default_model: DEFAULT_MODEL.to_owned(),
+            default_embedding_model: DEFAULT_EMBEDDING_MODEL.to_owned(),
+        }
+    }
+
+    /// The chat model used when the request names an OpenAI model.
+    pub fn with_default_model(mut self, model: impl Into<String>) -> Self {
+        self.default_model = model.into();
+        self
+    }
+
+    /// The embedding model used when the request names an OpenAI model.
+    pub fn with_default_embedding_model(mut self, model: impl Into<String>) -> Self {
+        self.default_embedding_model = model.into();
+        self
+    }
+
+    pub async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse> {
+        let req = self.translate(req)?;
+        let model = req.model.clone();
+        let res = self.send(req).await?;
+        let res = res.json::<GenerateContentResponse>().await?;
+        Ok(res.into_response(model))
+    }
+
+    pub async fn create_embedding(
+        &self,
+        req: CreateEmbeddingRequest,
+    ) -> Result<CreateEmbeddingResponse> {
+        let model = match req.model {
+            EmbeddingModel::Custom(id) => id,
+            _ => self.default_embedding_model.clone(),
+        };
+        let inputs = match req.input {
+            EmbeddingInput::String(s) => vec![s],
+            EmbeddingInput::StringArray(v) => v,
+        };
+        let req = BatchEmbedContentsRequest {
+            requests: inputs
+                .into_iter()
+                .map(|text| EmbedContentRequest {
+                    model: format!("models/{model}"),
+                    content: Content {
+                        role: None,
+                        parts: vec![Part::text(text)],
+                    },
+                })
+                .collect(),
+            model: model.clone(),
+        };
+        let res = self.send(req).await?;
+        let res = res.json::<BatchEmbedContentsResponse>().await?;
+        Ok(CreateEmbeddingResponse {
+            object: EmbeddingObject::List,
+            data: res
+                .embeddings
+                .into_iter()
+                .enumerate()
+                .map(|(index, e)| Embedding {
+                    index,
+                    embedding: e.values,
+                    object: EmbeddingObject::Embedding,
+                })
+                .collect(),
+            model: EmbeddingModel::Custom(model),
+            // batchEmbedContents doesn't report token usage
+            usage: EmbeddingUsage {
+                prompt_tokens: 0,
+                total_tokens: 0,
+            },
+        })
+    }
+
+    async fn send(&self, req: impl IntoRequest) -> Result<reqwest::Response> {
+        req.into_request(&self.base_url, self.client.clone())
+            // a header rather than the key query parameter keeps the key out of errors and logs
+            .header("x-goog-api-key", &self.api_key)
+            .timeout(Duration::from_secs(TIMEOUT))
+            .send_and_log()
+            .await
+    }
+
+    fn translate(&self, req: ChatCompletionRequest) -> Result<GenerateContentRequest> {
+        let mut system: Vec<Part> = vec![];
+        let mut contents: Vec<Content> = vec![];
+        // functionResponse parts are matched by name, tool messages only know the call id
+        let mut call_names: HashMap<String, String> = HashMap::new();
+        for message in req.messages {
+            let (role, parts) = match message {
+                ChatCompletionMessage::System(m) => {
+                    system.push(Part::text(m.content));
+                    continue;
+                }
+                ChatCompletionMessage::User(m) => ("user", vec![Part::text(m.content)]),
+                ChatCompletionMessage::Assistant(m) => {
+                    let mut parts = vec![];
+                    if let Some(text) = m.content.filter(|t| !t.is_empty()) {
+                        parts.push(Part::text(text));
+                    }
+                    for call in m.tool_calls {
+                        call_names.insert(call.id.clone(), call.function.name.clone());
+                        parts.push(Part {
+                            function_call: Some(FunctionCall {
+                                id: None,
+                                args: parse_arguments(&call.function.arguments)?,
+                                name: call.function.name,
+                            }),
+                            ..Default::default()
+                        });
+                    }
+                    ("model", parts)
+                }
+                ChatCompletionMessage::Tool(m) => {
+                    let name = call_names.get(&m.tool_call_id).cloned().ok_or_else(|| {
+                        anyhow!("no tool call {} before its result", m.tool_call_id)
+                    })?;
+                    // the response must be an object
+                    let response = match serde_json::from_str(&m.content) {
+                        Ok(serde_json::Value::Object(o)) => serde_json::Value::Object(o),
+                        _ => json!({ "result": m.content }),
+                    };
+                    (
+                        "user",
+                        vec![Part {
+                            function_response: Some(FunctionResponse { name, response }),
+                            ..Default::default()
+                        }],
+                    )
+                }
+            };
+            if parts.is_empty() {
+                continue;
+            }
+            match contents.last_mut() {
+                Some(last) if last.role.as_deref() == Some(role) => last.parts.extend(parts),
+                _ => contents.push(Content {
+                    role: Some(role.to_owned()),
+                    parts,
+                }),
+            }
+        }
+
+        let declarations = req
+            .tools
+            .into_iter()
+            .map(|t| FunctionDeclaration {
+                name: t.function.name,
+                description: t.function.description,
+                parameters: gemini_schema(t.function.parameters),
+            })
+            .collect::<Vec<_>>();
+        let tool_config = match (declarations.is_empty(), req.tool_choice) {
+            (true, _) | (false, None) => None,
+            (false, Some(choice)) => Some(tool_config(choice)),
+        };
+        let tools = match declarations.is_empty() {
+            true => vec![],
+            false => vec![GeminiTool {
+                function_declarations: declarations,
+            }],
+        };
+
+        let response_mime_type = req
+            .response_format
+            .filter(|f| f.typ == ChatResponseFormat::Json)
+            .map(|_| "application/json");
+
+        Ok(GenerateContentRequest {
+            model: match req.model {
+                ChatCompletionModel::Custom(id) => id,
+                _ => self.default_model.clone(),
+            },
+            contents,
+            system_instruction: (!system.is_empty()).then_some(Content {
+                role: None,
+                parts: system,
+            }),
+            tools,
+            tool_config,
+            generation_config: GenerationConfig {
+                stop_sequences: match req.stop {
+                    Some(Stop::One(stop)) => vec![stop],
+                    Some(Stop::Many(stops)) => stops,
+                    None => vec![],
+                },
+                candidate_count: req.n,
+                max_output_tokens: req.max_tokens,
+                temperature: req.temperature,
+                top_p: req.top_p,
+                presence_penalty: req.presence_penalty,
+                frequency_penalty: req.frequency_penalty,
+                seed: req.seed,
+                response_mime_type,
+            },
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl ChatProvider for GeminiSdk {
+    async fn chat_completion(&self, req: ChatCompletionRequest) -> Result<ChatCompletionResponse> {
+        GeminiSdk::chat_completion(self, req).await
+    }
+}
+
+#[async_trait::async_trait]
+impl EmbeddingProvider for GeminiSdk {
+    async fn create_embedding(
+        &self,
+        req: CreateEmbeddingRequest,
+    ) -> Result<CreateEmbeddingResponse> {
+        GeminiSdk::create_embedding(self, req).await
+    }
+}
+
+impl Part {
+    fn text(text: String) -> Self {
+        Self {
+            text: Some(text),
+            ..Default::default()
+        }
+    }
+}
+
+impl IntoRequest for GenerateContentRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/models/{}:generateContent", self.model);
+        client.post(url).json(&self)
+    }
+}
+
+impl IntoRequest for BatchEmbedContentsRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/models/{}:batchEmbedContents", self.model);
+        client.post(url).json(&self)
+    }
+}
+
+impl GenerateContentResponse {
+    fn into_response(self, model: String) -> ChatCompletionResponse {
+        let choices = self
+            .candidates
+            .into_iter()
+            .map(|candidate| {
+                let mut text: Option<String> = None;
+                let mut tool_calls = vec![];
+                let parts = candidate.content.map(|c| c.parts).unwrap_or_default();
+                for part in parts.into_iter().filter(|p| !p.thought) {
+                    if let Some(t) = part.text {
+                        text.get_or_insert_with(String::new).push_str(&t);
+                    }
+                    if let Some(call) = part.function_call {
+                        tool_calls.push(ToolCall {
+                            // older models don't send ids, make them unique within the response
+                            id: call.id.unwrap_or_else(|| {
+                                format!("call_{}_{}", candidate.index, tool_calls.len())
+                            }),
+                            typ: ToolCallType::Function,
+                            function: Function {
+                                name: call.name,
+                                arguments: call.args.to_string(),
+                            },
+                        });
+                    }
+                }
+                let finish_reason = match candidate.finish_reason.as_deref() {
+                    Some("MAX_TOKENS") => FinishReason::Length,
+                    Some("SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII") => {
+                        FinishReason::ContentFilter
+                    }
+                    _ if !tool_calls.is_empty() => FinishReason::ToolCalls,
+                    _ => FinishReason::Stop,
+                };
+                ChatCompletionChoice {
+                    finish_reason,
+                    index: candidate.index,
+                    message: AssistantMessage {
+                        content: text,
+                        name: None,
+                        tool_calls,
+                    },
+                    logprobs: None,
+                }
+            })
+            .collect();
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as usize)
+            .unwrap_or_default();
+
+        ChatCompletionResponse {
+            id: self.response_id.unwrap_or_default(),
+            choices,
+            created,
+            model: self.model_version.unwrap_or(model),
+            system_fingerprint: None,
+            object: "chat.completion".to_owned(),
+            usage: Some(self.usage_metadata.into()),
+        }
+    }
+}
+
+impl From<UsageMetadata> for ChatCompletionUsage {
+    fn from(usage: UsageMetadata) -> Self {
+        Self {
+            completion_tokens: usage.candidates_token_count,
+            prompt_tokens: usage.prompt_token_count,
+            total_tokens: usage.total_token_count,
+        }
+    }
+}
+
+/// The tool call arguments as a JSON object, as `functionCall.args` expects.
+fn parse_arguments(arguments: &str) -> Result<serde_json::Value> {
+    if arguments.trim().is_empty() {
+        return Ok(json!({}));
+    }
+    serde_json::from_str(arguments).map_err(|e| anyhow!("invalid tool call arguments: {e}"))
+}
+
+/// Gemini takes an OpenAPI subset and rejects the JSON Schema keys schemars puts at the root.
+fn gemini_schema(mut schema: serde_json::Value) -> serde_json::Value {
+    if let Some(o) = schema.as_object_mut() {
+        o.remove("$schema");
+        o.remove("title");
+    }
+    schema
+}
+
+fn tool_config(choice: ToolChoice) -> serde_json::Value {
+    let config = match choice {
+        ToolChoice::None => json!({ "mode": "NONE" }),
+        ToolChoice::Auto => json!({ "mode": "AUTO" }),
+        ToolChoice::Required => json!({ "mode": "ANY" }),
+        ToolChoice::Function { name } => json!({ "mode": "ANY", "allowedFunctionNames": [name] }),
+    };
+    json!({ "functionCallingConfig": config })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chat_completion::{ChatCompletionRequestBuilder, Tool},
+        stub::{StubResponse, StubServer},
+    };
+    use schemars::JsonSchema;
+
+    #[allow(dead_code)]
+    #[derive(Debug, Clone, Deserialize, JsonSchema)]
+    struct GetWeatherArgs {
+        /// The city to get the weather for.
+        city: String,
+    }
+
+    fn request() -> ChatCompletionRequest {
+        ChatCompletionRequestBuilder::default()
+            .messages(vec![
+                ChatCompletionMessage::new_system("You are a weather bot.", ""),
+                ChatCompletionMessage::new_user("Weather in Boston?", ""),
+                serde_json::from_value(json!({
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{"id": "call_1", "type": "function", "function": {"name": "get_weather", "arguments": "{\"city\":\"Boston\"}"}}]
+                }))
+                .unwrap(),
+                ChatCompletionMessage::new_tool("call_1", "22"),
+            ])
+            .tools(vec![Tool::new_function::<GetWeatherArgs>(
+                "get_weather",
+                "Get the weather for a city",
+            )])
+            .tool_choice(ToolChoice::function("get_weather"))
+            .max_tokens(100usize)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn gemini_request_should_translate() {
+        let sdk = GeminiSdk::new(GEMINI_API_URL, "", 0).with_default_model("gemini-2.0-flash");
+        let req = sdk.translate(request()).unwrap();
+        assert_eq!(req.model, "gemini-2.0-flash");
+        let req = serde_json::to_value(req).unwrap();
+        assert_eq!(
+            req["systemInstruction"],
+            json!({"parts": [{"text": "You are a weather bot."}]})
+        );
+        assert_eq!(
+            req["contents"],
+            json!([
+                {"role": "user", "parts": [{"text": "Weather in Boston?"}]},
+                {"role": "model", "parts": [{"functionCall": {"name": "get_weather", "args": {"city": "Boston"}}}]},
+                {"role": "user", "parts": [{"functionResponse": {"name": "get_weather", "response": {"result": "22"}}}]}
+            ])
+        );
+        let declaration = &req["tools"][0]["functionDeclarations"][0];
+        assert_eq!(declaration["name"], "get_weather");
+        assert!(declaration["parameters"].get("$schema").is_none());
+        assert_eq!(
+            declaration["parameters"]["properties"]["city"]["type"],
+            "string"
+        );
+        assert_eq!(
+            req["toolConfig"],
+            json!({"functionCallingConfig": {"mode": "ANY", "allowedFunctionNames": ["get_weather"]}})
+        );
+        assert_eq!(req["generationConfig"], json!({"maxOutputTokens": 100}));
+    }
+
+    #[tokio::test]
+    async fn gemini_chat_completion_should_work() -> Result<()> {
+        let server = StubServer::start(|_| {
+            StubResponse::json(
+                200,
+                json!({
+                    "candidates": [{
+                        "content": {"role": "model", "parts": [
+                            {"text": "The user wants the weather.", "thought": true},
+                            {"functionCall": {"name": "get_weather", "args": {"city": "Rome"}}, "thoughtSignature": "c2ln"},
+                            {"inlineData": {"mimeType": "image/png", "data": "aW1n"}}
+                        ]},
+                        "finishReason": "STOP",
+                        "index": 0
+                    }],
+                    "usageMetadata": {"promptTokenCount": 40, "candidatesTokenCount": 6, "totalTokenCount": 46},
+                    "modelVersion": "gemini-2.5-flash-001",
+                    "responseId": "resp_1"
+                }),
+            )
+        })
+        .await;
+        let provider: Box<dyn ChatProvider> = Box::new(GeminiSdk::new(server.url(), "g-key", 0));
+        let res = provider.chat_completion(request()).await?;
+
+        assert_eq!(res.id, "resp_1");
+        assert_eq!(res.model, "gemini-2.5-flash-001");
+        let choice = &res.choices[0];
+        assert_eq!(choice.finish_reason, FinishReason::ToolCalls);
+        assert_eq!(choice.message.content(), None);
+        assert_eq!(choice.message.tool_calls()[0].name(), "get_weather");
+        assert_eq!(
+            choice.message.tool_calls()[0].arguments(),
+            r#"{"city":"Rome"}"#
+        );
+        let usage = res.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, 40);
+        assert_eq!(usage.completion_tokens, 6);
+        assert_eq!(usage.total_tokens, 46);
+
+        let sent = &server.requests()[0];
+        assert_eq!(sent.path, "/models/gemini-2.5-flash:generateContent");
+        assert_eq!(sent.header("x-goog-api-key"), Some("g-key"));
+        assert!(sent.header("authorization").is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn gemini_create_embedding_should_work() -> Result<()> {
+        let server = StubServer::start(|req| {
+            let n = req.json()["requests"].as_array().unwrap().len();
+            let embeddings = (0..n)
+                .map(|i| json!({"values": [i as f64, 0.5]}))
+                .collect::<Vec<_>>();
+            StubResponse::json(200, json!({ "embeddings": embeddings }))
+        })
+        .await;
+        let provider: Box<dyn EmbeddingProvider> =
+            Box::new(GeminiSdk::new(server.url(), "g-key", 0));
+        let req = CreateEmbeddingRequest::new(vec!["hello".to_owned(), "world".to_owned()]);
+        let res = provider.create_embedding(req).await?;
+
+        assert_eq!(res.data.len(), 2);
+        assert_eq!(res.data[1].index, 1);
+        assert_eq!(res.data[1].embedding, vec![1.0, 0.5]);
+        assert_eq!(
+            res.model,
+            EmbeddingModel::Custom(DEFAULT_EMBEDDING_MODEL.to_owned())
+        );
+
+        let sent = &server.requests()[0];
+        assert_eq!(sent.path, "/models/gemini-embedding-001:batchEmbedContents");
+        assert_eq!(sent.header("x-goog-api-key"), Some("g-key"));
+        assert_eq!(
+            sent.json()["requests"][1],
+            json!({"model": "models/gemini-embedding-001", "content": {"parts": [{"text": "world"}]}})
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,515 @@
+//! Several API keys behind one `LlmSdk`, each request sent with the key that has the most headroom.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use reqwest::{header::HeaderMap, StatusCode};
+
+use crate::meta::RateLimit;
+
+/// How long a key is set aside after a 429 that tells no reset time.
+const DEFAULT_BACKOFF: Duration = Duration::from_secs(20);
+/// The window of the limits set by config, and of reported limits without a reset time.
+const LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// An API key with optional per-minute limits, used until the API reports the real ones in the
+/// `x-ratelimit-*` headers.
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+    key: String,
+    requests_per_minute: Option<u64>,
+    tokens_per_minute: Option<u64>,
+}
+
+/// A pool of API keys. Keys answered with 401 are disabled and the request is sent again right
+/// away with another key; keys answered with 429 are set aside until their limits reset.
+#[derive(Debug)]
+pub struct KeyPool {
+    keys: Vec<Mutex<KeyState>>,
+}
+
+/// What the pool knows about one of its keys.
+#[derive(Debug, Clone)]
+pub struct KeyStatus {
+    /// The last 4 characters of the key.
+    pub hint: String,
+
+    pub limit_requests: Option<u64>,
+    pub limit_tokens: Option<u64>,
+    pub remaining_requests: Option<u64>,
+    pub remaining_tokens: Option<u64>,
+
+    /// Set aside until then after a 429.
+    pub exhausted_until: Option<Instant>,
+
+    /// The key was rejected with 401.
+    pub disabled: bool,
+}
+
+/// The estimated tokens of a request, charged to the key each attempt is sent with. Settle it
+/// with the actual usage once the response is in, dropping it keeps the estimate.
+#[derive(Debug, Clone)]
+pub(crate) struct TokenCharge(Arc<Mutex<Charge>>);
+
+/// Marks the requests sent to the SDK's base URL, the only ones a key of the pool is sent with.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ApiRequest;
+
+#[derive(Debug)]
+struct Charge {
+    estimated: usize,
+    /// The key charged last, None once the API reported its remaining tokens.
+    key: Option<(Arc<KeyPool>, usize)>,
+}
+
+#[derive(Debug)]
+struct KeyState {
+    key: ApiKey,
+    limit_requests: Option<u64>,
+    limit_tokens: Option<u64>,
+    remaining_requests: Option<u64>,
+    remaining_tokens: Option<u64>,
+    reset_requests_at: Option<Instant>,
+    reset_tokens_at: Option<Instant>,
+    exhausted_until: Option<Instant>,
+    disabled: bool,
+}
+
+impl ApiKey {
+    pub fn new(key: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            requests_per_minute: None,
+            tokens_per_minute: None,
+        }
+    }
+
+    pub fn with_requests_per_minute(mut self, limit: u64) -> Self {
+        self.requests_per_minute = Some(limit);
+        self
+    }
+
+    /// The token limit, used to weigh the tokens left until the API reports the real numbers. The
+    /// estimated tokens of chat, completion and embedding requests are counted against it.
+    pub fn with_tokens_per_minute(mut self, limit: u64) -> Self {
+        self.tokens_per_minute = Some(limit);
+        self
+    }
+}
+
+impl From<&str> for ApiKey {
+    fn from(key: &str) -> Self {
+        Self::new(key)
+    }
+}
+
+impl From<String> for ApiKey {
+    fn from(key: String) -> Self {
+        Self::new(key)
+    }
+}
+
+impl KeyPool {
+    pub fn new(keys: impl IntoIterator<Item = impl Into<ApiKey>>) -> Self {
+        Self {
+            keys: keys
+                .into_iter()
+                .map(|key| Mutex::new(KeyState::new(key.into())))
+                .collect(),
+        }
+    }
+
+    pub fn status(&self) -> Vec<KeyStatus> {
+        self.keys
+            .iter()
+            .map(|state| state.lock().unwrap().status())
+            .collect()
+    }
+
+    /// Re-enable the keys disabled by a 401, e.g. after rotating them at the provider.
+    pub fn enable_all(&self) {
+        for state in &self.keys {
+            state.lock().unwrap().disabled = false;
+        }
+    }
+
+    /// Whether a key can be picked right now.
+    pub(crate) fn has_usable_key(&self) -> bool {
+        let now = Instant::now();
+        self.keys.iter().any(|state| {
+            let mut state = state.lock().unwrap();
+            state.refresh(now);
+            state.is_usable(now)
+        })
+    }
+
+    /// The index and value of the usable key with the most headroom, counting the request and its
+    /// estimated tokens against it.
+    pub(crate) fn pick(&self, tokens: usize) -> Result<(usize, String)> {
+        let now = Instant::now();
+        let best = self
+            .keys
+            .iter()
+            .enumerate()
+            .filter_map(|(i, state)| {
+                let mut state = state.lock().unwrap();
+                state.refresh(now);
+                state.is_usable(now).then(|| (i, state.headroom()))
+            })
+            // the first of equals wins, so keys are used in order while nothing is known
+            .fold(
+                None,
+                |best: Option<(usize, f64)>, (i, headroom)| match best {
+                    Some((_, h)) if h >= headroom => best,
+                    _ => Some((i, headroom)),
+                },
+            );
+        let Some((i, _)) = best else {
+            let next = self
+                .keys
+                .iter()
+                .filter_map(|state| state.lock().unwrap().available_at())
+                .min();
+            return Err(match next {
+                Some(at) => anyhow!(
+                    "all API keys are rate limited, the first frees up in {:?}",
+                    at.saturating_duration_since(now)
+                ),
+                None => anyhow!("all API keys in the pool are disabled"),
+            });
+        };
+        let mut state = self.keys[i].lock().unwrap();
+        state.count_request(now, tokens);
+        Ok((i, state.key.key.clone()))
+    }
+
+    /// Update the key from the response to a request sent with it. Returns whether the response
+    /// reported the remaining tokens of the key.
+    pub(crate) fn observe(&self, index: usize, status: StatusCode, headers: &HeaderMap) -> bool {
+        let now = Instant::now();
+        let mut state = self.keys[index].lock().unwrap();
+        let rate_limit = RateLimit::from_headers(headers);
+        if let Some(rate_limit) = &rate_limit {
+            state.update(rate_limit, now);
+        }
+        match status {
+            StatusCode::UNAUTHORIZED => {
+                tracing::warn!(key = state.status().hint, "API key rejected, disabling it");
+                state.disabled = true;
+            }
+            StatusCode::TOO_MANY_REQUESTS => {
+                let backoff = rate_limit
+                    .and_then(|r| r.reset_requests.max(r.reset_tokens))
+                    .or_else(|| retry_after(headers))
+                    .unwrap_or(DEFAULT_BACKOFF);
+                state.exhausted_until = Some(now + backoff);
+            }
+            _ => {}
+        }
+        rate_limit.is_some_and(|r| r.remaining_tokens.is_some())
+    }
+}
+
+impl TokenCharge {
+    pub(crate) fn new(estimated: usize) -> Self {
+        Self(Arc::new(Mutex::new(Charge {
+            estimated,
+            key: None,
+        })))
+    }
+
+    pub(crate) fn estimated(&self) -> usize {
+        self.0.lock().unwrap().estimated
+    }
+
+    /// Remember the key charged by an attempt, or forget it when the API reported the real numbers.
+    pub(crate) fn charged(&self, key: Option<(Arc<KeyPool>, usize)>) {
+        self.0.lock().unwrap().key = key;
+    }
+
+    /// Give back the over-estimate, or take the under-estimate.
+    pub(crate) fn settle(self, actual_tokens: usize) {
+        let charge = self.0.lock().unwrap();
+        if let Some((pool, index)) = &charge.key {
+            let mut state = pool.keys[*index].lock().unwrap();
+            let limit = state.limit_tokens.unwrap_or(u64::MAX);
+            if let Some(remaining) = &mut state.remaining_tokens {
+                *remaining = (*remaining + charge.estimated as u64)
+                    .saturating_sub(actual_tokens as u64)
+                    .min(limit);
+            }
+        }
+    }
+}
+
+impl KeyState {
+    fn new(key: ApiKey) -> Self {
+        Self {
+            limit_requests: key.requests_per_minute,
+            limit_tokens: key.tokens_per_minute,
+            remaining_requests: key.requests_per_minute,
+            remaining_tokens: key.tokens_per_minute,
+            reset_requests_at: None,
+            reset_tokens_at: None,
+            exhausted_until: None,
+            disabled: false,
+            key,
+        }
+    }
+
+    fn status(&self) -> KeyStatus {
+        let chars = self.key.key.chars().count();
+        KeyStatus {
+            hint: self.key.key.chars().skip(chars.saturating_sub(4)).collect(),
+            limit_requests: self.limit_requests,
+            limit_tokens: self.limit_tokens,
+            remaining_requests: self.remaining_requests,
+            remaining_tokens: self.remaining_tokens,
+            exhausted_until: self.exhausted_until,
+            disabled: self.disabled,
+        }
+    }
+
+    /// Restore the limits whose window has passed.
+    fn refresh(&mut self, now: Instant) {
+        if self.reset_requests_at.is_some_and(|at| at <= now) {
+            self.remaining_requests = self.limit_requests;
+            self.reset_requests_at = None;
+        }
+        if self.reset_tokens_at.is_some_and(|at| at <= now) {
+            self.remaining_tokens = self.limit_tokens;
+            self.reset_tokens_at = None;
+        }
+        if self.exhausted_until.is_some_and(|at| at <= now) {
+            self.exhausted_until = None;
+        }
+    }
+
+    fn is_usable(&self, now: Instant) -> bool {
+        !self.disabled
+            && self.exhausted_until.is_none_or(|at| at <= now)
+            && self.remaining_requests != Some(0)
+    }
+
+    /// When a key that is out of requests can be used again, None if it is disabled.
+    fn available_at(&self) -> Option<Instant> {
+        if self.disabled {
+            return None;
+        }
+        let limited = match self.remaining_requests {
+            Some(0) => self.reset_requests_at,
+            _ => None,
+        };
+        self.exhausted_until.max(limited)
+    }
+
+    /// The smaller of the shares of requests and tokens left, 1 when unknown.
+    fn headroom(&self) -> f64 {
+        let share = |remaining: Option<u64>, limit: Option<u64>| match (remaining, limit) {
+            (Some(remaining), Some(limit)) if limit > 0 => remaining as f64 / limit as f64,
+            (Some(0), _) => 0.0,
+            _ => 1.0,
+        };
+        share(self.remaining_requests, self.limit_requests)
+            .min(share(self.remaining_tokens, self.limit_tokens))
+    }
+
+    /// Count a request until the response reports the real numbers, so concurrent requests spread.
+    fn count_request(&mut self, now: Instant, tokens: usize) {
+        if let Some(remaining) = &mut self.remaining_requests {
+            *remaining = remaining.saturating_sub(1);
+            self.reset_requests_at.get_or_insert(now + LIMIT_WINDOW);
+        }
+        if let Some(remaining) = &mut self.remaining_tokens {
+            *remaining = remaining.saturating_sub(tokens as u64);
+            self.reset_tokens_at.get_or_insert(now + LIMIT_WINDOW);
+        }
+    }
+
+    fn update(&mut self, rate_limit: &RateLimit, now: Instant) {
+        if rate_limit.limit_requests.is_some() {
+            self.limit_requests = rate_limit.limit_requests;
+        }
+        if rate_limit.limit_tokens.is_some() {
+            self.limit_tokens = rate_limit.limit_tokens;
+        }
+        if rate_limit.remaining_requests.is_some() {
+            self.remaining_requests = rate_limit.remaining_requests;
+            self.reset_requests_at = Some(now + rate_limit.reset_requests.unwrap_or(LIMIT_WINDOW));
+        }
+        if rate_limit.remaining_tokens.is_some() {
+            self.remaining_tokens = rate_limit.remaining_tokens;
+            self.reset_tokens_at = Some(now + rate_limit.reset_tokens.unwrap_or(LIMIT_WINDOW));
+        }
+    }
+}
+
+/// The `retry-after` header, in seconds.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get("retry-after")?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chat_completion::{ChatCompletionMessage, ChatCompletionRequestBuilder},
+        stub::{StubResponse, StubServer},
+        LlmSdk,
+    };
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[test]
+    fn key_pool_should_pick_most_headroom() -> Result<()> {
+        let pool = KeyPool::new([
+            ApiKey::new("sk-aaaa").with_requests_per_minute(2),
+            ApiKey::new("sk-bbbb").with_requests_per_minute(4),
+        ]);
+        // equal headroom: the first key; then the one with the larger share left
+        let picks = (0..6)
+            .map(|_| pool.pick(0).map(|(i, _)| i))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(picks, vec![0, 1, 1, 0, 1, 1]);
+        assert!(pool.pick(0).is_err());
+
+        let status = pool.status();
+        assert_eq!(status[0].hint, "aaaa");
+        assert_eq!(status[1].remaining_requests, Some(0));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn key_pool_should_charge_estimated_tokens() -> Result<()> {
+        let pool = KeyPool::new([ApiKey::new("sk-aaaa").with_tokens_per_minute(10_000)]);
+        pool.pick(4_000)?;
+        assert_eq!(pool.status()[0].remaining_tokens, Some(6_000));
+
+        // settled with the usage when the response doesn't report the remaining tokens
+        let server = StubServer::start(|_| {
+            StubResponse::json(
+                200,
+                json!({
+                    "id": "chatcmpl-1",
+                    "object": "chat.completion",
+                    "created": 1700000000,
+                    "model": "gpt-3.5-turbo-0125",
+                    "choices": [{"index": 0, "finish_reason": "stop", "message": {"role": "assistant", "content": "hi"}}],
+                    "usage": {"prompt_tokens": 8, "completion_tokens": 1, "total_tokens": 9}
+                }),
+            )
+        })
+        .await;
+        let pool = Arc::new(KeyPool::new([
+            ApiKey::new("sk-aaaa").with_tokens_per_minute(10_000)
+        ]));
+        let sdk = LlmSdk::new_with_key_pool(server.url(), pool.clone(), 0);
+        let req = ChatCompletionRequestBuilder::default()
+            .messages(vec![ChatCompletionMessage::new_user("hi", "")])
+            .max_tokens(500usize)
+            .build()?;
+        sdk.chat_completion(req).await?;
+        assert_eq!(pool.status()[0].remaining_tokens, Some(9_991));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn key_pool_should_only_authenticate_api_requests() -> Result<()> {
+        let api =
+            StubServer::start(|_| StubResponse::json(200, json!({"object": "list", "data": []})))
+                .await;
+        let other = StubServer::start(|_| StubResponse::json(200, json!({}))).await;
+        let pool = Arc::new(KeyPool::new([
+            ApiKey::new("sk-aaaa").with_requests_per_minute(2)
+        ]));
+        let sdk = LlmSdk::new_with_key_pool(api.url(), pool.clone(), 0);
+
+        sdk.list_models().await?;
+        sdk.client.get(other.url()).send().await?;
+
+        assert_eq!(
+            api.requests()[0].header("authorization"),
+            Some("Bearer sk-aaaa")
+        );
+        assert_eq!(other.requests()[0].header("authorization"), None);
+        // and the request isn't counted against the key
+        assert_eq!(pool.status()[0].remaining_requests, Some(1));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn key_pool_should_rotate_on_rate_limits() -> Result<()> {
+        let server = StubServer::start(|req| match req.header("authorization") {
+            Some("Bearer sk-limited") => {
+                StubResponse::json(429, json!({"error": {"message": "slow down"}}))
+                    .with_header("x-ratelimit-remaining-requests", "0")
+                    .with_header("x-ratelimit-reset-requests", "6m0s")
+            }
+            Some("Bearer sk-revoked") => {
+                StubResponse::json(401, json!({"error": {"message": "invalid key"}}))
+            }
+            _ => StubResponse::json(
+                200,
+                json!({
+                    "id": "chatcmpl-1",
+                    "object": "chat.completion",
+                    "created": 1700000000,
+                    "model": "gpt-3.5-turbo-0125",
+                    "choices": [{"index": 0, "finish_reason": "stop", "message": {"role": "assistant", "content": "hi"}}]
+                }),
+            )
+            .with_header("x-ratelimit-limit-requests", "100")
+            .with_header("x-ratelimit-remaining-requests", "99"),
+        })
+        .await;
+        let pool = Arc::new(KeyPool::new(["sk-limited", "sk-revoked", "sk-ok"]));
+        let sdk = LlmSdk::new_with_key_pool(server.url(), pool.clone(), 2);
+        let req = ChatCompletionRequestBuilder::default()
+            .messages(vec![ChatCompletionMessage::new_user("hi", "")])
+            .build()?;
+
+        // the 429 is retried with another key, and so is the 401 of that key
+        let res = sdk.chat_completion(req.clone()).await?;
+        assert_eq!(res.id, "chatcmpl-1");
+        sdk.chat_completion(req).await?;
+
+        let keys: Vec<_> = server
+            .requests()
+            .iter()
+            .map(|r| r.header("authorization").unwrap_or_default().to_owned())
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                "Bearer sk-limited",
+                "Bearer sk-revoked",
+                "Bearer sk-ok",
+                "Bearer sk-ok"
+            ]
+        );
+        let status = pool.status();
+        assert!(status[0].exhausted_until.is_some());
+        assert!(status[1].disabled);
+        assert_eq!(status[2].remaining_requests, Some(99));
+
+        // without another key the 401 is the answer
+        let pool = Arc::new(KeyPool::new(["sk-revoked"]));
+        let sdk = LlmSdk::new_with_key_pool(server.url(), pool, 2);
+        let req = ChatCompletionRequestBuilder::default()
+            .messages(vec![ChatCompletionMessage::new_user("hi", "")])
+            .build()?;
+        let err = sdk.chat_completion(req).await.unwrap_err();
+        let err = err.downcast_ref::<crate::meta::ApiError>().unwrap();
+        assert_eq!(err.status, StatusCode::UNAUTHORIZED);
+        Ok(())
+    }
+}
@@ -0,0 +1,294 @@
+use std::{fmt, ops::Deref, time::Duration};
+
+use reqwest::{header::HeaderMap, Response, StatusCode};
+
+const REQUEST_ID: &str = "x-request-id";
+const PROCESSING_MS: &str = "openai-processing-ms";
+
+/// A response body together with the metadata of the HTTP response.
+#[derive(Debug, Clone)]
+pub struct WithMeta<T> {
+    pub data: T,
+    pub meta: ResponseMeta,
+}
+
+/// What the API tells about a request besides the body. Quote the request id when contacting OpenAI support.
+#[derive(Debug, Clone)]
+pub struct ResponseMeta {
+    pub status: StatusCode,
+
+    /// The `x-request-id` header.
+    pub request_id: Option<String>,
+
+    /// The `openai-processing-ms` header: time spent by the API on the request.
+    pub processing_time: Option<Duration>,
+
+    /// The `x-ratelimit-*` headers, None if the endpoint doesn't send them.
+    pub rate_limit: Option<RateLimit>,
+
+    pub headers: HeaderMap,
+
+    /// Whether the response was answered from the response cache, without a request to the API.
+    /// Cached responses have a 200 status and no headers.
+    pub cached: bool,
+}
+
+/// Rate-limit state of the organization, as of a response.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    /// The maximum number of requests permitted before exhausting the rate limit.
+    pub limit_requests: Option<u64>,
+
+    /// The maximum number of tokens permitted before exhausting the rate limit.
+    pub limit_tokens: Option<u64>,
+
+    pub remaining_requests: Option<u64>,
+
+    pub remaining_tokens: Option<u64>,
+
+    /// The time until the request rate limit resets to its initial state.
+    pub reset_requests: Option<Duration>,
+
+    /// The time until the token rate limit resets to its initial state.
+    pub reset_tokens: Option<Duration>,
+}
+
+/// An error response of the API. Find it with `err.downcast_ref::<ApiError>()`.
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub request_id: Option<String>,
+    pub body: String,
+}
+
+impl<T> WithMeta<T> {
+    pub fn new(data: T, meta: ResponseMeta) -> Self {
+        Self { data, meta }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> WithMeta<U> {
+        WithMeta {
+            data: f(self.data),
+            meta: self.meta,
+        }
+    }
+}
+
+impl<T> Deref for WithMeta<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
+impl ResponseMeta {
+    pub fn from_headers(status: StatusCode, headers: &HeaderMap) -> Self {
+        Self {
+            status,
+            request_id: header_str(headers, REQUEST_ID).map(|s| s.to_owned()),
+            processing_time: header_str(headers, PROCESSING_MS)
+                .and_then(|s| s.parse().ok())
+                .map(Duration::from_millis),
+            rate_limit: RateLimit::from_headers(headers),
+            headers: headers.clone(),
+            cached: false,
+        }
+    }
+
+    /// The metadata of a response answered from the response cache.
+    pub fn cached() -> Self {
+        Self {
+            cached: true,
+            ..Self::from_headers(StatusCode::OK, &HeaderMap::new())
+        }
+    }
+}
+
+impl From<&Response> for ResponseMeta {
+    fn from(res: &Response) -> Self {
+        Self::from_headers(res.status(), res.headers())
+    }
+}
+
+impl ApiError {
+    /// Whether the same request may succeed later: timeouts, conflicts, rate limits and server errors.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self.status.as_u16(), 408 | 409 | 429) || self.status.is_server_error()
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.request_id {
+            Some(id) => write!(f, "API failed (request id {id}): {:#?}", self.body),
+            None => write!(f, "API failed: {:#?}", self.body),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl RateLimit {
+    /// Parse the `x-ratelimit-*` headers, None if there are none.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let number = |name: &str| header_str(headers, name).and_then(|s| s.parse().ok());
+        let duration = |name: &str| header_str(headers, name).and_then(parse_duration);
+        let ret = Self {
+            limit_requests: number("x-ratelimit-limit-requests"),
+            limit_tokens: number("x-ratelimit-limit-tokens"),
+            remaining_requests: number("x-ratelimit-remaining-requests"),
+            remaining_tokens: number("x-ratelimit-remaining-tokens"),
+            reset_requests: duration("x-ratelimit-reset-requests"),
+            reset_tokens: duration("x-ratelimit-reset-tokens"),
+        };
+        (ret != Self::default()).then_some(ret)
+    }
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+/// Parse the reset durations OpenAI sends, e.g. "20ms", "1s", "6m0s" or "1h2m3.5s".
+fn parse_duration(s: &str) -> Option<Duration> {
+    let mut total = 0.0;
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let end = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let value: f64 = rest[..end].parse().ok()?;
+        rest = &rest[end..];
+        let (scale, len) = if rest.starts_with("ms") {
+            (0.001, 2)
+        } else if rest.starts_with('h') {
+            (3600.0, 1)
+        } else if rest.starts_with('m') {
+            (60.0, 1)
+        } else if rest.starts_with('s') {
+            (1.0, 1)
+        } else {
+            return None;
+        };
+        total += value * scale;
+        rest = &rest[len..];
+    }
+    Duration::try_from_secs_f64(total).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chat_completion::{ChatCompletionMessage, ChatCompletionRequestBuilder},
+        stub::{StubResponse, StubServer},
+        LlmSdk,
+    };
+    use reqwest::header::HeaderValue;
+    use serde_json::json;
+
+    #[test]
+    fn parse_duration_should_work() {
+        assert_eq!(parse_duration("20ms"), Some(Duration::from_millis(20)));
+        assert_eq!(parse_duration("6m0s"), Some(Duration::from_secs(360)));
+        assert_eq!(
+            parse_duration("1h2m3.5s"),
+            Some(Duration::from_millis(3_723_500))
+        );
+        assert_eq!(parse_duration("soon"), None);
+        assert_eq!(parse_duration("99999999999999999999h"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn response_meta_should_parse_headers() {
+        let mut headers = HeaderMap::new();
+        for (name, value) in [
+            ("x-request-id", "req_123"),
+            ("openai-processing-ms", "412"),
+            ("x-ratelimit-limit-requests", "10000"),
+            ("x-ratelimit-remaining-requests", "9999"),
+            ("x-ratelimit-remaining-tokens", "1999980"),
+            ("x-ratelimit-reset-requests", "6ms"),
+            ("x-ratelimit-reset-tokens", "0s"),
+        ] {
+            headers.insert(name, HeaderValue::from_static(value));
+        }
+        let meta = ResponseMeta::from_headers(StatusCode::OK, &headers);
+        assert_eq!(meta.request_id.as_deref(), Some("req_123"));
+        assert_eq!(meta.processing_time, Some(Duration::from_millis(412)));
+        let rate_limit = meta.rate_limit.unwrap();
+        assert_eq!(rate_limit.limit_requests, Some(10000));
+        assert_eq!(rate_limit.limit_tokens, None);
+        assert_eq!(rate_limit.remaining_tokens, Some(1999980));
+        assert_eq!(rate_limit.reset_requests, Some(Duration::from_millis(6)));
+
+        let meta = ResponseMeta::from_headers(StatusCode::OK, &HeaderMap::new());
+        assert!(meta.rate_limit.is_none());
+    }
+
+    #[tokio::test]
+    async fn sdk_should_keep_response_meta() -> anyhow::Result<()> {
+        let server = StubServer::start(|req| {
+            if req.json()["messages"][0]["content"] == "fail" {
+                return StubResponse::json(400, json!({"error": {"message": "bad"}}))
+                    .with_header("x-request-id", "req_bad");
+            }
+            StubResponse::json(
+                200,
+                json!({
+                    "id": "chatcmpl-1",
+                    "object": "chat.completion",
+                    "created": 1700000000,
+                    "model": "gpt-3.5-turbo-0125",
+                    "choices": [{
+                        "index": 0,
+                        "finish_reason": "stop",
+                        "message": {"role": "assistant", "content": "hi"}
+                    }],
+                    "usage": {"prompt_tokens": 8, "completion_tokens": 1, "total_tokens": 9}
+                }),
+            )
+            .with_header("x-request-id", "req_ok")
+            .with_header("openai-processing-ms", "35")
+            .with_header("x-ratelimit-remaining-requests", "41")
+        })
+        .await;
+        let sdk = LlmSdk::new(server.url(), "sk-test", 0);
+        assert!(sdk.rate_limit().is_none());
+
+        let req = |content: &str| {
+            ChatCompletionRequestBuilder::default()
+                .messages(vec![ChatCompletionMessage::new_user(content, "")])
+                .build()
+                .unwrap()
+        };
+        let res = sdk.chat_completion_with_meta(req("hi")).await?;
+        assert_eq!(res.id, "chatcmpl-1");
+        assert_eq!(res.meta.status, StatusCode::OK);
+        assert_eq!(res.meta.request_id.as_deref(), Some("req_ok"));
+        assert_eq!(res.meta.processing_time, Some(Duration::from_millis(35)));
+        assert_eq!(sdk.rate_limit().unwrap().remaining_requests, Some(41));
+        let sent = &server.requests()[0];
+        assert_eq!(sent.method, "POST");
+        assert_eq!(sent.path, "/chat/completions");
+        assert_eq!(sent.header("Authorization"), Some("Bearer sk-test"));
+
+        let err = sdk.chat_completion(req("fail")).await.unwrap_err();
+        assert!(err.to_string().contains("req_bad"));
+        let err = err.downcast_ref::<ApiError>().unwrap();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+        assert!(!err.is_retryable());
+        // responses without rate-limit headers keep the last snapshot
+        assert_eq!(sdk.rate_limit().unwrap().remaining_requests, Some(41));
+        Ok(())
+    }
+}
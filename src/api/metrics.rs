@@ -0,0 +1,609 @@
+//! Latency, status and retry metrics of the SDK's calls, and the token usage of the chat,
+//! completion and embedding calls, for dashboards that shouldn't have to parse logs.
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use futures::{Stream, StreamExt};
+use reqwest::StatusCode;
+use reqwest_middleware::RequestBuilder;
+
+use crate::{
+    circuit_breaker,
+    meta::{ApiError, WithMeta},
+};
+
+/// Receives the metrics of each call. Everything is optional to implement: the default does
+/// nothing.
+pub trait Metrics: fmt::Debug + Send + Sync {
+    /// Called once per call: when its response is in, when it failed, or when its stream ended or
+    /// was dropped.
+    fn record(&self, call: &CallMetrics) {
+        let _ = call;
+    }
+}
+
+/// What one call of the SDK took.
+#[derive(Debug, Clone)]
+pub struct CallMetrics {
+    /// The API path with ids templated, e.g. "chat/completions" or "files/{id}/content".
+    pub endpoint: String,
+
+    /// The requested model.
+    pub model: String,
+
+    /// The HTTP status of the final response, None if there was none (e.g. a timeout).
+    pub status: Option<StatusCode>,
+
+    /// From the call to its response, or to the end of its stream (or its drop).
+    pub latency: Duration,
+
+    /// From the call to its first streamed chunk, streams only.
+    pub time_to_first_token: Option<Duration>,
+
+    /// Attempts made beyond the first one.
+    pub retries: u32,
+
+    /// Token usage, None for cache hits: nothing was spent.
+    pub prompt_tokens: Option<usize>,
+    pub completion_tokens: Option<usize>,
+
+    /// The call was answered from the response cache, without a request to the API.
+    pub cached: bool,
+}
+
+/// Reports to the `metrics` crate facade, for whichever exporter the application installed:
+///
+/// - `llm_sdk_requests_total` counter, by endpoint, model and status ("error" without response)
+/// - `llm_sdk_request_duration_seconds` histogram, by endpoint and model
+/// - `llm_sdk_time_to_first_token_seconds` histogram, by endpoint and model
+/// - `llm_sdk_retries_total` counter, by endpoint and model
+/// - `llm_sdk_tokens_total` counter, by endpoint, model and type ("input" or "output")
+/// - `llm_sdk_cache_hits_total` counter, by endpoint and model; cache hits count in no other metric
+#[cfg(feature = "metrics")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MetricsFacade;
+
+/// The attempts of a request, counted by the middleware that finds it in the request extensions,
+/// along with the path they were sent to.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct AttemptCounter {
+    attempts: Arc<AtomicU32>,
+    path: Arc<OnceLock<String>>,
+}
+
+/// Times a call and reports it to the SDK's metrics, if any.
+pub(crate) struct Observer {
+    metrics: Option<Arc<dyn Metrics>>,
+    endpoint: Endpoint,
+    model: String,
+    start: Instant,
+    attempts: AttemptCounter,
+    cached: AtomicBool,
+}
+
+/// How an observer names the endpoint of its call.
+enum Endpoint {
+    Fixed(&'static str),
+    /// After the path the request was sent to, relative to the base URL's.
+    Path(String),
+}
+
+impl AttemptCounter {
+    pub(crate) fn increment(&self, path: &str) {
+        self.attempts.fetch_add(1, Ordering::Relaxed);
+        self.path.get_or_init(|| path.to_owned());
+    }
+
+    fn retries(&self) -> u32 {
+        self.attempts.load(Ordering::Relaxed).saturating_sub(1)
+    }
+}
+
+impl Observer {
+    pub(crate) fn new(
+        metrics: Option<Arc<dyn Metrics>>,
+        endpoint: &'static str,
+        model: String,
+    ) -> Self {
+        Self::with_endpoint(metrics, Endpoint::Fixed(endpoint), model)
+    }
+
+    /// An observer naming the endpoint after the path the request is sent to, for the calls
+    /// going through the shared send path.
+    pub(crate) fn for_path(
+        metrics: Option<Arc<dyn Metrics>>,
+        base_url: &str,
+        model: String,
+    ) -> Self {
+        let base_path = reqwest::Url::parse(base_url)
+            .map(|url| url.path().trim_end_matches('/').to_owned())
+            .unwrap_or_default();
+        Self::with_endpoint(metrics, Endpoint::Path(base_path), model)
+    }
+
+    fn with_endpoint(metrics: Option<Arc<dyn Metrics>>, endpoint: Endpoint, model: String) -> Self {
+        Self {
+            metrics,
+            endpoint,
+            model,
+            start: Instant::now(),
+            attempts: AttemptCounter::default(),
+            cached: AtomicBool::new(false),
+        }
+    }
+
+    fn endpoint(&self) -> String {
+        match &self.endpoint {
+            Endpoint::Fixed(endpoint) => (*endpoint).to_owned(),
+            Endpoint::Path(base_path) => {
+                let path = self
+                    .attempts
+                    .path
+                    .get()
+                    .map(String::as_str)
+                    .unwrap_or_default();
+                let path = path.strip_prefix(base_path.as_str()).unwrap_or(path);
+                circuit_breaker::route(path.trim_matches('/'))
+            }
+        }
+    }
+
+    /// The call is answered from the response cache.
+    pub(crate) fn cached(&self) {
+        self.cached.store(true, Ordering::Relaxed);
+    }
+
+    /// Count the attempts of the request.
+    pub(crate) fn attach(&self, req: RequestBuilder) -> RequestBuilder {
+        match self.metrics {
+            Some(_) => req.with_extension(self.attempts.clone()),
+            None => req,
+        }
+    }
+
+    /// Report a call, with the prompt and completion tokens of its response.
+    pub(crate) fn finish<T>(
+        &self,
+        res: &Result<WithMeta<T>>,
+        usage: impl FnOnce(&T) -> Option<(usize, Option<usize>)>,
+    ) {
+        let (status, usage) = match res {
+            Ok(res) => (Some(res.meta.status), usage(&res.data)),
+            Err(e) => (status_of(e), None),
+        };
+        self.report(status, usage, None);
+    }
+
+    /// Report the call once its stream has ended or was dropped, with the time to its first chunk.
+    pub(crate) fn observe_stream<T, S>(
+        self,
+        res: Result<S>,
+    ) -> Result<impl Stream<Item = Result<T>>>
+    where
+        S: Stream<Item = Result<T>> + Send + 'static,
+    {
+        let stream = match res {
+            Ok(stream) => stream,
+            Err(e) => {
+                self.report(status_of(&e), None, None);
+                return Err(e);
+            }
+        };
+        let report = StreamReport {
+            observer: self,
+            first: None,
+            status: Some(StatusCode::OK),
+        };
+        Ok(futures::stream::unfold(
+            (stream.boxed(), report),
+            |(mut stream, mut report)| async move {
+                let item = stream.next().await?;
+                report
+                    .first
+                    .get_or_insert_with(|| report.observer.start.elapsed());
+                if let Err(e) = &item {
+                    report.status = status_of(e);
+                }
+                Some((item, (stream, report)))
+            },
+        ))
+    }
+
+    fn report(
+        &self,
+        status: Option<StatusCode>,
+        usage: Option<(usize, Option<usize>)>,
+        time_to_first_token: Option<Duration>,
+    ) {
+        let Some(metrics) = &self.metrics else {
+            return;
+        };
+        let cached = self.cached.load(Ordering::Relaxed);
+        let usage = usage.filter(|_| !cached);
+        metrics.record(&CallMetrics {
+            endpoint: self.endpoint(),
+            model: self.model.clone(),
+            status,
+            latency: self.start.elapsed(),
+            time_to_first_token,
+            retries: self.attempts.retries(),
+            prompt_tokens: usage.map(|(prompt, _)| prompt),
+            completion_tokens: usage.and_then(|(_, completion)| completion),
+            cached,
+        });
+    }
+}
+
+/// Reports a streamed call when dropped: at the end of the stream, or when the caller drops it
+/// before.
+struct StreamReport {
+    observer: Observer,
+    first: Option<Duration>,
+    status: Option<StatusCode>,
+}
+
+impl Drop for StreamReport {
+    fn drop(&mut self) {
+        self.observer.report(self.status, None, self.first);
+    }
+}
+
+fn status_of(err: &anyhow::Error) -> Option<StatusCode> {
+    err.downcast_ref::<ApiError>().map(|e| e.status)
+}
+
+#[cfg(feature = "metrics")]
+impl Metrics for MetricsFacade {
+    fn record(&self, call: &CallMetrics) {
+        use ::metrics::{counter, histogram, Label};
+
+        let labels = vec![
+            Label::new("endpoint", call.endpoint.clone()),
+            Label::new("model", call.model.clone()),
+        ];
+        let with = |key: &'static str, value: String| {
+            let mut labels = labels.clone();
+            labels.push(Label::new(key, value));
+            labels
+        };
+        if call.cached {
+            counter!("llm_sdk_cache_hits_total", labels).increment(1);
+            return;
+        }
+        let status = match call.status {
+            Some(status) => status.as_u16().to_string(),
+            None => "error".to_owned(),
+        };
+        counter!("llm_sdk_requests_total", with("status", status)).increment(1);
+        histogram!("llm_sdk_request_duration_seconds", labels.clone())
+            .record(call.latency.as_secs_f64());
+        if let Some(ttft) = call.time_to_first_token {
+            histogram!("llm_sdk_time_to_first_token_seconds", labels.clone())
+                .record(ttft.as_secs_f64());
+        }
+        if call.retries > 0 {
+            counter!("llm_sdk_retries_total", labels.clone()).increment(call.retries as u64);
+        }
+        for (typ, tokens) in [
+            ("input", call.prompt_tokens),
+            ("output", call.completion_tokens),
+        ] {
+            if let Some(tokens) = tokens {
+                counter!("llm_sdk_tokens_total", with("type", typ.to_owned()))
+                    .increment(tokens as u64);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chat_completion::{ChatCompletionMessage, ChatCompletionRequestBuilder},
+        stub::{StubResponse, StubServer},
+        LlmSdk,
+    };
+    use serde_json::json;
+    use std::sync::{atomic::AtomicUsize, Mutex};
+
+    #[derive(Debug, Default)]
+    struct Recorded(Mutex<Vec<CallMetrics>>);
+
+    impl Metrics for Recorded {
+        fn record(&self, call: &CallMetrics) {
+            self.0.lock().unwrap().push(call.clone());
+        }
+    }
+
+    fn chat_request() -> crate::chat_completion::ChatCompletionRequest {
+        ChatCompletionRequestBuilder::default()
+            .messages(vec![ChatCompletionMessage::new_user("hi", "")])
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn sdk_should_record_call_metrics() -> Result<()> {
+        // the first attempt fails, the retry succeeds, then the API rejects the request
+        let calls = Arc::new(AtomicUsize::new(0));
+        let server = StubServer::start(move |_| match calls.fetch_add(1, Ordering::SeqCst) {
+            0 => StubResponse::json(503, json!({"error": "overloaded"})),
+            1 => StubResponse::json(
+                200,
+                json!({
+                    "id": "chatcmpl-1",
+                    "object": "chat.completion",
+                    "created": 1700000000,
+                    "model": "gpt-4o-2024-08-06",
+                    "choices": [{"index": 0, "finish_reason": "stop", "message": {"role": "assistant", "content": "hi"}}],
+                    "usage": {"prompt_tokens": 9, "completion_tokens": 1, "total_tokens": 10}
+                }),
+            ),
+            _ => StubResponse::json(400, json!({"error": "bad request"})),
+        })
+        .await;
+        let recorded = Arc::new(Recorded::default());
+        let sdk = LlmSdk::new(server.url(), "", 1).with_metrics(recorded.clone());
+
+        sdk.chat_completion(chat_request()).await?;
+        assert!(sdk.chat_completion(chat_request()).await.is_err());
+
+        let calls = recorded.0.lock().unwrap();
+        assert_eq!(calls.len(), 2);
+        let model = crate::usage::model_id(&chat_request().model);
+        assert_eq!(calls[0].endpoint, "chat/completions");
+        assert_eq!(calls[0].model, model);
+        assert_eq!(calls[0].status, Some(StatusCode::OK));
+        assert_eq!(calls[0].retries, 1);
+        assert_eq!(calls[0].prompt_tokens, Some(9));
+        assert_eq!(calls[0].completion_tokens, Some(1));
+        assert_eq!(calls[0].time_to_first_token, None);
+        assert_eq!(calls[1].status, Some(StatusCode::BAD_REQUEST));
+        assert_eq!(calls[1].retries, 0);
+        assert_eq!(calls[1].prompt_tokens, None);
+        assert!(!calls[0].cached && !calls[1].cached);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sdk_should_record_every_endpoint() -> Result<()> {
+        let server = StubServer::start(|req| {
+            if req.path.ends_with("/content") {
+                return StubResponse {
+                    status: 200,
+                    headers: vec![],
+                    body: b"{}".to_vec(),
+                };
+            }
+            StubResponse::json(
+                200,
+                json!({
+                    "id": "file-abc123",
+                    "object": "file",
+                    "bytes": 2,
+                    "created_at": 1677610602,
+                    "filename": "mydata.jsonl",
+                    "purpose": "batch",
+                }),
+            )
+        })
+        .await;
+        let recorded = Arc::new(Recorded::default());
+        let sdk = LlmSdk::new(format!("{}/v1", server.url()), "", 0).with_metrics(recorded.clone());
+
+        sdk.retrieve_file("file-abc123").await?;
+        assert_eq!(sdk.file_content("file-abc123").await?, "{}");
+
+        let calls = recorded.0.lock().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].endpoint, "files/{id}");
+        assert_eq!(calls[0].status, Some(StatusCode::OK));
+        assert_eq!(calls[0].prompt_tokens, None);
+        assert_eq!(calls[1].endpoint, "files/{id}/content");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sdk_should_mark_cache_hits() -> Result<()> {
+        let server = StubServer::start(|_| {
+            StubResponse::json(
+                200,
+                json!({
+                    "id": "chatcmpl-1",
+                    "object": "chat.completion",
+                    "created": 1700000000,
+                    "model": "gpt-4o-2024-08-06",
+                    "choices": [{"index": 0, "finish_reason": "stop", "message": {"role": "assistant", "content": "hi"}}],
+                    "usage": {"prompt_tokens": 9, "completion_tokens": 1, "total_tokens": 10}
+                }),
+            )
+        })
+        .await;
+        let recorded = Arc::new(Recorded::default());
+        let sdk = LlmSdk::new(server.url(), "", 0)
+            .with_metrics(recorded.clone())
+            .with_response_cache(crate::response_cache::MemoryCache::new().into_shared());
+
+        sdk.chat_completion(chat_request()).await?;
+        sdk.chat_completion(chat_request()).await?;
+
+        let calls = recorded.0.lock().unwrap();
+        assert!(!calls[0].cached);
+        assert_eq!(calls[0].prompt_tokens, Some(9));
+        assert!(calls[1].cached);
+        assert_eq!(calls[1].prompt_tokens, None);
+        assert_eq!(calls[1].completion_tokens, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sdk_should_record_stream_metrics_at_its_end() -> Result<()> {
+        use crate::completion::{CompletionModel, CompletionRequest, CompletionResponse};
+        use futures::TryStreamExt;
+
+        let server = StubServer::start(|_| {
+            let chunk = json!({
+                "id": "cmpl-1",
+                "object": "text_completion",
+                "created": 1700000000,
+                "model": "gpt-3.5-turbo-instruct",
+                "choices": [{"text": "Hello", "index": 0, "logprobs": null, "finish_reason": "stop"}]
+            });
+            StubResponse {
+                status: 200,
+                headers: vec![("content-type".into(), "text/event-stream".into())],
+                body: format!("data: {chunk}\n\ndata: [DONE]\n\n").into_bytes(),
+            }
+        })
+        .await;
+        let recorded = Arc::new(Recorded::default());
+        let sdk = LlmSdk::new(server.url(), "", 0).with_metrics(recorded.clone());
+        let req = CompletionRequest::new(CompletionModel::default(), "Say hello");
+
+        let stream = sdk.completion_stream(req).await?;
+        assert!(recorded.0.lock().unwrap().is_empty());
+        let chunks: Vec<CompletionResponse> = stream.try_collect().await?;
+        assert_eq!(chunks.len(), 1);
+
+        let calls = recorded.0.lock().unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].endpoint, "completions");
+        assert_eq!(calls[0].status, Some(StatusCode::OK));
+        let ttft = calls[0].time_to_first_token.unwrap();
+        assert!(ttft <= calls[0].latency);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sdk_should_record_streams_dropped_early() -> Result<()> {
+        use crate::completion::{CompletionModel, CompletionRequest};
+
+        let server = StubServer::start(|_| {
+            let chunk = |text: &str| {
+                json!({
+                    "id": "cmpl-1",
+                    "object": "text_completion",
+                    "created": 1700000000,
+                    "model": "gpt-3.5-turbo-instruct",
+                    "choices": [{"text": text, "index": 0, "logprobs": null, "finish_reason": null}]
+                })
+            };
+            StubResponse {
+                status: 200,
+                headers: vec![("content-type".into(), "text/event-stream".into())],
+                body: format!(
+                    "data: {}\n\ndata: {}\n\ndata: [DONE]\n\n",
+                    chunk("Hello"),
+                    chunk(" world")
+                )
+                .into_bytes(),
+            }
+        })
+        .await;
+        let recorded = Arc::new(Recorded::default());
+        let sdk = LlmSdk::new(server.url(), "", 0).with_metrics(recorded.clone());
+        let req = CompletionRequest::new(CompletionModel::default(), "Say hello");
+
+        let mut stream = Box::pin(sdk.completion_stream(req).await?);
+        stream.next().await.unwrap()?;
+        assert!(recorded.0.lock().unwrap().is_empty());
+        drop(stream);
+
+        let calls = recorded.0.lock().unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].endpoint, "completions");
+        assert_eq!(calls[0].status, Some(StatusCode::OK));
+        assert!(calls[0].time_to_first_token.is_some());
+        Ok(())
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn metrics_facade_should_report_to_the_recorder() {
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        ::metrics::with_local_recorder(&recorder, || {
+            MetricsFacade.record(&CallMetrics {
+                endpoint: "completions".into(),
+                model: "gpt-3.5-turbo-instruct".into(),
+                status: Some(StatusCode::OK),
+                latency: Duration::from_millis(1500),
+                time_to_first_token: Some(Duration::from_millis(200)),
+                retries: 2,
+                prompt_tokens: Some(5),
+                completion_tokens: None,
+                cached: false,
+            });
+            MetricsFacade.record(&CallMetrics {
+                endpoint: "completions".into(),
+                model: "gpt-3.5-turbo-instruct".into(),
+                status: Some(StatusCode::OK),
+                latency: Duration::from_millis(1),
+                time_to_first_token: None,
+                retries: 0,
+                prompt_tokens: None,
+                completion_tokens: None,
+                cached: true,
+            });
+        });
+
+        let metrics: Vec<_> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| {
+                let labels: Vec<_> = key
+                    .key()
+                    .labels()
+                    .map(|l| format!("{}={}", l.key(), l.value()))
+                    .collect();
+                (key.key().name().to_owned(), labels.join(","), value)
+            })
+            .collect();
+        let find = |name: &str| {
+            metrics
+                .iter()
+                .filter(|(n, _, _)| n == name)
+                .map(|(_, labels, value)| (labels.as_str(), value))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            find("llm_sdk_requests_total"),
+            vec![(
+                "endpoint=completions,model=gpt-3.5-turbo-instruct,status=200",
+                &DebugValue::Counter(1)
+            )]
+        );
+        assert_eq!(find("llm_sdk_retries_total")[0].1, &DebugValue::Counter(2));
+        assert_eq!(
+            find("llm_sdk_tokens_total"),
+            vec![(
+                "endpoint=completions,model=gpt-3.5-turbo-instruct,type=input",
+                &DebugValue::Counter(5)
+            )]
+        );
+        assert_eq!(
+            find("llm_sdk_time_to_first_token_seconds")[0].1,
+            &DebugValue::Histogram(vec![0.2.into()])
+        );
+        assert_eq!(find("llm_sdk_request_duration_seconds").len(), 1);
+        assert_eq!(
+            find("llm_sdk_cache_hits_total"),
+            vec![(
+                "endpoint=completions,model=gpt-3.5-turbo-instruct",
+                &DebugValue::Counter(1)
+            )]
+        );
+    }
+}
@@ -1,5 +1,33 @@
+pub mod anthropic;
+pub mod assistants;
+pub mod batch;
 pub mod chat_completion;
+pub mod chat_session;
+pub mod circuit_breaker;
+pub mod completion;
 pub mod create_embedding;
 pub mod create_image;
+pub mod files;
+pub mod fine_tuning;
+pub mod gemini;
+pub mod key_pool;
+pub mod meta;
+pub mod metrics;
+pub mod models;
+pub mod moderation;
+pub mod ollama;
+pub mod pagination;
+pub mod poll;
+pub mod provider;
+pub mod rate_limiter;
+pub mod response_cache;
+pub mod routing;
 pub mod speech;
+pub(crate) mod sse;
+#[cfg(feature = "otel")]
+pub mod telemetry;
+#[cfg(feature = "tokenizer")]
+pub mod tokenizer;
+pub mod usage;
+pub mod vector_stores;
 pub mod whisper;
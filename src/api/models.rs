@@ -0,0 +1,182 @@
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use serde::Deserialize;
+
+use crate::IntoRequest;
+
+#[derive(Debug, Clone, Default)]
+pub struct ListModelsRequest;
+
+#[derive(Debug, Clone)]
+pub struct RetrieveModelRequest {
+    /// The ID of the model to use for this request.
+    model: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeleteModelRequest {
+    /// The model to delete. You must have the Owner role in your organization to delete a fine-tuned model.
+    model: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ListModelsResponse {
+    /// The object type, which is always "list".
+    pub object: String,
+
+    /// The models available to the api key.
+    pub data: Vec<Model>,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct Model {
+    /// The model identifier, which can be referenced in the API endpoints.
+    pub id: String,
+
+    /// The Unix timestamp (in seconds) when the model was created.
+    pub created: u64,
+
+    /// The object type, which is always "model".
+    pub object: String,
+
+    /// The organization that owns the model.
+    pub owned_by: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DeleteModelResponse {
+    /// The ID of the deleted model.
+    pub id: String,
+
+    /// The object type, which is always "model".
+    pub object: String,
+
+    /// Whether the model was deleted.
+    pub deleted: bool,
+}
+
+impl RetrieveModelRequest {
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+        }
+    }
+}
+
+impl DeleteModelRequest {
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+        }
+    }
+}
+
+impl IntoRequest for ListModelsRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/models");
+        client.get(url)
+    }
+}
+
+impl IntoRequest for RetrieveModelRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/models/{}", self.model);
+        client.get(url)
+    }
+}
+
+impl IntoRequest for DeleteModelRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/models/{}", self.model);
+        client.delete(url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chat_completion::ChatCompletionModel, create_embedding::EmbeddingModel,
+        create_image::ImageModel, speech::SpeechModel, whisper::WhisperModel, SDK,
+    };
+
+    use super::*;
+    use anyhow::Result;
+    use serde_json::json;
+
+    #[test]
+    fn custom_models_should_round_trip() -> Result<()> {
+        let model = ChatCompletionModel::Custom("gpt-4o".into());
+        assert_eq!(serde_json::to_value(&model)?, json!("gpt-4o"));
+        assert_eq!(
+            serde_json::from_value::<ChatCompletionModel>(json!("gpt-4o"))?,
+            model
+        );
+        assert_eq!(
+            serde_json::from_value::<ChatCompletionModel>(json!("gpt-3.5-turbo"))?,
+            ChatCompletionModel::Gpt3Turbo
+        );
+
+        let model = EmbeddingModel::Custom("text-embedding-3-small".into());
+        assert_eq!(
+            serde_json::to_value(&model)?,
+            json!("text-embedding-3-small")
+        );
+        assert_eq!(
+            serde_json::from_value::<EmbeddingModel>(json!("text-embedding-3-small"))?,
+            model
+        );
+
+        let model = ImageModel::Custom("gpt-image-1".into());
+        assert_eq!(serde_json::to_value(&model)?, json!("gpt-image-1"));
+        assert_eq!(
+            serde_json::from_value::<ImageModel>(json!("gpt-image-1"))?,
+            model
+        );
+
+        let model = SpeechModel::Custom("gpt-4o-mini-tts".into());
+        assert_eq!(serde_json::to_value(&model)?, json!("gpt-4o-mini-tts"));
+        assert_eq!(
+            serde_json::from_value::<SpeechModel>(json!("gpt-4o-mini-tts"))?,
+            model
+        );
+
+        let model: WhisperModel = "gpt-4o-transcribe".parse()?;
+        assert_eq!(model, WhisperModel::Custom("gpt-4o-transcribe".into()));
+        assert_eq!(model.to_string(), "gpt-4o-transcribe");
+        assert_eq!(WhisperModel::Whisper1.to_string(), "whisper-1");
+        Ok(())
+    }
+
+    #[test]
+    fn model_list_should_deserialize() -> Result<()> {
+        let res: ListModelsResponse = serde_json::from_value(json!({
+            "object": "list",
+            "data": [
+                {
+                    "id": "gpt-4o",
+                    "object": "model",
+                    "created": 1715367049,
+                    "owned_by": "system"
+                }
+            ]
+        }))?;
+        assert_eq!(res.data.len(), 1);
+        assert_eq!(res.data[0].id, "gpt-4o");
+        assert_eq!(res.data[0].owned_by, "system");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_and_retrieve_models_should_work() -> Result<()> {
+        let res = SDK.list_models().await?;
+        assert_eq!(res.object, "list");
+        let model = res
+            .data
+            .iter()
+            .find(|m| m.id == "gpt-3.5-turbo")
+            .expect("gpt-3.5-turbo should be available");
+
+        let ret = SDK.retrieve_model(&model.id).await?;
+        assert_eq!(&ret, model);
+        Ok(())
+    }
+}
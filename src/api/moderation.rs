@@ -0,0 +1,313 @@
+use derive_builder::Builder;
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{chat_completion::ChatCompletionMessage, IntoRequest};
+
+#[derive(Debug, Serialize, Clone, Builder)]
+#[builder(pattern = "mutable")]
+pub struct ModerationRequest {
+    /// The input text to classify, a string or an array of strings.
+    #[builder(setter(into))]
+    input: ModerationInput,
+
+    /// Two content moderations models are available: text-moderation-stable and text-moderation-latest.
+    ///
+    /// The default is text-moderation-latest which will be automatically upgraded over time. This ensures
+    /// you are always using our most accurate model. If you use text-moderation-stable, we will provide
+    /// advanced notice before updating the model.
+    #[builder(default)]
+    model: ModerationModel,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum ModerationInput {
+    String(String),
+    StringArray(Vec<String>),
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ModerationModel {
+    #[default]
+    #[serde(rename = "text-moderation-latest")]
+    TextModerationLatest,
+
+    #[serde(rename = "text-moderation-stable")]
+    TextModerationStable,
+
+    #[serde(rename = "omni-moderation-latest")]
+    OmniModerationLatest,
+
+    /// Any other model id.
+    #[serde(untagged)]
+    Custom(String),
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ModerationResponse {
+    /// The unique identifier for the moderation request.
+    pub id: String,
+
+    /// The model used to generate the moderation results.
+    pub model: String,
+
+    /// A list of moderation objects, one for each input.
+    pub results: Vec<ModerationResult>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ModerationResult {
+    /// Whether the content violates OpenAI's usage policies.
+    pub flagged: bool,
+
+    /// A list of the categories, and whether they are flagged or not.
+    pub categories: ModerationCategories,
+
+    /// A list of the categories along with their scores as predicted by model.
+    pub category_scores: ModerationCategoryScores,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ModerationCategories {
+    /// Content that expresses, incites, or promotes hate based on race, gender, ethnicity, religion,
+    /// nationality, sexual orientation, disability status, or caste.
+    pub hate: bool,
+
+    /// Hateful content that also includes violence or serious harm towards the targeted group.
+    #[serde(rename = "hate/threatening")]
+    pub hate_threatening: bool,
+
+    /// Content that expresses, incites, or promotes harassing language towards any target.
+    pub harassment: bool,
+
+    /// Harassment content that also includes violence or serious harm towards any target.
+    #[serde(rename = "harassment/threatening")]
+    pub harassment_threatening: bool,
+
+    /// Content that promotes, encourages, or depicts acts of self-harm.
+    #[serde(rename = "self-harm")]
+    pub self_harm: bool,
+
+    /// Content where the speaker expresses that they are engaging or intend to engage in acts of self-harm.
+    #[serde(rename = "self-harm/intent")]
+    pub self_harm_intent: bool,
+
+    /// Content that encourages performing acts of self-harm, or that gives instructions or advice on how to commit such acts.
+    #[serde(rename = "self-harm/instructions")]
+    pub self_harm_instructions: bool,
+
+    /// Content meant to arouse sexual excitement, or that promotes sexual services (excluding sex education and wellness).
+    pub sexual: bool,
+
+    /// Sexual content that includes an individual who is under 18 years old.
+    #[serde(rename = "sexual/minors")]
+    pub sexual_minors: bool,
+
+    /// Content that depicts death, violence, or physical injury.
+    pub violence: bool,
+
+    /// Content that depicts death, violence, or physical injury in graphic detail.
+    #[serde(rename = "violence/graphic")]
+    pub violence_graphic: bool,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+pub struct ModerationCategoryScores {
+    pub hate: f64,
+    #[serde(rename = "hate/threatening")]
+    pub hate_threatening: f64,
+    pub harassment: f64,
+    #[serde(rename = "harassment/threatening")]
+    pub harassment_threatening: f64,
+    #[serde(rename = "self-harm")]
+    pub self_harm: f64,
+    #[serde(rename = "self-harm/intent")]
+    pub self_harm_intent: f64,
+    #[serde(rename = "self-harm/instructions")]
+    pub self_harm_instructions: f64,
+    pub sexual: f64,
+    #[serde(rename = "sexual/minors")]
+    pub sexual_minors: f64,
+    pub violence: f64,
+    #[serde(rename = "violence/graphic")]
+    pub violence_graphic: f64,
+}
+
+impl ModerationRequest {
+    pub fn new(input: impl Into<ModerationInput>) -> Self {
+        ModerationRequestBuilder::default()
+            .input(input)
+            .build()
+            .unwrap()
+    }
+
+    /// Build a request that screens the content of all user messages. Returns None if there are
+    /// no user messages to screen.
+    pub fn from_messages(messages: &[ChatCompletionMessage]) -> Option<Self> {
+        let input: Vec<String> = messages
+            .iter()
+            .filter(|m| matches!(m, ChatCompletionMessage::User(_)))
+            .filter_map(|m| m.content())
+            .map(|c| c.to_owned())
+            .collect();
+        if input.is_empty() {
+            None
+        } else {
+            Some(Self::new(input))
+        }
+    }
+}
+
+impl ModerationResponse {
+    /// Whether any of the inputs was flagged.
+    pub fn flagged(&self) -> bool {
+        self.results.iter().any(|r| r.flagged)
+    }
+}
+
+impl ModerationCategories {
+    /// The names of the flagged categories, as they appear in the API response.
+    pub fn flagged(&self) -> Vec<&'static str> {
+        [
+            (self.hate, "hate"),
+            (self.hate_threatening, "hate/threatening"),
+            (self.harassment, "harassment"),
+            (self.harassment_threatening, "harassment/threatening"),
+            (self.self_harm, "self-harm"),
+            (self.self_harm_intent, "self-harm/intent"),
+            (self.self_harm_instructions, "self-harm/instructions"),
+            (self.sexual, "sexual"),
+            (self.sexual_minors, "sexual/minors"),
+            (self.violence, "violence"),
+            (self.violence_graphic, "violence/graphic"),
+        ]
+        .into_iter()
+        .filter_map(|(flagged, name)| flagged.then_some(name))
+        .collect()
+    }
+}
+
+impl From<String> for ModerationInput {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<&str> for ModerationInput {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_owned())
+    }
+}
+
+impl From<Vec<String>> for ModerationInput {
+    fn from(value: Vec<String>) -> Self {
+        Self::StringArray(value)
+    }
+}
+
+impl From<&[String]> for ModerationInput {
+    fn from(value: &[String]) -> Self {
+        Self::StringArray(value.to_vec())
+    }
+}
+
+impl IntoRequest for ModerationRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/moderations");
+        client.post(url).json(&self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::SDK;
+
+    use super::*;
+    use anyhow::Result;
+    use serde_json::json;
+
+    #[test]
+    fn moderation_request_from_messages_should_only_use_user_content() -> Result<()> {
+        let messages = vec![
+            ChatCompletionMessage::new_system("You are a helpful assistant.", ""),
+            ChatCompletionMessage::new_user("Hello", "user1"),
+            ChatCompletionMessage::new_user("How are you?", ""),
+        ];
+        let req = ModerationRequest::from_messages(&messages).unwrap();
+        assert_eq!(
+            serde_json::to_value(req)?,
+            json!({
+                "input": ["Hello", "How are you?"],
+                "model": "text-moderation-latest",
+            })
+        );
+
+        assert!(ModerationRequest::from_messages(&messages[..1]).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn moderation_response_should_deserialize() -> Result<()> {
+        let res: ModerationResponse = serde_json::from_value(json!({
+            "id": "modr-XXXXX",
+            "model": "text-moderation-007",
+            "results": [
+                {
+                    "flagged": true,
+                    "categories": {
+                        "sexual": false,
+                        "hate": false,
+                        "harassment": false,
+                        "self-harm": false,
+                        "sexual/minors": false,
+                        "hate/threatening": false,
+                        "violence/graphic": false,
+                        "self-harm/intent": false,
+                        "self-harm/instructions": false,
+                        "harassment/threatening": true,
+                        "violence": true
+                    },
+                    "category_scores": {
+                        "sexual": 1.2282071e-06,
+                        "hate": 0.010696256,
+                        "harassment": 0.29842457,
+                        "self-harm": 1.5236925e-08,
+                        "sexual/minors": 5.7246268e-08,
+                        "hate/threatening": 0.0060676364,
+                        "violence/graphic": 4.435014e-06,
+                        "self-harm/intent": 8.098441e-10,
+                        "self-harm/instructions": 2.8498655e-11,
+                        "harassment/threatening": 0.63055265,
+                        "violence": 0.99011886
+                    }
+                }
+            ]
+        }))?;
+        assert!(res.flagged());
+        let result = &res.results[0];
+        assert_eq!(
+            result.categories.flagged(),
+            vec!["harassment/threatening", "violence"]
+        );
+        assert!(result.category_scores.violence > 0.99);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn create_moderation_should_work() -> Result<()> {
+        let req = ModerationRequest::new("I want to kill them.");
+        let res = SDK.create_moderation(req).await?;
+        assert_eq!(res.results.len(), 1);
+        assert!(res.flagged());
+        assert!(res.results[0].categories.violence);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn screen_messages_should_reject_flagged_content() -> Result<()> {
+        let messages = vec![ChatCompletionMessage::new_user("I want to kill them.", "")];
+        assert!(SDK.screen_messages(&messages).await.is_err());
+        Ok(())
+    }
+}
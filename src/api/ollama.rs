@@ -0,0 +1,455 @@
+//! Ollama's native `/api/chat` and `/api/embeddings` endpoints, as an alternative to its
+//! OpenAI-compatible `/v1` endpoints (which `LlmSdk` can use directly).
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    chat_completion::{
+        AssistantMessage, ChatCompletionChoice, ChatCompletionMessage, ChatCompletionModel,
+        ChatCompletionRequest, ChatCompletionResponse, ChatCompletionUsage, ChatResponseFormat,
+        FinishReason, Function, Stop, Tool, ToolCall, ToolCallType,
+    },
+    create_embedding::{
+        CreateEmbeddingRequest, CreateEmbeddingResponse, Embedding, EmbeddingInput, EmbeddingModel,
+        EmbeddingObject, EmbeddingUsage,
+    },
+    provider::{ChatProvider, EmbeddingProvider},
+    IntoRequest, SendAndLog, TIMEOUT,
+};
+
+pub const OLLAMA_URL: &str = "http://localhost:11434";
+const DEFAULT_MODEL: &str = "llama3.1";
+const DEFAULT_EMBEDDING_MODEL: &str = "nomic-embed-text";
+
+/// A chat and embedding provider backed by a local Ollama server.
+///
+/// Custom model ids are sent as is, the OpenAI models are replaced by the default models.
+/// Options without an Ollama counterpart (logit_bias, logprobs, n, tool_choice) are ignored.
+#[derive(Debug, Clone)]
+pub struct OllamaSdk {
+    base_url: String,
+    client: ClientWithMiddleware,
+    default_model: String,
+    default_embedding_model: String,
+    timeout: Duration,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ChatRequest {
+    model: String,
+    messages: Vec<Message>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<Tool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<&'static str>,
+    options: Options,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Message {
+    role: String,
+    #[serde(default)]
+    content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<OllamaToolCall>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct OllamaToolCall {
+    function: OllamaFunction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct OllamaFunction {
+    name: String,
+    #[serde(default)]
+    arguments: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+struct Options {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_predict: Option<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ChatResponse {
+    model: String,
+    message: Message,
+    #[serde(default)]
+    done_reason: Option<String>,
+    #[serde(default)]
+    prompt_eval_count: Option<usize>,
+    #[serde(default)]
+    eval_count: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct EmbeddingsRequest {
+    model: String,
+    prompt: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct EmbeddingsResponse {
+    embedding: Vec<f64>,
+}
+
+impl OllamaSdk {
+    pub fn new(base_url: impl Into<String>, max_retries: u32) -> Self {
+        Self {
+            base_url: base_url.into(),
+            client: crate::build_client(max_retries, &[], &[]),
+            default_model: DEFAULT_MODEL.to_owned(),
+            default_embedding_model: DEFAULT_EMBEDDING_MODEL.to_owned(),
+            timeout: Duration::from_secs(TIMEOUT),
+        }
+    }
+
+    /// The chat model used when the request names an OpenAI model.
+    pub fn with_default_model(mut self, model: impl Into<String>) -> Self {
+        self.default_model = model.into();
+        self
+    }
+
+    /// The embedding model used when the request names an OpenAI model.
+    pub fn with_default_embedding_model(mut self, model: impl Into<String>) -> Self {
+        self.default_embedding_model = model.into();
+        self
+    }
+
+    /// Local models on modest hardware can take longer than the default 30 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse> {
+        let req = self.translate(req)?;
+        let res = self.send(req).await?;
+        Ok(res.json::<ChatResponse>().await?.into())
+    }
+
+    /// Embed each input with one `/api/embeddings` call, the endpoint takes a single prompt.
+    pub async fn create_embedding(
+        &self,
+        req: CreateEmbeddingRequest,
+    ) -> Result<CreateEmbeddingResponse> {
+        let model = match req.model {
+            EmbeddingModel::Custom(id) => id,
+            _ => self.default_embedding_model.clone(),
+        };
+        let inputs = match req.input {
+            EmbeddingInput::String(s) => vec![s],
+            EmbeddingInput::StringArray(v) => v,
+        };
+        let mut data = Vec::with_capacity(inputs.len());
+        for (index, prompt) in inputs.into_iter().enumerate() {
+            let req = EmbeddingsRequest {
+                model: model.clone(),
+                prompt,
+            };
+            let res = self.send(req).await?;
+            data.push(Embedding {
+                index,
+                embedding: res.json::<EmbeddingsResponse>().await?.embedding,
+                object: EmbeddingObject::Embedding,
+            });
+        }
+        Ok(CreateEmbeddingResponse {
+            object: EmbeddingObject::List,
+            data,
+            model: EmbeddingModel::Custom(model),
+            // /api/embeddings doesn't report token usage
+            usage: EmbeddingUsage {
+                prompt_tokens: 0,
+                total_tokens: 0,
+            },
+        })
+    }
+
+    async fn send(&self, req: impl IntoRequest) -> Result<reqwest::Response> {
+        req.into_request(&self.base_url, self.client.clone())
+            .timeout(self.timeout)
+            .send_and_log()
+            .await
+    }
+
+    fn translate(&self, req: ChatCompletionRequest) -> Result<ChatRequest> {
+        let messages = req
+            .messages
+            .into_iter()
+            .map(|message| {
+                Ok(match message {
+                    ChatCompletionMessage::System(m) => Message::new("system", m.content),
+                    ChatCompletionMessage::User(m) => Message::new("user", m.content),
+                    ChatCompletionMessage::Assistant(m) => Message {
+                        role: "assistant".to_owned(),
+                        content: m.content.unwrap_or_default(),
+                        tool_calls: m
+                            .tool_calls
+                            .into_iter()
+                            .map(|call| {
+                                Ok(OllamaToolCall {
+                                    function: OllamaFunction {
+                                        arguments: parse_arguments(&call.function.arguments)?,
+                                        name: call.function.name,
+                                    },
+                                })
+                            })
+                            .collect::<Result<_>>()?,
+                    },
+                    ChatCompletionMessage::Tool(m) => Message::new("tool", m.content),
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(ChatRequest {
+            model: match req.model {
+                ChatCompletionModel::Custom(id) => id,
+                _ => self.default_model.clone(),
+            },
+            messages,
+            stream: false,
+            tools: req.tools,
+            format: req
+                .response_format
+                .filter(|f| f.typ == ChatResponseFormat::Json)
+                .map(|_| "json"),
+            options: Options {
+                temperature: req.temperature,
+                top_p: req.top_p,
+                num_predict: req.max_tokens,
+                stop: match req.stop {
+                    Some(Stop::One(stop)) => vec![stop],
+                    Some(Stop::Many(stops)) => stops,
+                    None => vec![],
+                },
+                seed: req.seed,
+                presence_penalty: req.presence_penalty,
+                frequency_penalty: req.frequency_penalty,
+            },
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl ChatProvider for OllamaSdk {
+    async fn chat_completion(&self, req: ChatCompletionRequest) -> Result<ChatCompletionResponse> {
+        OllamaSdk::chat_completion(self, req).await
+    }
+}
+
+#[async_trait::async_trait]
+impl EmbeddingProvider for OllamaSdk {
+    async fn create_embedding(
+        &self,
+        req: CreateEmbeddingRequest,
+    ) -> Result<CreateEmbeddingResponse> {
+        OllamaSdk::create_embedding(self, req).await
+    }
+}
+
+impl Message {
+    fn new(role: &str, content: String) -> Self {
+        Self {
+            role: role.to_owned(),
+            content,
+            tool_calls: vec![],
+        }
+    }
+}
+
+impl IntoRequest for ChatRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/api/chat");
+        client.post(url).json(&self)
+    }
+}
+
+impl IntoRequest for EmbeddingsRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/api/embeddings");
+        client.post(url).json(&self)
+    }
+}
+
+impl From<ChatResponse> for ChatCompletionResponse {
+    fn from(res: ChatResponse) -> Self {
+        // Ollama doesn't give tool calls ids, tool messages are matched by order
+        let tool_calls: Vec<ToolCall> = res
+            .message
+            .tool_calls
+            .into_iter()
+            .enumerate()
+            .map(|(i, call)| ToolCall {
+                id: format!("call_{i}"),
+                typ: ToolCallType::Function,
+                function: Function {
+                    name: call.function.name,
+                    arguments: call.function.arguments.to_string(),
+                },
+            })
+            .collect();
+        let finish_reason = match res.done_reason.as_deref() {
+            Some("length") => FinishReason::Length,
+            _ if !tool_calls.is_empty() => FinishReason::ToolCalls,
+            _ => FinishReason::Stop,
+        };
+        let usage = match (res.prompt_eval_count, res.eval_count) {
+            (None, None) => None,
+            (prompt, completion) => {
+                let (prompt, completion) = (prompt.unwrap_or(0), completion.unwrap_or(0));
+                Some(ChatCompletionUsage {
+                    completion_tokens: completion,
+                    prompt_tokens: prompt,
+                    total_tokens: prompt + completion,
+                })
+            }
+        };
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as usize)
+            .unwrap_or_default();
+
+        Self {
+            id: String::new(),
+            choices: vec![ChatCompletionChoice {
+                finish_reason,
+                index: 0,
+                message: AssistantMessage {
+                    content: (!res.message.content.is_empty()).then_some(res.message.content),
+                    name: None,
+                    tool_calls,
+                },
+                logprobs: None,
+            }],
+            created,
+            model: res.model,
+            system_fingerprint: None,
+            object: "chat.completion".to_owned(),
+            usage,
+        }
+    }
+}
+
+/// The tool call arguments as a JSON object, as Ollama expects them.
+fn parse_arguments(arguments: &str) -> Result<serde_json::Value> {
+    if arguments.trim().is_empty() {
+        return Ok(json!({}));
+    }
+    serde_json::from_str(arguments).map_err(|e| anyhow!("invalid tool call arguments: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chat_completion::ChatCompletionRequestBuilder,
+        stub::{StubResponse, StubServer},
+    };
+
+    #[tokio::test]
+    async fn ollama_chat_completion_should_work() -> Result<()> {
+        let server = StubServer::start(|_| {
+            StubResponse::json(
+                200,
+                json!({
+                    "model": "llama3.2",
+                    "created_at": "2024-10-01T12:00:00Z",
+                    "message": {
+                        "role": "assistant",
+                        "content": "",
+                        "tool_calls": [{"function": {"name": "get_weather", "arguments": {"city": "Oslo"}}}]
+                    },
+                    "done": true,
+                    "done_reason": "stop",
+                    "prompt_eval_count": 26,
+                    "eval_count": 12
+                }),
+            )
+        })
+        .await;
+        let provider: Box<dyn ChatProvider> = Box::new(OllamaSdk::new(server.url(), 0));
+        let req = ChatCompletionRequestBuilder::default()
+            .model(ChatCompletionModel::Custom("llama3.2".into()))
+            .messages(vec![
+                ChatCompletionMessage::new_system("Be brief.", ""),
+                ChatCompletionMessage::new_user("Weather in Oslo?", ""),
+            ])
+            .max_tokens(64usize)
+            .stop("END")
+            .build()?;
+        let res = provider.chat_completion(req).await?;
+
+        let choice = &res.choices[0];
+        assert_eq!(choice.finish_reason, FinishReason::ToolCalls);
+        assert_eq!(choice.message.content(), None);
+        assert_eq!(choice.message.tool_calls()[0].id(), "call_0");
+        assert_eq!(
+            choice.message.tool_calls()[0].arguments(),
+            r#"{"city":"Oslo"}"#
+        );
+        assert_eq!(res.usage.unwrap().total_tokens, 38);
+
+        let sent = &server.requests()[0];
+        assert_eq!(sent.path, "/api/chat");
+        assert_eq!(
+            sent.json(),
+            json!({
+                "model": "llama3.2",
+                "messages": [
+                    {"role": "system", "content": "Be brief."},
+                    {"role": "user", "content": "Weather in Oslo?"}
+                ],
+                "stream": false,
+                "options": {"num_predict": 64, "stop": ["END"]}
+            })
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn ollama_create_embedding_should_work() -> Result<()> {
+        let server = StubServer::start(|req| {
+            let len = req.json()["prompt"].as_str().unwrap().len();
+            StubResponse::json(200, json!({ "embedding": [len as f64, 0.25] }))
+        })
+        .await;
+        let provider: Box<dyn EmbeddingProvider> = Box::new(OllamaSdk::new(server.url(), 0));
+        let req = CreateEmbeddingRequest::new(vec!["a".to_owned(), "abc".to_owned()]);
+        let res = provider.create_embedding(req).await?;
+
+        assert_eq!(res.data.len(), 2);
+        assert_eq!(res.data[1].embedding, vec![3.0, 0.25]);
+        let sent = server.requests();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].path, "/api/embeddings");
+        assert_eq!(
+            sent[0].json(),
+            json!({"model": DEFAULT_EMBEDDING_MODEL, "prompt": "a"})
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
+
+/// The cursor based pagination parameters shared by the list endpoints.
+#[derive(Debug, Serialize, Clone, Default, Builder)]
+#[builder(pattern = "mutable")]
+pub struct ListParams {
+    /// A limit on the number of objects to be returned. Limit can range between 1 and 100 for most
+    /// endpoints (10000 for files).
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit: Option<usize>,
+
+    /// Sort order by the created_at timestamp of the objects.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    order: Option<ListOrder>,
+
+    /// A cursor for use in pagination. after is an object ID that defines your place in the list.
+    /// For instance, if you make a list request and receive 100 objects, ending with obj_foo, your
+    /// subsequent call can include after=obj_foo in order to fetch the next page of the list.
+    #[builder(default, setter(strip_option, into))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    after: Option<String>,
+
+    /// A cursor for use in pagination. before is an object ID that defines your place in the list.
+    /// Not every endpoint supports it.
+    #[builder(default, setter(strip_option, into))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    before: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ListOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ListResponse<T> {
+    /// The object type, which is always "list".
+    pub object: String,
+
+    /// The objects in this page.
+    pub data: Vec<T>,
+
+    /// The ID of the first object in this page, if the endpoint returns it.
+    #[serde(default)]
+    pub first_id: Option<String>,
+
+    /// The ID of the last object in this page, if the endpoint returns it.
+    #[serde(default)]
+    pub last_id: Option<String>,
+
+    /// Whether there are more objects after this page.
+    #[serde(default)]
+    pub has_more: bool,
+}
+
+impl ListParams {
+    /// The params to fetch the page after the given object ID.
+    pub fn after(after: impl Into<String>) -> Self {
+        ListParamsBuilder::default().after(after).build().unwrap()
+    }
+}
+
+impl<T> ListResponse<T> {
+    /// The params to fetch the next page, or None if this is the last one.
+    pub fn next_page(&self) -> Option<ListParams> {
+        if !self.has_more {
+            return None;
+        }
+        self.last_id.as_ref().map(ListParams::after)
+    }
+}
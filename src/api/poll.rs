@@ -0,0 +1,133 @@
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+
+/// How to poll a long running object (batch, run, file batch...) until it reaches a terminal status.
+#[derive(Debug, Clone, Copy)]
+pub struct PollConfig {
+    /// The delay between the first poll (made right away) and the second one.
+    pub initial_delay: Duration,
+
+    /// The delay is multiplied by this factor after every poll. Values below 1 (or NaN) keep the
+    /// delay constant.
+    pub multiplier: f64,
+
+    /// The upper bound of the delay between two polls.
+    pub max_delay: Duration,
+
+    /// Give up once the object has been polled for this long.
+    pub timeout: Option<Duration>,
+}
+
+/// Suited to batches, which take minutes to hours: 5 seconds, growing up to 5 minutes.
+impl Default for PollConfig {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(5),
+            multiplier: 1.5,
+            max_delay: Duration::from_secs(300),
+            timeout: None,
+        }
+    }
+}
+
+impl PollConfig {
+    /// Suited to runs and vector store file batches, which take seconds: 1 second, growing up to
+    /// 1 minute.
+    pub fn for_runs() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn next_delay(&self, delay: Duration) -> Duration {
+        let secs = delay.as_secs_f64() * self.multiplier.max(1.0);
+        Duration::try_from_secs_f64(secs)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay)
+    }
+
+    /// Call `poll` with exponential backoff until `done` returns true for its result.
+    pub(crate) async fn run<T, F, Fut>(&self, mut poll: F, done: impl Fn(&T) -> bool) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let start = Instant::now();
+        let mut delay = self.initial_delay;
+        loop {
+            let ret = poll().await?;
+            if done(&ret) {
+                return Ok(ret);
+            }
+            if let Some(timeout) = self.timeout {
+                if start.elapsed() >= timeout {
+                    return Err(anyhow!("polling timed out after {:?}", timeout));
+                }
+            }
+            tokio::time::sleep(delay).await;
+            delay = self.next_delay(delay);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn poll_config_should_back_off() {
+        let config = PollConfig {
+            initial_delay: Duration::from_secs(10),
+            multiplier: 2.0,
+            max_delay: Duration::from_secs(30),
+            timeout: None,
+        };
+        let delay = config.next_delay(config.initial_delay);
+        assert_eq!(delay, Duration::from_secs(20));
+        assert_eq!(config.next_delay(delay), Duration::from_secs(30));
+
+        // a negative or NaN multiplier keeps the delay, an infinite one jumps to the bound
+        for (multiplier, expected) in [(-1.0, 10), (f64::NAN, 10), (f64::INFINITY, 30)] {
+            let config = PollConfig {
+                multiplier,
+                ..config
+            };
+            let delay = config.next_delay(config.initial_delay);
+            assert_eq!(delay, Duration::from_secs(expected));
+        }
+    }
+
+    #[tokio::test]
+    async fn poll_should_stop_when_done() -> Result<()> {
+        let config = PollConfig {
+            initial_delay: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let mut count = 0;
+        let ret = config
+            .run(
+                || {
+                    count += 1;
+                    let n = count;
+                    async move { Ok(n) }
+                },
+                |n| *n == 3,
+            )
+            .await?;
+        assert_eq!(ret, 3);
+
+        let config = PollConfig {
+            initial_delay: Duration::from_millis(1),
+            timeout: Some(Duration::from_millis(5)),
+            ..Default::default()
+        };
+        assert!(config.run(|| async { Ok(()) }, |_| false).await.is_err());
+        Ok(())
+    }
+}
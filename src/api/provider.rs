@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::{
+    chat_completion::{ChatCompletionRequest, ChatCompletionResponse},
+    create_embedding::{CreateEmbeddingRequest, CreateEmbeddingResponse},
+    LlmSdk,
+};
+
+/// A backend serving chat completions. Requests and responses use the OpenAI shapes, so the
+/// provider can be picked at runtime (e.g. per customer) without changing the call sites.
+#[async_trait::async_trait]
+pub trait ChatProvider: Send + Sync {
+    async fn chat_completion(&self, req: ChatCompletionRequest) -> Result<ChatCompletionResponse>;
+}
+
+/// A backend serving embeddings, with the OpenAI request and response shapes.
+#[async_trait::async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    async fn create_embedding(
+        &self,
+        req: CreateEmbeddingRequest,
+    ) -> Result<CreateEmbeddingResponse>;
+}
+
+#[async_trait::async_trait]
+impl ChatProvider for LlmSdk {
+    async fn chat_completion(&self, req: ChatCompletionRequest) -> Result<ChatCompletionResponse> {
+        LlmSdk::chat_completion(self, req).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: ChatProvider + ?Sized> ChatProvider for Arc<T> {
+    async fn chat_completion(&self, req: ChatCompletionRequest) -> Result<ChatCompletionResponse> {
+        (**self).chat_completion(req).await
+    }
+}
+
+#[async_trait::async_trait]
+impl EmbeddingProvider for LlmSdk {
+    async fn create_embedding(
+        &self,
+        req: CreateEmbeddingRequest,
+    ) -> Result<CreateEmbeddingResponse> {
+        LlmSdk::create_embedding(self, req).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: EmbeddingProvider + ?Sized> EmbeddingProvider for Arc<T> {
+    async fn create_embedding(
+        &self,
+        req: CreateEmbeddingRequest,
+    ) -> Result<CreateEmbeddingResponse> {
+        (**self).create_embedding(req).await
+    }
+}
@@ -0,0 +1,396 @@
+//! Client-side requests-per-minute and tokens-per-minute limits, enforced before sending.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+/// Limits of one model (or model prefix), per minute.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub requests_per_minute: Option<u32>,
+    pub tokens_per_minute: Option<u32>,
+}
+
+/// Token buckets per model, refilled continuously. Requests wait for capacity in arrival order.
+///
+/// The token cost of a request is estimated before sending (prompt + max_tokens for chat and
+/// completions, the input for embeddings) and corrected with the usage of the response.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    /// Model id or prefix to its limits.
+    limits: HashMap<String, Limits>,
+    default: Option<Limits>,
+    buckets: Mutex<HashMap<String, Arc<ModelBucket>>>,
+}
+
+/// Capacity taken for a request. Settle it with the actual usage once the response is in,
+/// dropping it keeps the estimate.
+pub(crate) struct Permit {
+    bucket: Option<Arc<ModelBucket>>,
+    estimated: usize,
+}
+
+/// Carried by a request whose first attempt took a permit: each retry takes a request slot of
+/// the model. Retries take no tokens, a failed attempt is not billed.
+#[derive(Debug, Clone)]
+pub(crate) struct RetrySlots {
+    limiter: Arc<RateLimiter>,
+    model: String,
+    attempts: Arc<AtomicUsize>,
+}
+
+#[derive(Debug)]
+struct ModelBucket {
+    /// Held while waiting for capacity. tokio's mutex is fair, so waiters go in arrival order.
+    queue: tokio::sync::Mutex<()>,
+    requests: Option<Mutex<TokenBucket>>,
+    tokens: Option<Mutex<TokenBucket>>,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    /// May go negative when the actual usage exceeds the estimate.
+    available: f64,
+    per_second: f64,
+    updated: Instant,
+}
+
+impl Limits {
+    pub fn new(requests_per_minute: u32, tokens_per_minute: u32) -> Self {
+        Self {
+            requests_per_minute: Some(requests_per_minute),
+            tokens_per_minute: Some(tokens_per_minute),
+        }
+    }
+
+    pub fn requests_per_minute(limit: u32) -> Self {
+        Self {
+            requests_per_minute: Some(limit),
+            tokens_per_minute: None,
+        }
+    }
+
+    pub fn tokens_per_minute(limit: u32) -> Self {
+        Self {
+            requests_per_minute: None,
+            tokens_per_minute: Some(limit),
+        }
+    }
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the models whose id starts with `model`, the longest matching prefix wins.
+    pub fn with_limit(mut self, model: impl Into<String>, limits: Limits) -> Self {
+        self.limits.insert(model.into(), limits);
+        self
+    }
+
+    /// Limit the models without a limit of their own, each with its own buckets.
+    pub fn with_default_limit(mut self, limits: Limits) -> Self {
+        self.default = Some(limits);
+        self
+    }
+
+    pub fn into_shared(self) -> Arc<Self> {
+        Arc::new(self)
+    }
+
+    /// The limits applying to the model, if any.
+    pub fn limits(&self, model: &str) -> Option<Limits> {
+        self.limits
+            .get(model)
+            .or_else(|| {
+                self.limits
+                    .iter()
+                    .filter(|(prefix, _)| model.starts_with(prefix.as_str()))
+                    .max_by_key(|(prefix, _)| prefix.len())
+                    .map(|(_, limits)| limits)
+            })
+            .or(self.default.as_ref())
+            .copied()
+    }
+
+    /// Wait until the model has capacity for one request of `tokens` estimated tokens, and take it.
+    pub(crate) async fn acquire(&self, model: &str, tokens: usize) -> Permit {
+        let Some(bucket) = self.bucket(model) else {
+            return Permit::none();
+        };
+        {
+            let _turn = bucket.queue.lock().await;
+            loop {
+                let now = Instant::now();
+                let wait = [
+                    bucket
+                        .requests
+                        .as_ref()
+                        .map(|b| b.lock().unwrap().wait(1.0, now)),
+                    bucket
+                        .tokens
+                        .as_ref()
+                        .map(|b| b.lock().unwrap().wait(tokens as f64, now)),
+                ]
+                .into_iter()
+                .flatten()
+                .max()
+                .unwrap_or_default();
+                if wait.is_zero() {
+                    break;
+                }
+                tokio::time::sleep(wait).await;
+            }
+            if let Some(b) = &bucket.requests {
+                b.lock().unwrap().take(1.0);
+            }
+            if let Some(b) = &bucket.tokens {
+                b.lock().unwrap().take(tokens as f64);
+            }
+        }
+        Permit {
+            bucket: Some(bucket),
+            estimated: tokens,
+        }
+    }
+
+    fn bucket(&self, model: &str) -> Option<Arc<ModelBucket>> {
+        let limits = self.limits(model)?;
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(model.to_owned()).or_insert_with(|| {
+            Arc::new(ModelBucket {
+                queue: tokio::sync::Mutex::new(()),
+                requests: limits.requests_per_minute.map(TokenBucket::per_minute),
+                tokens: limits.tokens_per_minute.map(TokenBucket::per_minute),
+            })
+        });
+        Some(bucket.clone())
+    }
+}
+
+impl Permit {
+    pub(crate) fn none() -> Self {
+        Self {
+            bucket: None,
+            estimated: 0,
+        }
+    }
+
+    /// Give back the over-estimate, or take the under-estimate.
+    pub(crate) fn settle(self, actual_tokens: usize) {
+        if let Some(tokens) = self.bucket.as_ref().and_then(|b| b.tokens.as_ref()) {
+            tokens
+                .lock()
+                .unwrap()
+                .take(actual_tokens as f64 - self.estimated as f64);
+        }
+    }
+}
+
+impl RetrySlots {
+    pub(crate) fn new(limiter: Arc<RateLimiter>, model: impl Into<String>) -> Self {
+        Self {
+            limiter,
+            model: model.into(),
+            attempts: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Wait for a request slot, unless this is the first attempt.
+    pub(crate) async fn next_attempt(&self) {
+        if self.attempts.fetch_add(1, Ordering::Relaxed) > 0 {
+            self.limiter.acquire(&self.model, 0).await;
+        }
+    }
+}
+
+impl TokenBucket {
+    fn per_minute(limit: u32) -> Mutex<Self> {
+        Mutex::new(Self {
+            capacity: limit as f64,
+            available: limit as f64,
+            per_second: limit as f64 / 60.0,
+            updated: Instant::now(),
+        })
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.available = (self.available + elapsed * self.per_second).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// How long until `amount` is available. Requests larger than the bucket wait for a full one.
+    fn wait(&mut self, amount: f64, now: Instant) -> Duration {
+        self.refill(now);
+        let missing = amount.min(self.capacity) - self.available;
+        if missing <= 0.0 || self.per_second <= 0.0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(missing / self.per_second)
+    }
+
+    fn take(&mut self, amount: f64) {
+        self.available = (self.available - amount).min(self.capacity);
+    }
+}
+
+/// About 4 characters per token, or the cl100k_base count with the `tokenizer` feature.
+pub(crate) fn estimate_text_tokens(text: &str) -> usize {
+    #[cfg(feature = "tokenizer")]
+    return crate::tokenizer::count_tokens(text);
+    #[cfg(not(feature = "tokenizer"))]
+    return text.len().div_ceil(4);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chat_completion::{ChatCompletionMessage, ChatCompletionRequestBuilder},
+        stub::{StubResponse, StubServer},
+        LlmSdk,
+    };
+    use serde_json::json;
+
+    #[test]
+    fn rate_limiter_should_match_model_prefix() {
+        let limiter = RateLimiter::new()
+            .with_limit("gpt-4", Limits::requests_per_minute(10))
+            .with_limit("gpt-4o", Limits::new(500, 30_000));
+        assert_eq!(
+            limiter.limits("gpt-4o-mini"),
+            Some(Limits::new(500, 30_000))
+        );
+        assert_eq!(
+            limiter.limits("gpt-4-turbo"),
+            Some(Limits::requests_per_minute(10))
+        );
+        assert_eq!(limiter.limits("o1"), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limiter_should_queue_requests() {
+        let limiter =
+            Arc::new(RateLimiter::new().with_limit("gpt-4o", Limits::requests_per_minute(2)));
+        let start = Instant::now();
+        let mut tasks = vec![];
+        for _ in 0..4 {
+            let limiter = limiter.clone();
+            tasks.push(tokio::spawn(async move {
+                limiter.acquire("gpt-4o", 0).await.settle(0);
+                start.elapsed()
+            }));
+        }
+        let mut elapsed = vec![];
+        for task in tasks {
+            elapsed.push(task.await.unwrap().as_secs());
+        }
+        // a full bucket of 2, then one request every 30 seconds
+        assert_eq!(elapsed, vec![0, 0, 30, 60]);
+
+        // unlimited models don't wait
+        limiter.acquire("o1", 1_000_000).await.settle(0);
+        assert_eq!(start.elapsed().as_secs(), 60);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limiter_should_settle_actual_tokens() {
+        let limiter = RateLimiter::new().with_limit("gpt-4o", Limits::tokens_per_minute(600));
+        let start = Instant::now();
+        // estimated 600, used 300: the other half is available right away
+        limiter.acquire("gpt-4o", 600).await.settle(300);
+        limiter.acquire("gpt-4o", 300).await.settle(300);
+        assert_eq!(start.elapsed().as_secs(), 0);
+        // used more than estimated: the debt is waited for
+        limiter.acquire("gpt-4o", 10).await.settle(310);
+        limiter.acquire("gpt-4o", 10).await.settle(10);
+        assert_eq!(start.elapsed().as_secs(), 32);
+    }
+
+    #[tokio::test]
+    async fn sdk_should_settle_with_response_usage() -> anyhow::Result<()> {
+        let server = StubServer::start(|_| {
+            StubResponse::json(
+                200,
+                json!({
+                    "id": "chatcmpl-1",
+                    "object": "chat.completion",
+                    "created": 1700000000,
+                    "model": "gpt-4o-2024-08-06",
+                    "choices": [{"index": 0, "finish_reason": "stop", "message": {"role": "assistant", "content": "hi"}}],
+                    "usage": {"prompt_tokens": 9, "completion_tokens": 1, "total_tokens": 10}
+                }),
+            )
+        })
+        .await;
+        let limiter = RateLimiter::new()
+            .with_limit("gpt-4o", Limits::tokens_per_minute(1000))
+            .into_shared();
+        let sdk = LlmSdk::new(server.url(), "", 0).with_rate_limiter(limiter.clone());
+        let req = ChatCompletionRequestBuilder::default()
+            .model(crate::chat_completion::ChatCompletionModel::Custom(
+                "gpt-4o".into(),
+            ))
+            .messages(vec![ChatCompletionMessage::new_user("hi", "")])
+            .max_tokens(500usize)
+            .build()?;
+        assert!(req.estimated_tokens() > 500);
+        sdk.chat_completion(req).await?;
+
+        let bucket = limiter.bucket("gpt-4o").unwrap();
+        let available = bucket.tokens.as_ref().unwrap().lock().unwrap().available;
+        assert!((989.0..=1000.0).contains(&available), "{available}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sdk_should_take_a_request_slot_per_attempt() -> anyhow::Result<()> {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let server = StubServer::start({
+            let calls = calls.clone();
+            move |_| match calls.fetch_add(1, Ordering::SeqCst) {
+                0 => StubResponse::json(503, json!({"error": "overloaded"})),
+                _ => StubResponse::json(
+                    200,
+                    json!({
+                        "object": "list",
+                        "data": [],
+                        "model": "text-embedding-ada-002",
+                        "usage": {"prompt_tokens": 1, "total_tokens": 1}
+                    }),
+                ),
+            }
+        })
+        .await;
+        let limiter = RateLimiter::new()
+            .with_default_limit(Limits::requests_per_minute(10))
+            .into_shared();
+        let sdk = LlmSdk::new(server.url(), "", 1).with_rate_limiter(limiter.clone());
+        let req = crate::create_embedding::CreateEmbeddingRequest::new("hi");
+        sdk.create_embedding(req).await?;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let model = limiter
+            .buckets
+            .lock()
+            .unwrap()
+            .keys()
+            .next()
+            .cloned()
+            .unwrap();
+        let bucket = limiter.bucket(&model).unwrap();
+        let available = bucket.requests.as_ref().unwrap().lock().unwrap().available;
+        assert!((8.0..8.5).contains(&available), "{available}");
+        Ok(())
+    }
+}
@@ -0,0 +1,469 @@
+//! Opt-in caching of chat and completion responses, for eval reruns and dev loops that send the
+//! same deterministic requests (e.g. `temperature: 0` with a fixed `seed`) again and again.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use futures::{Stream, StreamExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::time::Instant;
+
+/// Where responses are kept, keyed by [`cache_key`]. Backends decide how long entries live.
+#[async_trait::async_trait]
+pub trait ResponseCache: fmt::Debug + Send + Sync {
+    async fn get(&self, key: &str) -> Result<Option<CachedResponse>>;
+
+    async fn put(&self, key: &str, response: CachedResponse) -> Result<()>;
+}
+
+/// A cached response body, or the chunks of a streamed response (the JSON of each event).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CachedResponse {
+    Body(String),
+    Chunks(Vec<String>),
+}
+
+/// How a call uses the response cache of the SDK, see `LlmSdk::with_cache_control`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CacheControl {
+    /// Answer from the cache if possible, otherwise send the request and cache its response.
+    #[default]
+    Default,
+    /// Neither read nor write the cache.
+    Bypass,
+    /// Send the request and cache its response, replacing the cached one.
+    Refresh,
+    /// Answer from the cache, fail with [`CacheMissError`] instead of sending the request.
+    OnlyIfCached,
+}
+
+/// The error of an `OnlyIfCached` call whose response isn't cached.
+#[derive(Debug, Clone)]
+pub struct CacheMissError {
+    pub key: String,
+}
+
+/// Responses kept in memory, until the process exits or their TTL expires.
+#[derive(Debug, Default)]
+pub struct MemoryCache {
+    ttl: Option<Duration>,
+    entries: Mutex<HashMap<String, (CachedResponse, Option<Instant>)>>,
+}
+
+/// Responses kept as one JSON file per key in a directory, shared across runs.
+#[derive(Debug)]
+pub struct FileCache {
+    dir: PathBuf,
+    ttl: Option<Duration>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FileEntry {
+    /// Seconds since the Unix epoch.
+    expires_at: Option<u64>,
+    response: CachedResponse,
+}
+
+/// The SHA-256 of the URL and the serialized request, with object keys sorted at every level so
+/// the same request always gives the same key, however it was built (e.g. from a `HashMap`).
+pub fn cache_key(url: &str, req: &impl Serialize) -> Result<String> {
+    let req = serde_json::to_value(req)?;
+    let mut canonical = String::new();
+    write_canonical(&req, &mut canonical);
+    let mut hasher = Sha256::new();
+    hasher.update(url.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(canonical.as_bytes());
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn write_canonical(value: &serde_json::Value, out: &mut String) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::Value::from(key.as_str()).to_string());
+                out.push(':');
+                write_canonical(value, out);
+            }
+            out.push('}');
+        }
+        serde_json::Value::Array(values) => {
+            out.push('[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(value, out);
+            }
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+impl MemoryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn into_shared(self) -> Arc<Self> {
+        Arc::new(self)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+#[async_trait::async_trait]
+impl ResponseCache for MemoryCache {
+    async fn get(&self, key: &str) -> Result<Option<CachedResponse>> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some((_, Some(expires_at))) if *expires_at <= Instant::now() => {
+                entries.remove(key);
+                Ok(None)
+            }
+            Some((response, _)) => Ok(Some(response.clone())),
+            None => Ok(None),
+        }
+    }
+
+    async fn put(&self, key: &str, response: CachedResponse) -> Result<()> {
+        let expires_at = self.ttl.map(|ttl| Instant::now() + ttl);
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key.to_owned(), (response, expires_at));
+        Ok(())
+    }
+}
+
+impl FileCache {
+    /// The directory is created on the first write.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            ttl: None,
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn into_shared(self) -> Arc<Self> {
+        Arc::new(self)
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.json"))
+    }
+}
+
+#[async_trait::async_trait]
+impl ResponseCache for FileCache {
+    async fn get(&self, key: &str) -> Result<Option<CachedResponse>> {
+        let path = self.path(key);
+        let data = match tokio::fs::read(&path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let entry: FileEntry = serde_json::from_slice(&data)?;
+        if entry.expires_at.is_some_and(|t| t <= unix_now()) {
+            let _ = tokio::fs::remove_file(&path).await;
+            return Ok(None);
+        }
+        Ok(Some(entry.response))
+    }
+
+    async fn put(&self, key: &str, response: CachedResponse) -> Result<()> {
+        let entry = FileEntry {
+            expires_at: self.ttl.map(|ttl| unix_now() + ttl.as_secs()),
+            response,
+        };
+        tokio::fs::create_dir_all(&self.dir).await?;
+        // write then rename, so concurrent readers never see a partial file
+        let path = self.path(key);
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        tokio::fs::write(&tmp, serde_json::to_vec(&entry)?).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Replay cached chunks as the stream they were recorded from.
+pub(crate) fn replay<T: DeserializeOwned>(chunks: Vec<String>) -> impl Stream<Item = Result<T>> {
+    futures::stream::iter(
+        chunks
+            .into_iter()
+            .map(|chunk| serde_json::from_str(&chunk).map_err(Into::into)),
+    )
+}
+
+/// Pass the events through, and cache them once the stream has ended without error.
+pub(crate) fn record<T, S>(
+    events: S,
+    cache: Option<(Arc<dyn ResponseCache>, String)>,
+) -> impl Stream<Item = Result<T>>
+where
+    T: DeserializeOwned,
+    S: Stream<Item = Result<serde_json::Value>> + Send + 'static,
+{
+    let state = (events.boxed(), Vec::new(), cache, false);
+    futures::stream::unfold(
+        state,
+        |(mut events, mut chunks, cache, mut failed)| async move {
+            match events.next().await {
+                Some(Ok(event)) => {
+                    chunks.push(event.to_string());
+                    let item = serde_json::from_value(event).map_err(Into::into);
+                    Some((item, (events, chunks, cache, failed)))
+                }
+                Some(Err(e)) => {
+                    failed = true;
+                    Some((Err(e), (events, chunks, cache, failed)))
+                }
+                None => {
+                    if let (Some((cache, key)), false) = (cache, failed) {
+                        if let Err(e) = cache.put(&key, CachedResponse::Chunks(chunks)).await {
+                            tracing::warn!("failed to cache streamed response: {e:#}");
+                        }
+                    }
+                    None
+                }
+            }
+        },
+    )
+}
+
+impl fmt::Display for CacheMissError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "response not cached (key {})", self.key)
+    }
+}
+
+impl std::error::Error for CacheMissError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chat_completion::{ChatCompletionMessage, ChatCompletionRequestBuilder},
+        completion::{CompletionModel, CompletionRequest, CompletionResponse},
+        stub::{StubResponse, StubServer},
+        LlmSdk,
+    };
+    use futures::TryStreamExt;
+    use serde_json::json;
+
+    fn chat_request(seed: usize) -> crate::chat_completion::ChatCompletionRequest {
+        ChatCompletionRequestBuilder::default()
+            .messages(vec![ChatCompletionMessage::new_user("hi", "")])
+            .temperature(0.0)
+            .seed(seed)
+            .build()
+            .unwrap()
+    }
+
+    async fn chat_server() -> StubServer {
+        StubServer::start(|_| {
+            StubResponse::json(
+                200,
+                json!({
+                    "id": "chatcmpl-1",
+                    "object": "chat.completion",
+                    "created": 1700000000,
+                    "model": "gpt-4o-2024-08-06",
+                    "choices": [{"index": 0, "finish_reason": "stop", "message": {"role": "assistant", "content": "hi"}}],
+                    "usage": {"prompt_tokens": 9, "completion_tokens": 1, "total_tokens": 10}
+                }),
+            )
+        })
+        .await
+    }
+
+    #[test]
+    fn cache_key_should_not_depend_on_field_order() -> Result<()> {
+        let a = json!({"model": "gpt-4o", "seed": 1, "temperature": 0.0});
+        let b = json!({"temperature": 0.0, "seed": 1, "model": "gpt-4o"});
+        assert_eq!(cache_key("u", &a)?, cache_key("u", &b)?);
+        assert_ne!(cache_key("u", &a)?, cache_key("v", &a)?);
+        // maps with their own iteration order, as logit_bias
+        let bias = |tokens: &mut dyn Iterator<Item = u32>| json!({ "logit_bias": tokens.map(|t| (t, 1)).collect::<std::collections::HashMap<_, _>>() });
+        assert_eq!(
+            cache_key("u", &bias(&mut (0..64)))?,
+            cache_key("u", &bias(&mut (0..64).rev()))?
+        );
+        assert_ne!(
+            cache_key("u", &chat_request(1))?,
+            cache_key("u", &chat_request(2))?
+        );
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn memory_cache_should_expire_entries() -> Result<()> {
+        let cache = MemoryCache::new().with_ttl(Duration::from_secs(60));
+        let body = CachedResponse::Body("{}".into());
+        cache.put("k", body.clone()).await?;
+        tokio::time::advance(Duration::from_secs(59)).await;
+        assert_eq!(cache.get("k").await?, Some(body));
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(cache.get("k").await?, None);
+        assert!(cache.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn file_cache_should_roundtrip_and_expire() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("llm-sdk-cache-{}", std::process::id()));
+        let cache = FileCache::new(&dir);
+        let chunks = CachedResponse::Chunks(vec!["{\"a\":1}".into(), "{\"a\":2}".into()]);
+        assert_eq!(cache.get("k").await?, None);
+        cache.put("k", chunks.clone()).await?;
+        assert_eq!(FileCache::new(&dir).get("k").await?, Some(chunks.clone()));
+
+        let expired = FileCache::new(&dir).with_ttl(Duration::ZERO);
+        expired.put("k", chunks).await?;
+        assert_eq!(expired.get("k").await?, None);
+        assert!(!dir.join("k.json").exists());
+        tokio::fs::remove_dir_all(&dir).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sdk_should_follow_cache_control() -> Result<()> {
+        let server = chat_server().await;
+        let cache = MemoryCache::new().into_shared();
+        let sdk = LlmSdk::new(server.url(), "", 0).with_response_cache(cache.clone());
+
+        let err = sdk
+            .with_cache_control(CacheControl::OnlyIfCached)
+            .chat_completion(chat_request(1))
+            .await
+            .unwrap_err();
+        assert!(err.is::<CacheMissError>());
+        assert_eq!(server.requests().len(), 0);
+
+        let res = sdk.chat_completion_with_meta(chat_request(1)).await?;
+        assert_eq!(res.choices[0].message.content.as_deref(), Some("hi"));
+        assert!(!res.meta.cached);
+        let res = sdk.chat_completion_with_meta(chat_request(1)).await?;
+        assert_eq!(res.id, "chatcmpl-1");
+        assert!(res.meta.cached);
+        assert_eq!(server.requests().len(), 1);
+
+        // another seed is another request
+        sdk.chat_completion(chat_request(2)).await?;
+        assert_eq!(server.requests().len(), 2);
+        assert_eq!(cache.len(), 2);
+
+        sdk.with_cache_control(CacheControl::Refresh)
+            .chat_completion(chat_request(1))
+            .await?;
+        sdk.with_cache_control(CacheControl::Bypass)
+            .chat_completion(chat_request(3))
+            .await?;
+        assert_eq!(server.requests().len(), 4);
+        assert_eq!(cache.len(), 2);
+
+        sdk.with_cache_control(CacheControl::OnlyIfCached)
+            .chat_completion(chat_request(2))
+            .await?;
+        assert_eq!(server.requests().len(), 4);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sdk_should_replay_cached_stream() -> Result<()> {
+        let server = StubServer::start(|_| {
+            let chunk = |text: &str, finish_reason: Option<&str>| {
+                json!({
+                    "id": "cmpl-1",
+                    "object": "text_completion",
+                    "created": 1700000000,
+                    "model": "gpt-3.5-turbo-instruct",
+                    "choices": [{"text": text, "index": 0, "logprobs": null, "finish_reason": finish_reason}]
+                })
+            };
+            let body = format!(
+                "data: {}\n\ndata: {}\n\ndata: [DONE]\n\n",
+                chunk("Hello", None),
+                chunk(" world", Some("stop"))
+            );
+            StubResponse {
+                status: 200,
+                headers: vec![("content-type".into(), "text/event-stream".into())],
+                body: body.into_bytes(),
+            }
+        })
+        .await;
+        let sdk =
+            LlmSdk::new(server.url(), "", 0).with_response_cache(MemoryCache::new().into_shared());
+        let req = CompletionRequest::new(CompletionModel::default(), "Say hello");
+
+        let sent: Vec<CompletionResponse> = sdk
+            .completion_stream(req.clone())
+            .await?
+            .try_collect()
+            .await?;
+        let replayed: Vec<CompletionResponse> = sdk
+            .completion_stream(req.clone())
+            .await?
+            .try_collect()
+            .await?;
+        assert_eq!(server.requests().len(), 1);
+        assert_eq!(replayed.len(), 2);
+        let text = |chunks: &[CompletionResponse]| -> String {
+            chunks.iter().map(|c| c.choices[0].text.as_str()).collect()
+        };
+        assert_eq!(text(&sent), "Hello world");
+        assert_eq!(text(&replayed), "Hello world");
+
+        // the non-streaming request is cached apart
+        let err = sdk
+            .with_cache_control(CacheControl::OnlyIfCached)
+            .completion(req)
+            .await
+            .unwrap_err();
+        assert!(err.is::<CacheMissError>());
+        Ok(())
+    }
+}
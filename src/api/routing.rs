@@ -0,0 +1,484 @@
+//! Spread requests over several backends, failing over when one of them is down.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    chat_completion::{ChatCompletionRequest, ChatCompletionResponse},
+    circuit_breaker::CircuitOpenError,
+    create_embedding::{CreateEmbeddingRequest, CreateEmbeddingResponse},
+    meta::ApiError,
+    provider::{ChatProvider, EmbeddingProvider},
+    usage,
+};
+
+const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+/// Weight of the latest sample in the latency moving average.
+const LATENCY_SMOOTHING: f64 = 0.2;
+
+/// How the backends serving a request are ordered. The first is tried first, the others are
+/// fallbacks on retryable errors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RoutingPolicy {
+    /// In the order they were added.
+    #[default]
+    Priority,
+    /// Rotate the first backend, each getting a share of requests proportional to its weight.
+    WeightedRoundRobin,
+    /// The fastest first, by the moving average of their response times. Untried backends go first.
+    LeastLatency,
+}
+
+/// One of the backends of a `RoutedSdk`, e.g. an `LlmSdk` for another region or key, or another provider.
+pub struct Backend {
+    name: String,
+    chat: Option<Arc<dyn ChatProvider>>,
+    embedding: Option<Arc<dyn EmbeddingProvider>>,
+    weight: usize,
+    health: Mutex<Health>,
+}
+
+/// The health of a backend, as seen by the router.
+#[derive(Debug, Clone, Default)]
+pub struct Health {
+    /// Retryable failures since the last success.
+    pub consecutive_failures: u32,
+
+    /// The backend is skipped until then, unless all backends are cooling down.
+    pub cooldown_until: Option<Instant>,
+
+    /// Moving average of the response time of successful requests.
+    pub latency: Option<Duration>,
+}
+
+/// A client over several backends with the same `chat_completion` and `create_embedding` methods
+/// as `LlmSdk`.
+///
+/// Retryable errors (rate limits, server errors, timeouts, connection failures) fall through to
+/// the next backend; other errors are returned as is. A backend failing `failure_threshold`
+/// times in a row is skipped for the cooldown.
+pub struct RoutedSdk {
+    backends: Vec<Backend>,
+    policy: RoutingPolicy,
+    /// Model id to the names of the backends serving it, in priority order.
+    routes: HashMap<String, Vec<String>>,
+    failure_threshold: u32,
+    cooldown: Duration,
+    next: AtomicUsize,
+}
+
+#[derive(Clone, Copy)]
+enum Capability {
+    Chat,
+    Embedding,
+}
+
+impl Backend {
+    /// A backend serving chat completions and embeddings.
+    pub fn new<P>(name: impl Into<String>, provider: P) -> Self
+    where
+        P: ChatProvider + EmbeddingProvider + 'static,
+    {
+        let provider = Arc::new(provider);
+        Self {
+            chat: Some(provider.clone()),
+            embedding: Some(provider),
+            ..Self::empty(name.into())
+        }
+    }
+
+    /// A backend serving chat completions only, e.g. `AnthropicSdk`.
+    pub fn chat(name: impl Into<String>, provider: impl ChatProvider + 'static) -> Self {
+        Self {
+            chat: Some(Arc::new(provider)),
+            ..Self::empty(name.into())
+        }
+    }
+
+    /// A backend serving embeddings only.
+    pub fn embedding(name: impl Into<String>, provider: impl EmbeddingProvider + 'static) -> Self {
+        Self {
+            embedding: Some(Arc::new(provider)),
+            ..Self::empty(name.into())
+        }
+    }
+
+    /// The share of requests for `RoutingPolicy::WeightedRoundRobin` (1 by default).
+    pub fn with_weight(mut self, weight: usize) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn health(&self) -> Health {
+        self.health.lock().unwrap().clone()
+    }
+
+    fn empty(name: String) -> Self {
+        Self {
+            name,
+            chat: None,
+            embedding: None,
+            weight: 1,
+            health: Mutex::new(Health::default()),
+        }
+    }
+
+    fn serves(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::Chat => self.chat.is_some(),
+            Capability::Embedding => self.embedding.is_some(),
+        }
+    }
+
+    fn is_cooling_down(&self, now: Instant) -> bool {
+        self.health
+            .lock()
+            .unwrap()
+            .cooldown_until
+            .is_some_and(|until| until > now)
+    }
+
+    fn record_success(&self, elapsed: Duration) {
+        let mut health = self.health.lock().unwrap();
+        health.consecutive_failures = 0;
+        health.cooldown_until = None;
+        health.latency = Some(match health.latency {
+            Some(latency) => {
+                latency.mul_f64(1.0 - LATENCY_SMOOTHING) + elapsed.mul_f64(LATENCY_SMOOTHING)
+            }
+            None => elapsed,
+        });
+    }
+
+    fn record_failure(&self, threshold: u32, cooldown: Duration) {
+        let mut health = self.health.lock().unwrap();
+        health.consecutive_failures += 1;
+        if health.consecutive_failures >= threshold {
+            health.cooldown_until = Some(Instant::now() + cooldown);
+        }
+    }
+}
+
+impl RoutedSdk {
+    pub fn new(policy: RoutingPolicy) -> Self {
+        Self {
+            backends: vec![],
+            policy,
+            routes: HashMap::new(),
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            cooldown: DEFAULT_COOLDOWN,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn with_backend(mut self, backend: Backend) -> Self {
+        self.backends.push(backend);
+        self
+    }
+
+    /// Serve `model` by the named backends only, in this priority order.
+    pub fn with_route(mut self, model: impl Into<String>, backends: &[&str]) -> Self {
+        self.routes.insert(
+            model.into(),
+            backends.iter().map(|name| name.to_string()).collect(),
+        );
+        self
+    }
+
+    /// Skip a backend for `cooldown` after `failures` retryable failures in a row (3 and 30s by default).
+    pub fn with_cooldown(mut self, failures: u32, cooldown: Duration) -> Self {
+        self.failure_threshold = failures.max(1);
+        self.cooldown = cooldown;
+        self
+    }
+
+    pub fn backends(&self) -> &[Backend] {
+        &self.backends
+    }
+
+    pub async fn chat_completion(
+        &self,
+        req: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse> {
+        let model = usage::model_id(&req.model);
+        let mut last_err = None;
+        for i in self.candidates(&model, Capability::Chat)? {
+            let backend = &self.backends[i];
+            let Some(provider) = &backend.chat else {
+                continue;
+            };
+            let start = Instant::now();
+            match provider.chat_completion(req.clone()).await {
+                Ok(res) => {
+                    backend.record_success(start.elapsed());
+                    return Ok(res);
+                }
+                Err(e) => last_err = Some(self.failed(backend, e)?),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| anyhow!("no backend serves chat completions")))
+    }
+
+    pub async fn create_embedding(
+        &self,
+        req: CreateEmbeddingRequest,
+    ) -> Result<CreateEmbeddingResponse> {
+        let model = usage::model_id(&req.model);
+        let mut last_err = None;
+        for i in self.candidates(&model, Capability::Embedding)? {
+            let backend = &self.backends[i];
+            let Some(provider) = &backend.embedding else {
+                continue;
+            };
+            let start = Instant::now();
+            match provider.create_embedding(req.clone()).await {
+                Ok(res) => {
+                    backend.record_success(start.elapsed());
+                    return Ok(res);
+                }
+                Err(e) => last_err = Some(self.failed(backend, e)?),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| anyhow!("no backend serves embeddings")))
+    }
+
+    /// Record a failure of the backend. Returns the error back to try the next backend if it is
+    /// retryable, fails with it otherwise.
+    fn failed(&self, backend: &Backend, err: anyhow::Error) -> Result<anyhow::Error> {
+        if !is_retryable(&err) {
+            return Err(err);
+        }
+        tracing::warn!(
+            backend = backend.name,
+            "backend failed, trying the next: {err:#}"
+        );
+        backend.record_failure(self.failure_threshold, self.cooldown);
+        Ok(err)
+    }
+
+    /// Indexes of the backends to try for the model, in order.
+    fn candidates(&self, model: &str, capability: Capability) -> Result<Vec<usize>> {
+        let mut candidates: Vec<usize> = match self.routes.get(model) {
+            Some(names) => names
+                .iter()
+                .map(|name| {
+                    self.backends
+                        .iter()
+                        .position(|b| &b.name == name)
+                        .ok_or_else(|| anyhow!("route for {model} names unknown backend {name}"))
+                })
+                .collect::<Result<_>>()?,
+            None => (0..self.backends.len()).collect(),
+        };
+        candidates.retain(|i| self.backends[*i].serves(capability));
+
+        match self.policy {
+            RoutingPolicy::Priority => {}
+            RoutingPolicy::WeightedRoundRobin => {
+                let total: usize = candidates.iter().map(|i| self.backends[*i].weight).sum();
+                if total > 0 {
+                    let mut n = self.next.fetch_add(1, Ordering::Relaxed) % total;
+                    let first = candidates
+                        .iter()
+                        .position(|i| match n.checked_sub(self.backends[*i].weight) {
+                            Some(rest) => {
+                                n = rest;
+                                false
+                            }
+                            None => true,
+                        })
+                        .unwrap_or(0);
+                    let first = candidates.remove(first);
+                    candidates.insert(0, first);
+                }
+            }
+            RoutingPolicy::LeastLatency => {
+                candidates.sort_by_key(|i| self.backends[*i].health().latency.unwrap_or_default());
+            }
+        }
+
+        // healthy backends first; cooling down ones stay as a last resort
+        let now = Instant::now();
+        candidates.sort_by_key(|i| self.backends[*i].is_cooling_down(now));
+        Ok(candidates)
+    }
+}
+
+#[async_trait::async_trait]
+impl ChatProvider for RoutedSdk {
+    async fn chat_completion(&self, req: ChatCompletionRequest) -> Result<ChatCompletionResponse> {
+        RoutedSdk::chat_completion(self, req).await
+    }
+}
+
+#[async_trait::async_trait]
+impl EmbeddingProvider for RoutedSdk {
+    async fn create_embedding(
+        &self,
+        req: CreateEmbeddingRequest,
+    ) -> Result<CreateEmbeddingResponse> {
+        RoutedSdk::create_embedding(self, req).await
+    }
+}
+
+/// API errors that may succeed elsewhere or later, and failures to reach the backend at all.
+fn is_retryable(err: &anyhow::Error) -> bool {
+    if let Some(err) = err.downcast_ref::<ApiError>() {
+        return err.is_retryable();
+    }
+    if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+        return !err.is_decode();
+    }
+    if err.is::<CircuitOpenError>() {
+        return true;
+    }
+    err.downcast_ref::<reqwest_middleware::Error>().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chat_completion::{
+            ChatCompletionMessage, ChatCompletionModel, ChatCompletionRequestBuilder,
+        },
+        stub::{StubResponse, StubServer},
+        LlmSdk,
+    };
+    use serde_json::json;
+
+    async fn server(status: u16, model: &str) -> StubServer {
+        let model = model.to_owned();
+        StubServer::start(move |_| {
+            if status != 200 {
+                return StubResponse::json(status, json!({"error": {"message": "unavailable"}}));
+            }
+            StubResponse::json(
+                200,
+                json!({
+                    "id": "chatcmpl-1",
+                    "object": "chat.completion",
+                    "created": 1700000000,
+                    "model": model,
+                    "choices": [{"index": 0, "finish_reason": "stop", "message": {"role": "assistant", "content": "hi"}}]
+                }),
+            )
+        })
+        .await
+    }
+
+    fn req(model: ChatCompletionModel) -> ChatCompletionRequest {
+        ChatCompletionRequestBuilder::default()
+            .model(model)
+            .messages(vec![ChatCompletionMessage::new_user("hi", "")])
+            .build()
+            .unwrap()
+    }
+
+    fn backend(name: &str, server: &StubServer) -> Backend {
+        Backend::new(name, LlmSdk::new(server.url(), "", 0))
+    }
+
+    #[tokio::test]
+    async fn routed_sdk_should_fail_over_and_cool_down() -> Result<()> {
+        let down = server(503, "down").await;
+        let up = server(200, "up").await;
+        let sdk = RoutedSdk::new(RoutingPolicy::Priority)
+            .with_backend(backend("primary", &down))
+            .with_backend(backend("secondary", &up))
+            .with_cooldown(2, Duration::from_secs(60));
+
+        for _ in 0..3 {
+            let res = sdk
+                .chat_completion(req(ChatCompletionModel::Gpt3Turbo))
+                .await?;
+            assert_eq!(res.model, "up");
+        }
+        // the primary is skipped once it cooled down
+        assert_eq!(down.requests().len(), 2);
+        assert_eq!(up.requests().len(), 3);
+        let health = sdk.backends()[0].health();
+        assert_eq!(health.consecutive_failures, 2);
+        assert!(health.cooldown_until.is_some());
+        assert!(sdk.backends()[1].health().latency.is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn routed_sdk_should_not_fail_over_on_client_errors() {
+        let bad = server(400, "bad").await;
+        let up = server(200, "up").await;
+        let sdk = RoutedSdk::new(RoutingPolicy::Priority)
+            .with_backend(backend("primary", &bad))
+            .with_backend(backend("secondary", &up));
+
+        let err = sdk
+            .chat_completion(req(ChatCompletionModel::Gpt3Turbo))
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<ApiError>().is_some());
+        assert!(up.requests().is_empty());
+        assert_eq!(sdk.backends()[0].health().consecutive_failures, 0);
+    }
+
+    #[tokio::test]
+    async fn routed_sdk_should_follow_weights_and_routes() -> Result<()> {
+        let a = server(200, "a").await;
+        let b = server(200, "b").await;
+        let sdk = RoutedSdk::new(RoutingPolicy::WeightedRoundRobin)
+            .with_backend(backend("a", &a).with_weight(3))
+            .with_backend(backend("b", &b))
+            .with_route("gpt-4o", &["b"]);
+
+        let mut models = vec![];
+        for _ in 0..8 {
+            let res = sdk
+                .chat_completion(req(ChatCompletionModel::Gpt3Turbo))
+                .await?;
+            models.push(res.model);
+        }
+        assert_eq!(models.iter().filter(|m| *m == "a").count(), 6);
+
+        let res = sdk
+            .chat_completion(req(ChatCompletionModel::Custom("gpt-4o".into())))
+            .await?;
+        assert_eq!(res.model, "b");
+
+        let sdk = sdk.with_route("gpt-4", &["c"]);
+        assert!(sdk
+            .chat_completion(req(ChatCompletionModel::Custom("gpt-4".into())))
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn routed_sdk_should_prefer_least_latency() -> Result<()> {
+        let slow = server(200, "slow").await;
+        let fast = server(200, "fast").await;
+        let sdk = RoutedSdk::new(RoutingPolicy::LeastLatency)
+            .with_backend(backend("slow", &slow))
+            .with_backend(backend("fast", &fast));
+        sdk.backends()[0].record_success(Duration::from_millis(900));
+        sdk.backends()[1].record_success(Duration::from_millis(100));
+
+        let res = sdk
+            .chat_completion(req(ChatCompletionModel::Gpt3Turbo))
+            .await?;
+        assert_eq!(res.model, "fast");
+        Ok(())
+    }
+}
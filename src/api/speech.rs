@@ -1,6 +1,6 @@
 use derive_builder::Builder;
 use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::IntoRequest;
 
@@ -38,13 +38,16 @@ pub enum SpeechResponseFormat {
     Flac,
 }
 
-#[derive(Debug, Serialize, Clone, Copy, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
 pub enum SpeechModel {
     #[default]
     #[serde(rename = "tts-1")]
     Tts1,
     #[serde(rename = "tts-1-hd")]
     Tts1Hd,
+    /// Any other model id, e.g. "gpt-4o-mini-tts".
+    #[serde(untagged)]
+    Custom(String),
 }
 
 #[derive(Debug, Serialize, Clone, Copy, Default)]
@@ -66,6 +69,14 @@ impl SpeechRequest {
             .build()
             .unwrap()
     }
+
+    pub(crate) fn model(&self) -> &SpeechModel {
+        &self.model
+    }
+
+    pub(crate) fn input(&self) -> &str {
+        &self.input
+    }
 }
 
 impl IntoRequest for SpeechRequest {
@@ -0,0 +1,141 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use serde::de::DeserializeOwned;
+
+/// The sentinel OpenAI sends as the last event of a stream.
+const DONE: &str = "[DONE]";
+
+/// Turn a server-sent events body into a stream of JSON payloads, one per `data:` event.
+///
+/// The stream ends at the `data: [DONE]` sentinel (or when the body ends). Comments and
+/// events without data are skipped, and an event whose data is not valid JSON for `T`
+/// is yielded as an error without ending the stream.
+pub(crate) fn json_events<T, S>(body: S) -> impl Stream<Item = Result<T>>
+where
+    T: DeserializeOwned,
+    S: Stream<Item = Result<Bytes>> + Unpin,
+{
+    let state = SseState {
+        body,
+        buf: Vec::new(),
+        done: false,
+    };
+    futures::stream::unfold(state, |mut state| async move {
+        loop {
+            if state.done {
+                return None;
+            }
+            let event = match next_event(&mut state.buf) {
+                Some(event) => event,
+                None => match state.body.next().await {
+                    Some(Ok(bytes)) => {
+                        state.buf.extend(bytes.iter().filter(|b| **b != b'\r'));
+                        continue;
+                    }
+                    Some(Err(e)) => {
+                        state.done = true;
+                        return Some((Err(e), state));
+                    }
+                    None => {
+                        // flush a last event that was not terminated by a blank line
+                        state.done = true;
+                        std::mem::take(&mut state.buf)
+                    }
+                },
+            };
+            match event_data(&event) {
+                Some(data) if data == DONE => return None,
+                Some(data) => {
+                    let item = serde_json::from_str(&data).map_err(Into::into);
+                    return Some((item, state));
+                }
+                None => continue,
+            }
+        }
+    })
+}
+
+struct SseState<S> {
+    body: S,
+    buf: Vec<u8>,
+    done: bool,
+}
+
+/// Split the first complete event (terminated by a blank line) off the buffer.
+fn next_event(buf: &mut Vec<u8>) -> Option<Vec<u8>> {
+    let pos = buf.windows(2).position(|w| w == b"\n\n")?;
+    let event = buf[..pos].to_vec();
+    buf.drain(..pos + 2);
+    Some(event)
+}
+
+/// Join the `data:` lines of an event, None if it has none.
+fn event_data(event: &[u8]) -> Option<String> {
+    let event = String::from_utf8_lossy(event);
+    let lines: Vec<_> = event
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(|data| data.strip_prefix(' ').unwrap_or(data))
+        .collect();
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::TryStreamExt;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Chunk {
+        text: String,
+    }
+
+    fn body(chunks: &[&'static str]) -> impl Stream<Item = Result<Bytes>> + Unpin {
+        futures::stream::iter(
+            chunks
+                .iter()
+                .map(|c| Ok(Bytes::from_static(c.as_bytes())))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[tokio::test]
+    async fn json_events_should_handle_split_chunks() -> Result<()> {
+        let body = body(&[
+            ": keep-alive\n\n",
+            "data: {\"text\":",
+            "\"Hello\"}\r\n\r\ndata: {\"text\":\" world\"}\n",
+            "\ndata: [DONE]\n\n",
+            "data: {\"text\":\"ignored\"}\n\n",
+        ]);
+        let chunks: Vec<Chunk> = json_events(body).try_collect().await?;
+        assert_eq!(
+            chunks,
+            vec![
+                Chunk {
+                    text: "Hello".to_owned()
+                },
+                Chunk {
+                    text: " world".to_owned()
+                }
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn json_events_should_flush_last_event_and_report_bad_json() -> Result<()> {
+        let body = body(&["data: not json\n\n", "data: {\"text\":\"end\"}"]);
+        let items: Vec<Result<Chunk>> = json_events(body).collect().await;
+        assert_eq!(items.len(), 2);
+        assert!(items[0].is_err());
+        assert_eq!(items[1].as_ref().unwrap().text, "end");
+        Ok(())
+    }
+}
@@ -0,0 +1,527 @@
+//! OpenTelemetry spans for chat, completion and embedding calls, following the GenAI semantic
+//! conventions (`gen_ai.*` attributes), so traces show the model, settings and token usage of
+//! each call next to its latency.
+
+use std::{borrow::Cow, fmt, sync::Arc};
+
+use anyhow::Result;
+use futures::{Stream, StreamExt};
+use opentelemetry::{
+    global::{self, BoxedSpan, BoxedTracer, ObjectSafeTracerProvider},
+    trace::{Span, SpanKind, Status, Tracer},
+    InstrumentationScope, KeyValue, StringValue, Value,
+};
+use serde::Serialize;
+
+use crate::{
+    chat_completion::{ChatCompletionRequest, ChatCompletionResponse, FinishReason},
+    completion::{CompletionRequest, CompletionResponse},
+    create_embedding::{CreateEmbeddingRequest, CreateEmbeddingResponse},
+    meta::{ApiError, WithMeta},
+    usage,
+};
+
+const SCOPE: &str = "llm-sdk";
+
+type Redact = Arc<dyn Fn(&str) -> String + Send + Sync>;
+
+/// Where the spans go and what they record. Prompts and completions are left out unless enabled
+/// with `with_content` or `with_redacted_content`.
+pub struct GenAiTelemetry {
+    tracer: BoxedTracer,
+    system: Cow<'static, str>,
+    content: Option<Redact>,
+}
+
+impl GenAiTelemetry {
+    /// Spans go to the global tracer provider.
+    pub fn new() -> Self {
+        Self {
+            tracer: global::tracer(SCOPE),
+            system: "openai".into(),
+            content: None,
+        }
+    }
+
+    pub fn with_tracer_provider(mut self, provider: &impl ObjectSafeTracerProvider) -> Self {
+        self.tracer =
+            BoxedTracer::new(provider.boxed_tracer(InstrumentationScope::builder(SCOPE).build()));
+        self
+    }
+
+    /// The `gen_ai.system` attribute, "openai" by default. Set it when the SDK talks to another
+    /// OpenAI-compatible server, e.g. "ollama" or "vllm".
+    pub fn with_system(mut self, system: impl Into<Cow<'static, str>>) -> Self {
+        self.system = system.into();
+        self
+    }
+
+    /// Record the messages sent and received as `gen_ai.input.messages` and `gen_ai.output.messages`.
+    pub fn with_content(self) -> Self {
+        self.with_redacted_content(str::to_owned)
+    }
+
+    /// Record the messages like `with_content`, with their texts (contents and tool call arguments)
+    /// passed through `redact` first.
+    pub fn with_redacted_content(
+        mut self,
+        redact: impl Fn(&str) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.content = Some(Arc::new(redact));
+        self
+    }
+
+    pub fn into_shared(self) -> Arc<Self> {
+        Arc::new(self)
+    }
+
+    pub(crate) fn chat_span(&self, base_url: &str, req: &ChatCompletionRequest) -> BoxedSpan {
+        let mut attributes = request_attributes(req.temperature, req.top_p, req.max_tokens);
+        if let Some(seed) = req.seed {
+            attributes.push(KeyValue::new("gen_ai.request.seed", seed as i64));
+        }
+        if let Some(n) = req.n.filter(|n| *n != 1) {
+            attributes.push(KeyValue::new("gen_ai.request.choice.count", n as i64));
+        }
+        if let Some(content) = self.content(&req.messages) {
+            attributes.push(KeyValue::new("gen_ai.input.messages", content));
+        }
+        self.start("chat", &usage::model_id(&req.model), base_url, attributes)
+    }
+
+    pub(crate) fn end_chat(
+        &self,
+        mut span: BoxedSpan,
+        res: &Result<WithMeta<ChatCompletionResponse>>,
+    ) {
+        if let Ok(res) = res {
+            let res = &res.data;
+            span.set_attributes(response_attributes(&res.id, &res.model));
+            span.set_attribute(finish_reasons(res.choices.iter().map(|c| &c.finish_reason)));
+            if let Some(u) = &res.usage {
+                span.set_attributes(usage_attributes(u.prompt_tokens, Some(u.completion_tokens)));
+            }
+            let messages: Vec<_> = res.choices.iter().map(|c| &c.message).collect();
+            if let Some(content) = self.content(&messages) {
+                span.set_attribute(KeyValue::new("gen_ai.output.messages", content));
+            }
+        }
+        end(span, res.as_ref().err());
+    }
+
+    pub(crate) fn completion_span(&self, base_url: &str, req: &CompletionRequest) -> BoxedSpan {
+        let mut attributes = request_attributes(req.temperature, req.top_p, req.max_tokens);
+        if let Some(seed) = req.seed {
+            attributes.push(KeyValue::new("gen_ai.request.seed", seed as i64));
+        }
+        if let Some(content) = self.texts(&req.prompt) {
+            attributes.push(KeyValue::new("gen_ai.input.messages", content));
+        }
+        self.start(
+            "text_completion",
+            &usage::model_id(&req.model),
+            base_url,
+            attributes,
+        )
+    }
+
+    pub(crate) fn end_completion(
+        &self,
+        mut span: BoxedSpan,
+        res: &Result<WithMeta<CompletionResponse>>,
+    ) {
+        if let Ok(res) = res {
+            let res = &res.data;
+            span.set_attributes(response_attributes(&res.id, &res.model));
+            span.set_attribute(finish_reasons(
+                res.choices.iter().filter_map(|c| c.finish_reason.as_ref()),
+            ));
+            if let Some(u) = &res.usage {
+                span.set_attributes(usage_attributes(u.prompt_tokens, Some(u.completion_tokens)));
+            }
+            let texts: Vec<_> = res.choices.iter().map(|c| &c.text).collect();
+            if let Some(content) = self.texts(&texts) {
+                span.set_attribute(KeyValue::new("gen_ai.output.messages", content));
+            }
+        }
+        end(span, res.as_ref().err());
+    }
+
+    pub(crate) fn embedding_span(&self, base_url: &str, req: &CreateEmbeddingRequest) -> BoxedSpan {
+        let mut attributes = vec![];
+        if let Some(format) = &req.encoding_format {
+            attributes.push(KeyValue::new(
+                "gen_ai.request.encoding_formats",
+                Value::Array(vec![StringValue::from(usage::model_id(format))].into()),
+            ));
+        }
+        self.start(
+            "embeddings",
+            &usage::model_id(&req.model),
+            base_url,
+            attributes,
+        )
+    }
+
+    pub(crate) fn end_embedding(
+        &self,
+        mut span: BoxedSpan,
+        res: &Result<WithMeta<CreateEmbeddingResponse>>,
+    ) {
+        if let Ok(res) = res {
+            let res = &res.data;
+            span.set_attribute(KeyValue::new(
+                "gen_ai.response.model",
+                usage::model_id(&res.model),
+            ));
+            span.set_attributes(usage_attributes(res.usage.prompt_tokens, None));
+        }
+        end(span, res.as_ref().err());
+    }
+
+    /// A client span named "{operation} {model}", as the conventions want.
+    fn start(
+        &self,
+        operation: &'static str,
+        model: &str,
+        base_url: &str,
+        mut attributes: Vec<KeyValue>,
+    ) -> BoxedSpan {
+        attributes.push(KeyValue::new("gen_ai.operation.name", operation));
+        attributes.push(KeyValue::new("gen_ai.system", self.system.clone()));
+        attributes.push(KeyValue::new("gen_ai.request.model", model.to_owned()));
+        if let Ok(url) = reqwest::Url::parse(base_url) {
+            if let Some(host) = url.host_str() {
+                attributes.push(KeyValue::new("server.address", host.to_owned()));
+            }
+            if let Some(port) = url.port_or_known_default() {
+                attributes.push(KeyValue::new("server.port", port as i64));
+            }
+        }
+        self.tracer
+            .span_builder(format!("{operation} {model}"))
+            .with_kind(SpanKind::Client)
+            .with_attributes(attributes)
+            .start(&self.tracer)
+    }
+
+    /// The JSON of `value` with its texts redacted, None unless content is recorded.
+    fn content(&self, value: &impl Serialize) -> Option<String> {
+        self.redacted(value, false)
+    }
+
+    /// The JSON of bare texts (prompts, completions) with all of them redacted.
+    fn texts(&self, value: &impl Serialize) -> Option<String> {
+        self.redacted(value, true)
+    }
+
+    fn redacted(&self, value: &impl Serialize, all: bool) -> Option<String> {
+        let redact = self.content.as_ref()?;
+        let mut value = serde_json::to_value(value).ok()?;
+        redact_texts(&mut value, redact.as_ref(), all);
+        Some(value.to_string())
+    }
+}
+
+impl Default for GenAiTelemetry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for GenAiTelemetry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GenAiTelemetry")
+            .field("system", &self.system)
+            .field("content", &self.content.is_some())
+            .finish()
+    }
+}
+
+/// Keep the span open while the stream is read, with the response id, model and finish reasons
+/// of its chunks. Streamed chunks carry no usage, nor is their text recorded.
+pub(crate) fn trace_stream<S>(
+    mut span: Option<BoxedSpan>,
+    res: Result<S>,
+) -> Result<impl Stream<Item = Result<CompletionResponse>>>
+where
+    S: Stream<Item = Result<CompletionResponse>>,
+{
+    let stream = match res {
+        Ok(stream) => stream,
+        Err(e) => {
+            if let Some(span) = span {
+                end(span, Some(&e));
+            }
+            return Err(e);
+        }
+    };
+    let mut described = false;
+    let mut reasons = Vec::new();
+    Ok(stream.map(move |chunk| {
+        match (&chunk, span.as_mut()) {
+            (Ok(chunk), Some(s)) => {
+                if !described {
+                    s.set_attributes(response_attributes(&chunk.id, &chunk.model));
+                    described = true;
+                }
+                reasons.extend(chunk.choices.iter().filter_map(|c| c.finish_reason));
+                if !reasons.is_empty() {
+                    s.set_attribute(finish_reasons(reasons.iter()));
+                }
+            }
+            (Err(e), Some(_)) => end(span.take().unwrap(), Some(e)),
+            (_, None) => {}
+        }
+        chunk
+    }))
+}
+
+fn request_attributes(
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    max_tokens: Option<usize>,
+) -> Vec<KeyValue> {
+    let mut attributes = vec![];
+    if let Some(t) = temperature {
+        attributes.push(KeyValue::new("gen_ai.request.temperature", t as f64));
+    }
+    if let Some(p) = top_p {
+        attributes.push(KeyValue::new("gen_ai.request.top_p", p as f64));
+    }
+    if let Some(n) = max_tokens {
+        attributes.push(KeyValue::new("gen_ai.request.max_tokens", n as i64));
+    }
+    attributes
+}
+
+fn response_attributes(id: &str, model: &str) -> Vec<KeyValue> {
+    vec![
+        KeyValue::new("gen_ai.response.id", id.to_owned()),
+        KeyValue::new("gen_ai.response.model", model.to_owned()),
+    ]
+}
+
+fn finish_reasons<'a>(reasons: impl Iterator<Item = &'a FinishReason>) -> KeyValue {
+    let reasons: Vec<StringValue> = reasons.map(|r| usage::model_id(r).into()).collect();
+    KeyValue::new(
+        "gen_ai.response.finish_reasons",
+        Value::Array(reasons.into()),
+    )
+}
+
+fn usage_attributes(input: usize, output: Option<usize>) -> Vec<KeyValue> {
+    let mut attributes = vec![KeyValue::new("gen_ai.usage.input_tokens", input as i64)];
+    if let Some(output) = output {
+        attributes.push(KeyValue::new("gen_ai.usage.output_tokens", output as i64));
+    }
+    attributes
+}
+
+/// End the span, with the error status and `error.type` (the HTTP status of API errors) on failure.
+fn end(mut span: BoxedSpan, err: Option<&anyhow::Error>) {
+    if let Some(err) = err {
+        let typ = match err.downcast_ref::<ApiError>() {
+            Some(e) => e.status.as_u16().to_string(),
+            None => "_OTHER".to_owned(),
+        };
+        span.set_attribute(KeyValue::new("error.type", typ));
+        span.set_status(Status::error(err.to_string()));
+    }
+    span.end();
+}
+
+/// Redact the strings holding text: message contents and parts, tool call arguments, and prompts
+/// (the whole value when `all`).
+fn redact_texts(value: &mut serde_json::Value, redact: &dyn Fn(&str) -> String, all: bool) {
+    match value {
+        serde_json::Value::String(s) if all => *s = redact(s),
+        serde_json::Value::Array(values) => {
+            for value in values {
+                redact_texts(value, redact, all);
+            }
+        }
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                let text = matches!(key.as_str(), "content" | "text" | "arguments");
+                redact_texts(value, redact, all || text);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chat_completion::{ChatCompletionMessage, ChatCompletionRequestBuilder},
+        stub::{StubResponse, StubServer},
+        LlmSdk,
+    };
+    use opentelemetry::Array;
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+    use serde_json::json;
+
+    fn attribute<'a>(span: &'a SpanData, key: &str) -> Option<&'a Value> {
+        span.attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| &kv.value)
+    }
+
+    async fn traced_sdk(
+        server: &StubServer,
+        telemetry: GenAiTelemetry,
+    ) -> (LlmSdk, InMemorySpanExporter) {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let telemetry = telemetry.with_tracer_provider(&provider).into_shared();
+        let sdk = LlmSdk::new(server.url(), "", 0).with_telemetry(telemetry);
+        (sdk, exporter)
+    }
+
+    fn chat_request() -> ChatCompletionRequest {
+        ChatCompletionRequestBuilder::default()
+            .messages(vec![
+                ChatCompletionMessage::new_system("be brief", ""),
+                ChatCompletionMessage::new_user("my card is 4242", ""),
+            ])
+            .temperature(0.5)
+            .max_tokens(64usize)
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn chat_span_should_follow_genai_conventions() -> Result<()> {
+        let server = StubServer::start(|_| {
+            StubResponse::json(
+                200,
+                json!({
+                    "id": "chatcmpl-1",
+                    "object": "chat.completion",
+                    "created": 1700000000,
+                    "model": "gpt-4o-2024-08-06",
+                    "choices": [{"index": 0, "finish_reason": "stop", "message": {"role": "assistant", "content": "noted"}}],
+                    "usage": {"prompt_tokens": 12, "completion_tokens": 2, "total_tokens": 14}
+                }),
+            )
+        })
+        .await;
+        let (sdk, exporter) = traced_sdk(&server, GenAiTelemetry::new()).await;
+        sdk.chat_completion(chat_request()).await?;
+
+        let spans = exporter.get_finished_spans()?;
+        assert_eq!(spans.len(), 1);
+        let span = &spans[0];
+        let model = usage::model_id(&chat_request().model);
+        assert_eq!(span.name, format!("chat {model}"));
+        assert_eq!(span.span_kind, SpanKind::Client);
+        assert_eq!(span.status, Status::Unset);
+        let expected = [
+            ("gen_ai.operation.name", Value::from("chat")),
+            ("gen_ai.system", Value::from("openai")),
+            ("gen_ai.request.model", Value::from(model)),
+            ("gen_ai.request.temperature", Value::F64(0.5)),
+            ("gen_ai.request.max_tokens", Value::I64(64)),
+            ("gen_ai.response.id", Value::from("chatcmpl-1")),
+            ("gen_ai.response.model", Value::from("gpt-4o-2024-08-06")),
+            (
+                "gen_ai.response.finish_reasons",
+                Value::Array(Array::String(vec!["stop".into()])),
+            ),
+            ("gen_ai.usage.input_tokens", Value::I64(12)),
+            ("gen_ai.usage.output_tokens", Value::I64(2)),
+            ("server.address", Value::from("127.0.0.1")),
+        ];
+        for (key, value) in expected {
+            assert_eq!(attribute(span, key), Some(&value), "{key}");
+        }
+        // content is opt-in
+        assert_eq!(attribute(span, "gen_ai.input.messages"), None);
+        assert_eq!(attribute(span, "gen_ai.output.messages"), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn chat_span_should_redact_content_and_record_errors() -> Result<()> {
+        let server = StubServer::start(|req| {
+            if req.json()["messages"].as_array().unwrap().len() > 2 {
+                return StubResponse::json(400, json!({"error": {"message": "bad request"}}));
+            }
+            StubResponse::json(
+                200,
+                json!({
+                    "id": "chatcmpl-1",
+                    "object": "chat.completion",
+                    "created": 1700000000,
+                    "model": "gpt-4o-2024-08-06",
+                    "choices": [{"index": 0, "finish_reason": "stop", "message": {"role": "assistant", "content": "card 4242 noted"}}],
+                    "usage": {"prompt_tokens": 12, "completion_tokens": 3, "total_tokens": 15}
+                }),
+            )
+        })
+        .await;
+        let telemetry = GenAiTelemetry::new()
+            .with_system("vllm")
+            .with_redacted_content(|text| text.replace("4242", "****"));
+        let (sdk, exporter) = traced_sdk(&server, telemetry).await;
+        sdk.chat_completion(chat_request()).await?;
+
+        let spans = exporter.get_finished_spans()?;
+        let span = &spans[0];
+        assert_eq!(attribute(span, "gen_ai.system"), Some(&Value::from("vllm")));
+        let input = attribute(span, "gen_ai.input.messages").unwrap().as_str();
+        let input: serde_json::Value = serde_json::from_str(&input)?;
+        assert_eq!(input[0]["role"], "system");
+        assert_eq!(input[1]["content"], "my card is ****");
+        let output = attribute(span, "gen_ai.output.messages").unwrap().as_str();
+        assert!(output.contains("card **** noted"), "{output}");
+        assert!(!output.contains("4242"));
+
+        let mut req = chat_request();
+        req.messages
+            .push(ChatCompletionMessage::new_user("again", ""));
+        assert!(sdk.chat_completion(req).await.is_err());
+        let spans = exporter.get_finished_spans()?;
+        let span = &spans[1];
+        assert!(matches!(span.status, Status::Error { .. }));
+        assert_eq!(attribute(span, "error.type"), Some(&Value::from("400")));
+        assert_eq!(attribute(span, "gen_ai.response.id"), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn completion_span_should_redact_content() -> Result<()> {
+        let server = StubServer::start(|_| {
+            StubResponse::json(
+                200,
+                json!({
+                    "id": "cmpl-1",
+                    "object": "text_completion",
+                    "created": 1700000000,
+                    "model": "gpt-3.5-turbo-instruct",
+                    "choices": [{"index": 0, "finish_reason": "stop", "text": " card 4242 noted"}],
+                    "usage": {"prompt_tokens": 5, "completion_tokens": 3, "total_tokens": 8}
+                }),
+            )
+        })
+        .await;
+        let telemetry =
+            GenAiTelemetry::new().with_redacted_content(|text| text.replace("4242", "****"));
+        let (sdk, exporter) = traced_sdk(&server, telemetry).await;
+        let req = CompletionRequest::new(Default::default(), "my card is 4242");
+        sdk.completion(req).await?;
+
+        let spans = exporter.get_finished_spans()?;
+        let span = &spans[0];
+        let input = attribute(span, "gen_ai.input.messages").unwrap().as_str();
+        assert_eq!(input, r#""my card is ****""#);
+        let output = attribute(span, "gen_ai.output.messages").unwrap().as_str();
+        assert_eq!(output, r#"[" card **** noted"]"#);
+        Ok(())
+    }
+}
@@ -0,0 +1,275 @@
+//! Local byte pair encoding with the cl100k_base and o200k_base vocabularies, to estimate
+//! prompt sizes before sending a request.
+//!
+//! Special tokens (e.g. `<|endoftext|>`) are encoded as plain text, like the API does for
+//! user content.
+
+use std::{collections::HashMap, sync::OnceLock};
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use fancy_regex::Regex;
+
+use crate::chat_completion::ChatCompletionModel;
+
+const CL100K_BASE: &str = include_str!("../../assets/cl100k_base.tiktoken");
+const O200K_BASE: &str = include_str!("../../assets/o200k_base.tiktoken");
+
+const CL100K_BASE_PATTERN: &str = r"'(?i:[sdmt]|ll|ve|re)|[^\r\n\p{L}\p{N}]?+\p{L}++|\p{N}{1,3}+| ?[^\s\p{L}\p{N}]++[\r\n]*+|\s++$|\s*[\r\n]|\s+(?!\S)|\s";
+
+const O200K_BASE_PATTERN: &str = concat!(
+    r"[^\r\n\p{L}\p{N}]?[\p{Lu}\p{Lt}\p{Lm}\p{Lo}\p{M}]*[\p{Ll}\p{Lm}\p{Lo}\p{M}]+(?i:'s|'t|'re|'ve|'m|'ll|'d)?",
+    r"|[^\r\n\p{L}\p{N}]?[\p{Lu}\p{Lt}\p{Lm}\p{Lo}\p{M}]+[\p{Ll}\p{Lm}\p{Lo}\p{M}]*(?i:'s|'t|'re|'ve|'m|'ll|'d)?",
+    r"|\p{N}{1,3}",
+    r"| ?[^\s\p{L}\p{N}]+[\r\n/]*",
+    r"|\s*[\r\n]+",
+    r"|\s+(?!\S)",
+    r"|\s+",
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// gpt-3.5-turbo, gpt-4, gpt-4-turbo and the text-embedding-3 / ada-002 embedding models.
+    Cl100kBase,
+    /// gpt-4o, gpt-4.1 and the o-series models.
+    O200kBase,
+}
+
+pub struct Tokenizer {
+    ranks: HashMap<Vec<u8>, u32>,
+    tokens: Vec<Vec<u8>>,
+    pattern: Regex,
+}
+
+/// A span of the text, as split by the pre-tokenization pattern.
+enum Piece<'a> {
+    Matched(&'a str),
+    /// The rest of the text after the pattern failed to match (backtrack limit exceeded), one
+    /// token per byte.
+    Unmatched(&'a str),
+}
+
+impl Encoding {
+    /// The encoding used by the given chat model. Unknown models get cl100k_base.
+    pub fn for_model(model: &ChatCompletionModel) -> Self {
+        match model {
+            ChatCompletionModel::Custom(id)
+                if id.starts_with("gpt-4o")
+                    || id.starts_with("gpt-4.1")
+                    || id.starts_with("gpt-4.5")
+                    || id.starts_with("gpt-5")
+                    || id.starts_with("chatgpt-4o")
+                    || id.starts_with("o1")
+                    || id.starts_with("o3")
+                    || id.starts_with("o4") =>
+            {
+                Encoding::O200kBase
+            }
+            _ => Encoding::Cl100kBase,
+        }
+    }
+
+    /// The tokenizer for this encoding. The vocabulary is parsed on first use.
+    pub fn tokenizer(self) -> &'static Tokenizer {
+        static CL100K: OnceLock<Tokenizer> = OnceLock::new();
+        static O200K: OnceLock<Tokenizer> = OnceLock::new();
+        match self {
+            Encoding::Cl100kBase => {
+                CL100K.get_or_init(|| Tokenizer::new(CL100K_BASE, CL100K_BASE_PATTERN))
+            }
+            Encoding::O200kBase => {
+                O200K.get_or_init(|| Tokenizer::new(O200K_BASE, O200K_BASE_PATTERN))
+            }
+        }
+    }
+}
+
+impl Tokenizer {
+    fn new(vocab: &str, pattern: &str) -> Self {
+        let mut ranks = HashMap::new();
+        let mut tokens = Vec::new();
+        for line in vocab.lines().filter(|l| !l.is_empty()) {
+            let (token, rank) = line.split_once(' ').expect("malformed vocabulary line");
+            let token = STANDARD.decode(token).expect("malformed vocabulary token");
+            let rank: u32 = rank.parse().expect("malformed vocabulary rank");
+            debug_assert_eq!(rank as usize, tokens.len());
+            ranks.insert(token.clone(), rank);
+            tokens.push(token);
+        }
+        let pattern = Regex::new(pattern).expect("invalid pre-tokenization pattern");
+        Self {
+            ranks,
+            tokens,
+            pattern,
+        }
+    }
+
+    /// Encode the text into token ids.
+    pub fn encode(&self, text: &str) -> Vec<u32> {
+        let mut ret = Vec::new();
+        for piece in self.pieces(text) {
+            match piece {
+                Piece::Matched(piece) => match self.ranks.get(piece.as_bytes()) {
+                    Some(rank) => ret.push(*rank),
+                    None => ret.extend(self.byte_pair_encode(piece.as_bytes())),
+                },
+                Piece::Unmatched(rest) => {
+                    ret.extend(rest.bytes().map(|b| self.ranks[std::slice::from_ref(&b)]))
+                }
+            }
+        }
+        ret
+    }
+
+    /// Decode token ids back into text. Fails on unknown ids or if the tokens split a UTF-8 character.
+    pub fn decode(&self, tokens: &[u32]) -> Result<String> {
+        let mut bytes = Vec::new();
+        for token in tokens {
+            let token = self
+                .tokens
+                .get(*token as usize)
+                .ok_or_else(|| anyhow!("unknown token {token}"))?;
+            bytes.extend_from_slice(token);
+        }
+        Ok(String::from_utf8(bytes)?)
+    }
+
+    /// The number of tokens in the text.
+    pub fn count_tokens(&self, text: &str) -> usize {
+        self.pieces(text)
+            .map(|piece| match piece {
+                Piece::Matched(piece) if self.ranks.contains_key(piece.as_bytes()) => 1,
+                Piece::Matched(piece) => self.byte_pair_encode(piece.as_bytes()).len(),
+                Piece::Unmatched(rest) => rest.len(),
+            })
+            .sum()
+    }
+
+    /// Split the text with the pattern. Matching stops at the first error, the rest of the text
+    /// is then left unsplit so that it still counts, if high.
+    fn pieces<'a>(&'a self, text: &'a str) -> impl Iterator<Item = Piece<'a>> + 'a {
+        let mut matches = self.pattern.find_iter(text);
+        let mut end = 0;
+        std::iter::from_fn(move || match matches.next()? {
+            Ok(m) => {
+                end = m.end();
+                Some(Piece::Matched(m.as_str()))
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "pre-tokenization failed ({e}), encoding the last {} bytes one token each",
+                    text.len() - end
+                );
+                Some(Piece::Unmatched(&text[end..]))
+            }
+        })
+    }
+
+    /// Merge the lowest ranked adjacent pair until no pair is in the vocabulary.
+    fn byte_pair_encode(&self, piece: &[u8]) -> Vec<u32> {
+        // token i is piece[bounds[i]..bounds[i + 1]]
+        let mut bounds: Vec<usize> = (0..=piece.len()).collect();
+        loop {
+            let mut best: Option<(u32, usize)> = None;
+            for i in 0..bounds.len().saturating_sub(2) {
+                if let Some(rank) = self.ranks.get(&piece[bounds[i]..bounds[i + 2]]) {
+                    if best.is_none_or(|(r, _)| *rank < r) {
+                        best = Some((*rank, i));
+                    }
+                }
+            }
+            match best {
+                Some((_, i)) => {
+                    bounds.remove(i + 1);
+                }
+                None => break,
+            }
+        }
+        bounds
+            .windows(2)
+            .map(|w| self.ranks[&piece[w[0]..w[1]]])
+            .collect()
+    }
+}
+
+/// The number of cl100k_base tokens in the text (gpt-3.5-turbo, gpt-4 and the embedding models).
+pub fn count_tokens(text: &str) -> usize {
+    Encoding::Cl100kBase.tokenizer().count_tokens(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenizer_should_count_text_the_pattern_fails_on() -> Result<()> {
+        // the alternatives of the last branch backtrack exponentially on a run of "a"
+        let tokenizer = Tokenizer::new(CL100K_BASE, r"\w+(?= )|\s+|(?:a|a(?=a))*(?=c)");
+        let text = format!("hello {}", "a".repeat(40));
+        let tokens = tokenizer.encode(&text);
+        assert_eq!(tokens.len(), 2 + 40);
+        assert_eq!(tokenizer.count_tokens(&text), tokens.len());
+        assert_eq!(tokenizer.decode(&tokens)?, text);
+        Ok(())
+    }
+
+    #[test]
+    fn cl100k_base_should_encode() -> Result<()> {
+        let tokenizer = Encoding::Cl100kBase.tokenizer();
+        let tokens = tokenizer.encode("hello world");
+        assert_eq!(tokens, vec![15339, 1917]);
+        assert_eq!(tokenizer.decode(&tokens)?, "hello world");
+        assert_eq!(count_tokens(""), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn o200k_base_should_encode() -> Result<()> {
+        let tokenizer = Encoding::O200kBase.tokenizer();
+        let tokens = tokenizer.encode("hello world");
+        assert_eq!(tokens, vec![24912, 2375]);
+        assert_eq!(tokenizer.decode(&tokens)?, "hello world");
+        Ok(())
+    }
+
+    #[test]
+    fn tokenizer_should_round_trip_unicode() -> Result<()> {
+        let text = "天气怎么样? It's 22°C in Boston.\n\n  fn main() {}";
+        let expected = [
+            (
+                Encoding::Cl100kBase,
+                vec![
+                    36827, 30320, 242, 17486, 236, 82696, 91985, 30, 1102, 596, 220, 1313, 32037,
+                    304, 10406, 382, 220, 5279, 1925, 368, 4792,
+                ],
+            ),
+            (
+                Encoding::O200kBase,
+                vec![
+                    167823, 34633, 30, 7744, 220, 1709, 26557, 306, 17814, 364, 220, 9784, 2758,
+                    416, 9902,
+                ],
+            ),
+        ];
+        for (encoding, expected) in expected {
+            let tokenizer = encoding.tokenizer();
+            let tokens = tokenizer.encode(text);
+            assert_eq!(tokens, expected);
+            assert_eq!(tokens.len(), tokenizer.count_tokens(text));
+            assert_eq!(tokenizer.decode(&tokens)?, text);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn encoding_should_follow_model() {
+        assert_eq!(
+            Encoding::for_model(&ChatCompletionModel::Gpt4Turbo),
+            Encoding::Cl100kBase
+        );
+        assert_eq!(
+            Encoding::for_model(&ChatCompletionModel::Custom("gpt-4o-mini".to_owned())),
+            Encoding::O200kBase
+        );
+    }
+}
@@ -0,0 +1,403 @@
+use std::{
+    collections::HashMap,
+    ops::AddAssign,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+/// What a set of requests consumed, and what it cost according to the tracker's price table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Images generated.
+    pub images: u64,
+    /// Characters converted to speech.
+    pub tts_characters: u64,
+    /// Seconds of audio transcribed or translated.
+    pub audio_seconds: f64,
+    /// Estimated cost in USD.
+    pub cost: f64,
+}
+
+/// Prices of a model in USD. Only the fields relevant to the model need to be set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub prompt_per_million_tokens: f64,
+    pub completion_per_million_tokens: f64,
+    pub per_image: f64,
+    pub per_million_characters: f64,
+    pub per_audio_minute: f64,
+}
+
+/// Prices per model id. A model without an exact entry uses the longest entry it starts with,
+/// so "gpt-4o-mini" also prices "gpt-4o-mini-2024-07-18".
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PriceTable {
+    prices: HashMap<String, ModelPrice>,
+}
+
+/// Aggregated usage, overall, per model and per tag.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageReport {
+    pub total: Usage,
+    pub by_model: HashMap<String, Usage>,
+    pub by_tag: HashMap<String, Usage>,
+}
+
+/// Opt-in usage accounting for an `LlmSdk`, see `LlmSdk::with_usage_tracker`.
+///
+/// Requests are rejected before being sent once the spend (overall or for the request's tag)
+/// exceeds its budget.
+#[derive(Debug, Default)]
+pub struct UsageTracker {
+    prices: PriceTable,
+    budget: Option<f64>,
+    tag_budgets: HashMap<String, f64>,
+    report: Mutex<UsageReport>,
+}
+
+impl Usage {
+    /// A request to a token-priced model (chat, completions, embeddings).
+    pub fn tokens(prompt_tokens: usize, completion_tokens: usize) -> Self {
+        Self {
+            requests: 1,
+            prompt_tokens: prompt_tokens as u64,
+            completion_tokens: completion_tokens as u64,
+            ..Default::default()
+        }
+    }
+
+    pub fn images(images: usize) -> Self {
+        Self {
+            requests: 1,
+            images: images as u64,
+            ..Default::default()
+        }
+    }
+
+    pub fn tts_characters(characters: usize) -> Self {
+        Self {
+            requests: 1,
+            tts_characters: characters as u64,
+            ..Default::default()
+        }
+    }
+
+    pub fn audio_seconds(seconds: f64) -> Self {
+        Self {
+            requests: 1,
+            audio_seconds: seconds,
+            ..Default::default()
+        }
+    }
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        self.requests += other.requests;
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.images += other.images;
+        self.tts_characters += other.tts_characters;
+        self.audio_seconds += other.audio_seconds;
+        self.cost += other.cost;
+    }
+}
+
+impl ModelPrice {
+    /// A model priced by input and output tokens.
+    pub fn tokens(prompt_per_million: f64, completion_per_million: f64) -> Self {
+        Self {
+            prompt_per_million_tokens: prompt_per_million,
+            completion_per_million_tokens: completion_per_million,
+            ..Default::default()
+        }
+    }
+
+    pub fn per_image(price: f64) -> Self {
+        Self {
+            per_image: price,
+            ..Default::default()
+        }
+    }
+
+    pub fn per_million_characters(price: f64) -> Self {
+        Self {
+            per_million_characters: price,
+            ..Default::default()
+        }
+    }
+
+    pub fn per_audio_minute(price: f64) -> Self {
+        Self {
+            per_audio_minute: price,
+            ..Default::default()
+        }
+    }
+
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        (usage.prompt_tokens as f64 * self.prompt_per_million_tokens
+            + usage.completion_tokens as f64 * self.completion_per_million_tokens
+            + usage.tts_characters as f64 * self.per_million_characters)
+            / 1_000_000.0
+            + usage.images as f64 * self.per_image
+            + usage.audio_seconds / 60.0 * self.per_audio_minute
+    }
+}
+
+impl PriceTable {
+    /// OpenAI list prices at the time of writing (standard quality 1024x1024 images). Override
+    /// with `with_price` when they change or for negotiated rates.
+    pub fn openai() -> Self {
+        Self::default()
+            .with_price("gpt-3.5-turbo", ModelPrice::tokens(0.5, 1.5))
+            .with_price("gpt-3.5-turbo-instruct", ModelPrice::tokens(1.5, 2.0))
+            .with_price("gpt-4", ModelPrice::tokens(30.0, 60.0))
+            .with_price("gpt-4-32k", ModelPrice::tokens(60.0, 120.0))
+            .with_price("gpt-4-turbo", ModelPrice::tokens(10.0, 30.0))
+            .with_price("gpt-4-1106", ModelPrice::tokens(10.0, 30.0))
+            .with_price("gpt-4-0125", ModelPrice::tokens(10.0, 30.0))
+            .with_price("gpt-4o", ModelPrice::tokens(2.5, 10.0))
+            .with_price("gpt-4o-mini", ModelPrice::tokens(0.15, 0.6))
+            .with_price("gpt-4.1", ModelPrice::tokens(2.0, 8.0))
+            .with_price("gpt-4.1-mini", ModelPrice::tokens(0.4, 1.6))
+            .with_price("gpt-4.1-nano", ModelPrice::tokens(0.1, 0.4))
+            .with_price("gpt-4.5-preview", ModelPrice::tokens(75.0, 150.0))
+            .with_price("gpt-5", ModelPrice::tokens(1.25, 10.0))
+            .with_price("gpt-5-mini", ModelPrice::tokens(0.25, 2.0))
+            .with_price("gpt-5-nano", ModelPrice::tokens(0.05, 0.4))
+            .with_price("o1", ModelPrice::tokens(15.0, 60.0))
+            .with_price("o1-mini", ModelPrice::tokens(1.1, 4.4))
+            .with_price("o1-pro", ModelPrice::tokens(150.0, 600.0))
+            .with_price("o3", ModelPrice::tokens(2.0, 8.0))
+            .with_price("o3-mini", ModelPrice::tokens(1.1, 4.4))
+            .with_price("o3-pro", ModelPrice::tokens(20.0, 80.0))
+            .with_price("o4-mini", ModelPrice::tokens(1.1, 4.4))
+            .with_price("davinci-002", ModelPrice::tokens(2.0, 2.0))
+            .with_price("babbage-002", ModelPrice::tokens(0.4, 0.4))
+            .with_price("text-embedding-ada-002", ModelPrice::tokens(0.1, 0.0))
+            .with_price("text-embedding-3-small", ModelPrice::tokens(0.02, 0.0))
+            .with_price("text-embedding-3-large", ModelPrice::tokens(0.13, 0.0))
+            .with_price("dall-e-2", ModelPrice::per_image(0.02))
+            .with_price("dall-e-3", ModelPrice::per_image(0.04))
+            .with_price("tts-1", ModelPrice::per_million_characters(15.0))
+            .with_price("tts-1-hd", ModelPrice::per_million_characters(30.0))
+            .with_price("whisper-1", ModelPrice::per_audio_minute(0.006))
+    }
+
+    pub fn with_price(mut self, model: impl Into<String>, price: ModelPrice) -> Self {
+        self.set_price(model, price);
+        self
+    }
+
+    pub fn set_price(&mut self, model: impl Into<String>, price: ModelPrice) {
+        self.prices.insert(model.into(), price);
+    }
+
+    /// The price of the model, or of the longest model id it is a snapshot of ("gpt-4o-2024-08-06"
+    /// is priced as "gpt-4o"). Only whole `-` separated parts match: "gpt-4.1" isn't a "gpt-4".
+    pub fn get(&self, model: &str) -> Option<&ModelPrice> {
+        self.prices.get(model).or_else(|| {
+            self.prices
+                .iter()
+                .filter(|(prefix, _)| {
+                    model
+                        .strip_prefix(prefix.as_str())
+                        .is_some_and(|rest| rest.starts_with('-'))
+                })
+                .max_by_key(|(prefix, _)| prefix.len())
+                .map(|(_, price)| price)
+        })
+    }
+}
+
+impl UsageTracker {
+    pub fn new(prices: PriceTable) -> Self {
+        Self {
+            prices,
+            ..Default::default()
+        }
+    }
+
+    /// Reject requests once the total spend exceeds this many USD.
+    pub fn with_budget(mut self, usd: f64) -> Self {
+        self.budget = Some(usd);
+        self
+    }
+
+    /// Reject requests tagged `tag` once their spend exceeds this many USD.
+    pub fn with_tag_budget(mut self, tag: impl Into<String>, usd: f64) -> Self {
+        self.tag_budgets.insert(tag.into(), usd);
+        self
+    }
+
+    pub fn into_shared(self) -> Arc<Self> {
+        Arc::new(self)
+    }
+
+    pub fn report(&self) -> UsageReport {
+        self.report.lock().unwrap().clone()
+    }
+
+    pub fn total(&self) -> Usage {
+        self.report.lock().unwrap().total
+    }
+
+    pub fn reset(&self) {
+        *self.report.lock().unwrap() = UsageReport::default();
+    }
+
+    /// Fails if the total spend, or the spend of the tag, is over budget.
+    pub fn check_budget(&self, tag: Option<&str>) -> Result<()> {
+        let report = self.report.lock().unwrap();
+        if let Some(budget) = self.budget {
+            if report.total.cost > budget {
+                return Err(anyhow!(
+                    "usage budget exceeded: spent ${:.4} of ${budget:.4}",
+                    report.total.cost
+                ));
+            }
+        }
+        if let Some(tag) = tag {
+            let spent = report.by_tag.get(tag).map(|u| u.cost).unwrap_or_default();
+            if let Some(budget) = self.tag_budgets.get(tag) {
+                if spent > *budget {
+                    return Err(anyhow!(
+                        "usage budget for {tag} exceeded: spent ${spent:.4} of ${budget:.4}"
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Price the usage of a request and add it to the report. Models missing from the price table
+    /// cost 0, with a warning when a budget applies since it can't be enforced for them.
+    pub fn record(&self, model: &str, tag: Option<&str>, mut usage: Usage) {
+        match self.prices.get(model) {
+            Some(price) => usage.cost = price.cost(&usage),
+            None if self.budget.is_some()
+                || tag.is_some_and(|tag| self.tag_budgets.contains_key(tag)) =>
+            {
+                tracing::warn!(
+                    model,
+                    "no price for model, its usage doesn't count against the budget"
+                );
+            }
+            None => {}
+        }
+        let mut report = self.report.lock().unwrap();
+        report.total += usage;
+        *report.by_model.entry(model.to_owned()).or_default() += usage;
+        if let Some(tag) = tag {
+            *report.by_tag.entry(tag.to_owned()).or_default() += usage;
+        }
+    }
+}
+
+/// The id of a model enum, as sent to the API.
+pub(crate) fn model_id(model: &impl Serialize) -> String {
+    match serde_json::to_value(model) {
+        Ok(serde_json::Value::String(id)) => id,
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chat_completion::{ChatCompletionMessage, ChatCompletionRequestBuilder},
+        LlmSdk,
+    };
+
+    #[test]
+    fn price_table_should_match_longest_prefix() {
+        let prices = PriceTable::openai();
+        assert_eq!(
+            prices.get("gpt-4o-mini-2024-07-18"),
+            Some(&ModelPrice::tokens(0.15, 0.6))
+        );
+        assert_eq!(
+            prices.get("gpt-4o-2024-08-06"),
+            Some(&ModelPrice::tokens(2.5, 10.0))
+        );
+        assert_eq!(
+            prices.get("gpt-4-32k-0613"),
+            Some(&ModelPrice::tokens(60.0, 120.0))
+        );
+        assert_eq!(
+            prices.get("gpt-4.1-2025-04-14"),
+            Some(&ModelPrice::tokens(2.0, 8.0))
+        );
+        assert_eq!(prices.get("o3-mini"), Some(&ModelPrice::tokens(1.1, 4.4)));
+        assert_eq!(
+            prices.get("gpt-5-mini-2025-08-07"),
+            Some(&ModelPrice::tokens(0.25, 2.0))
+        );
+        assert_eq!(prices.get("gpt-4.9"), None);
+        assert_eq!(prices.get("claude-3"), None);
+    }
+
+    #[test]
+    fn usage_tracker_should_aggregate() {
+        let tracker = UsageTracker::new(PriceTable::openai());
+        tracker.record(
+            "gpt-3.5-turbo-0125",
+            Some("search"),
+            Usage::tokens(1_000_000, 1_000_000),
+        );
+        tracker.record("dall-e-3", Some("avatars"), Usage::images(2));
+        tracker.record("whisper-1", None, Usage::audio_seconds(90.0));
+        tracker.record("my-model", None, Usage::tokens(10, 10));
+
+        let report = tracker.report();
+        assert_eq!(report.total.requests, 4);
+        assert_eq!(report.total.prompt_tokens, 1_000_010);
+        assert!((report.by_tag["search"].cost - 2.0).abs() < 1e-9);
+        assert!((report.by_tag["avatars"].cost - 0.08).abs() < 1e-9);
+        assert!((report.by_model["whisper-1"].cost - 0.009).abs() < 1e-9);
+        assert_eq!(report.by_model["my-model"].cost, 0.0);
+        assert!((report.total.cost - 2.089).abs() < 1e-9);
+
+        tracker.reset();
+        assert_eq!(tracker.total(), Usage::default());
+    }
+
+    #[test]
+    fn usage_tracker_should_enforce_budgets() {
+        let tracker = UsageTracker::new(PriceTable::openai())
+            .with_budget(1.0)
+            .with_tag_budget("avatars", 0.05);
+        tracker.record("dall-e-3", Some("avatars"), Usage::images(2));
+        assert!(tracker.check_budget(None).is_ok());
+        assert!(tracker.check_budget(Some("search")).is_ok());
+        assert!(tracker.check_budget(Some("avatars")).is_err());
+
+        tracker.record("gpt-4", None, Usage::tokens(20_000, 10_000));
+        assert!(tracker.check_budget(None).is_err());
+    }
+
+    #[tokio::test]
+    async fn sdk_should_reject_requests_over_budget() {
+        let tracker = UsageTracker::new(PriceTable::openai())
+            .with_budget(0.0)
+            .into_shared();
+        tracker.record("dall-e-3", None, Usage::images(1));
+        let sdk = LlmSdk::new("http://localhost:1", "", 0).with_usage_tracker(tracker);
+        let req = ChatCompletionRequestBuilder::default()
+            .messages(vec![ChatCompletionMessage::new_user("hi", "")])
+            .build()
+            .unwrap();
+        let err = sdk
+            .with_tag("search")
+            .chat_completion(req)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("usage budget exceeded"));
+    }
+}
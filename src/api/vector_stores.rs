@@ -0,0 +1,544 @@
+use derive_builder::Builder;
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    assistants::{LastError, Metadata, ASSISTANTS_V2, OPENAI_BETA},
+    pagination::ListParams,
+    IntoRequest,
+};
+
+#[derive(Debug, Serialize, Clone, Default, Builder)]
+#[builder(pattern = "mutable")]
+pub struct CreateVectorStoreRequest {
+    /// The name of the vector store.
+    #[builder(default, setter(strip_option, into))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+
+    /// A list of File IDs that the vector store should use. Useful for tools like file_search that can access files.
+    #[builder(default, setter(into))]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    file_ids: Vec<String>,
+
+    /// The expiration policy for a vector store.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_after: Option<ExpiresAfter>,
+
+    #[builder(default, setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<Metadata>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetrieveVectorStoreRequest {
+    vector_store_id: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeleteVectorStoreRequest {
+    vector_store_id: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ListVectorStoresRequest {
+    params: ListParams,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CreateVectorStoreFileRequest {
+    #[serde(skip)]
+    vector_store_id: String,
+
+    /// A File ID that the vector store should use.
+    file_id: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ListVectorStoreFilesRequest {
+    #[serde(skip)]
+    vector_store_id: String,
+
+    /// Filter by file status.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    filter: Option<VectorStoreFileStatus>,
+
+    #[serde(flatten)]
+    params: ListParams,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeleteVectorStoreFileRequest {
+    vector_store_id: String,
+    file_id: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CreateFileBatchRequest {
+    #[serde(skip)]
+    vector_store_id: String,
+
+    /// A list of File IDs that the vector store should use.
+    file_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetrieveFileBatchRequest {
+    vector_store_id: String,
+    batch_id: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct CancelFileBatchRequest {
+    vector_store_id: String,
+    batch_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct ExpiresAfter {
+    /// Anchor timestamp after which the expiration policy applies. Supported anchors: last_active_at.
+    pub anchor: ExpiresAfterAnchor,
+
+    /// The number of days after the anchor time that the vector store will expire.
+    pub days: u32,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiresAfterAnchor {
+    #[default]
+    LastActiveAt,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct VectorStore {
+    /// The identifier, which can be referenced in API endpoints.
+    pub id: String,
+
+    /// The object type, which is always "vector_store".
+    pub object: String,
+
+    /// The Unix timestamp (in seconds) for when the vector store was created.
+    pub created_at: u64,
+
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// The total number of bytes used by the files in the vector store.
+    pub usage_bytes: u64,
+
+    pub file_counts: FileCounts,
+
+    /// The status of the vector store. A status of completed indicates that the vector store is ready for use.
+    pub status: VectorStoreStatus,
+
+    #[serde(default)]
+    pub expires_after: Option<ExpiresAfter>,
+
+    /// The Unix timestamp (in seconds) for when the vector store will expire.
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+
+    /// The Unix timestamp (in seconds) for when the vector store was last active.
+    #[serde(default)]
+    pub last_active_at: Option<u64>,
+
+    #[serde(default)]
+    pub metadata: Metadata,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileCounts {
+    /// The number of files that are currently being processed.
+    pub in_progress: usize,
+
+    /// The number of files that have been successfully processed.
+    pub completed: usize,
+
+    /// The number of files that have failed to process.
+    pub failed: usize,
+
+    /// The number of files that were cancelled.
+    pub cancelled: usize,
+
+    /// The total number of files.
+    pub total: usize,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VectorStoreStatus {
+    Expired,
+    InProgress,
+    Completed,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct VectorStoreFile {
+    /// The identifier, which can be referenced in API endpoints.
+    pub id: String,
+
+    /// The object type, which is always "vector_store.file".
+    pub object: String,
+
+    /// The total vector store usage in bytes. Note that this may be different from the original file size.
+    pub usage_bytes: u64,
+
+    pub created_at: u64,
+
+    /// The ID of the vector store that the file is attached to.
+    pub vector_store_id: String,
+
+    /// The status of the vector store file. The status completed indicates that the file is ready for use.
+    pub status: VectorStoreFileStatus,
+
+    /// The last error associated with this vector store file. Will be None if there are no errors.
+    #[serde(default)]
+    pub last_error: Option<LastError>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VectorStoreFileStatus {
+    InProgress,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct VectorStoreFileBatch {
+    /// The identifier, which can be referenced in API endpoints.
+    pub id: String,
+
+    /// The object type, which is always "vector_store.file_batch".
+    pub object: String,
+
+    pub created_at: u64,
+
+    /// The ID of the vector store that the files are attached to.
+    pub vector_store_id: String,
+
+    /// The status of the vector store files batch.
+    pub status: VectorStoreFileStatus,
+
+    pub file_counts: FileCounts,
+}
+
+impl CreateVectorStoreRequest {
+    pub fn new(name: impl Into<String>) -> Self {
+        CreateVectorStoreRequestBuilder::default()
+            .name(name)
+            .build()
+            .unwrap()
+    }
+}
+
+impl RetrieveVectorStoreRequest {
+    pub fn new(vector_store_id: impl Into<String>) -> Self {
+        Self {
+            vector_store_id: vector_store_id.into(),
+        }
+    }
+}
+
+impl DeleteVectorStoreRequest {
+    pub fn new(vector_store_id: impl Into<String>) -> Self {
+        Self {
+            vector_store_id: vector_store_id.into(),
+        }
+    }
+}
+
+impl ListVectorStoresRequest {
+    pub fn new(params: ListParams) -> Self {
+        Self { params }
+    }
+}
+
+impl CreateVectorStoreFileRequest {
+    pub fn new(vector_store_id: impl Into<String>, file_id: impl Into<String>) -> Self {
+        Self {
+            vector_store_id: vector_store_id.into(),
+            file_id: file_id.into(),
+        }
+    }
+}
+
+impl ListVectorStoreFilesRequest {
+    pub fn new(vector_store_id: impl Into<String>, params: ListParams) -> Self {
+        Self {
+            vector_store_id: vector_store_id.into(),
+            filter: None,
+            params,
+        }
+    }
+
+    /// Only list the files with the given status.
+    pub fn with_filter(mut self, filter: VectorStoreFileStatus) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+}
+
+impl DeleteVectorStoreFileRequest {
+    pub fn new(vector_store_id: impl Into<String>, file_id: impl Into<String>) -> Self {
+        Self {
+            vector_store_id: vector_store_id.into(),
+            file_id: file_id.into(),
+        }
+    }
+}
+
+impl CreateFileBatchRequest {
+    pub fn new(vector_store_id: impl Into<String>, file_ids: Vec<String>) -> Self {
+        Self {
+            vector_store_id: vector_store_id.into(),
+            file_ids,
+        }
+    }
+}
+
+impl RetrieveFileBatchRequest {
+    pub fn new(vector_store_id: impl Into<String>, batch_id: impl Into<String>) -> Self {
+        Self {
+            vector_store_id: vector_store_id.into(),
+            batch_id: batch_id.into(),
+        }
+    }
+}
+
+impl CancelFileBatchRequest {
+    pub fn new(vector_store_id: impl Into<String>, batch_id: impl Into<String>) -> Self {
+        Self {
+            vector_store_id: vector_store_id.into(),
+            batch_id: batch_id.into(),
+        }
+    }
+}
+
+impl ExpiresAfter {
+    /// Expire the vector store the given number of days after it was last active.
+    pub fn days(days: u32) -> Self {
+        Self {
+            anchor: ExpiresAfterAnchor::LastActiveAt,
+            days,
+        }
+    }
+}
+
+impl VectorStoreFileStatus {
+    /// Whether the ingestion is finished, successfully or not.
+    pub fn is_terminal(&self) -> bool {
+        !matches!(self, Self::InProgress)
+    }
+}
+
+impl IntoRequest for CreateVectorStoreRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/vector_stores");
+        client
+            .post(url)
+            .header(OPENAI_BETA, ASSISTANTS_V2)
+            .json(&self)
+    }
+}
+
+impl IntoRequest for RetrieveVectorStoreRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/vector_stores/{}", self.vector_store_id);
+        client.get(url).header(OPENAI_BETA, ASSISTANTS_V2)
+    }
+}
+
+impl IntoRequest for DeleteVectorStoreRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/vector_stores/{}", self.vector_store_id);
+        client.delete(url).header(OPENAI_BETA, ASSISTANTS_V2)
+    }
+}
+
+impl IntoRequest for ListVectorStoresRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/vector_stores");
+        client
+            .get(url)
+            .header(OPENAI_BETA, ASSISTANTS_V2)
+            .query(&self.params)
+    }
+}
+
+impl IntoRequest for CreateVectorStoreFileRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/vector_stores/{}/files", self.vector_store_id);
+        client
+            .post(url)
+            .header(OPENAI_BETA, ASSISTANTS_V2)
+            .json(&self)
+    }
+}
+
+impl IntoRequest for ListVectorStoreFilesRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!("{base_url}/vector_stores/{}/files", self.vector_store_id);
+        client
+            .get(url)
+            .header(OPENAI_BETA, ASSISTANTS_V2)
+            .query(&self)
+    }
+}
+
+impl IntoRequest for DeleteVectorStoreFileRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!(
+            "{base_url}/vector_stores/{}/files/{}",
+            self.vector_store_id, self.file_id
+        );
+        client.delete(url).header(OPENAI_BETA, ASSISTANTS_V2)
+    }
+}
+
+impl IntoRequest for CreateFileBatchRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!(
+            "{base_url}/vector_stores/{}/file_batches",
+            self.vector_store_id
+        );
+        client
+            .post(url)
+            .header(OPENAI_BETA, ASSISTANTS_V2)
+            .json(&self)
+    }
+}
+
+impl IntoRequest for RetrieveFileBatchRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!(
+            "{base_url}/vector_stores/{}/file_batches/{}",
+            self.vector_store_id, self.batch_id
+        );
+        client.get(url).header(OPENAI_BETA, ASSISTANTS_V2)
+    }
+}
+
+impl IntoRequest for CancelFileBatchRequest {
+    fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
+        let url = format!(
+            "{base_url}/vector_stores/{}/file_batches/{}/cancel",
+            self.vector_store_id, self.batch_id
+        );
+        client.post(url).header(OPENAI_BETA, ASSISTANTS_V2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        files::{FilePurpose, UploadFileRequest},
+        poll::PollConfig,
+        LlmSdk, SDK,
+    };
+
+    use super::*;
+    use anyhow::Result;
+    use serde_json::json;
+
+    #[test]
+    fn create_vector_store_request_should_serialize() -> Result<()> {
+        let req = CreateVectorStoreRequestBuilder::default()
+            .name("Support FAQ")
+            .file_ids(vec!["file-abc123".to_owned()])
+            .expires_after(ExpiresAfter::days(7))
+            .build()?;
+        assert_eq!(
+            serde_json::to_value(req)?,
+            json!({
+                "name": "Support FAQ",
+                "file_ids": ["file-abc123"],
+                "expires_after": {"anchor": "last_active_at", "days": 7},
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn vector_store_file_batch_should_deserialize() -> Result<()> {
+        let batch: VectorStoreFileBatch = serde_json::from_value(json!({
+            "id": "vsfb_abc123",
+            "object": "vector_store.file_batch",
+            "created_at": 1699061776,
+            "vector_store_id": "vs_abc123",
+            "status": "in_progress",
+            "file_counts": {
+                "in_progress": 1,
+                "completed": 1,
+                "failed": 0,
+                "cancelled": 0,
+                "total": 2
+            }
+        }))?;
+        assert_eq!(batch.status, VectorStoreFileStatus::InProgress);
+        assert!(!batch.status.is_terminal());
+        assert_eq!(batch.file_counts.total, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn list_vector_store_files_request_should_build_query() -> Result<()> {
+        let sdk = LlmSdk::new("https://api.openai.com/v1", "", 0);
+        let req = ListVectorStoreFilesRequest::new("vs_abc123", ListParams::default())
+            .with_filter(VectorStoreFileStatus::Failed);
+        let req = sdk.prepare_request(req).build()?;
+        assert_eq!(
+            req.url().as_str(),
+            "https://api.openai.com/v1/vector_stores/vs_abc123/files?filter=failed"
+        );
+        assert_eq!(req.headers()["OpenAI-Beta"], "assistants=v2");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn vector_store_file_batch_should_work() -> Result<()> {
+        let store = SDK
+            .create_vector_store(CreateVectorStoreRequest::new("llm-sdk test"))
+            .await?;
+        let req = UploadFileRequest::new(
+            b"The quick brown fox jumped over the lazy dog.".to_vec(),
+            "fox.txt",
+            FilePurpose::Assistants,
+        );
+        let file = SDK.upload_file(req).await?;
+
+        let batch = SDK
+            .create_file_batch(CreateFileBatchRequest::new(
+                &store.id,
+                vec![file.id.clone()],
+            ))
+            .await?;
+        let config = PollConfig {
+            initial_delay: Duration::from_millis(500),
+            timeout: Some(Duration::from_secs(60)),
+            ..PollConfig::for_runs()
+        };
+        let batch = SDK
+            .wait_for_file_batch(&store.id, &batch.id, config)
+            .await?;
+        assert_eq!(batch.status, VectorStoreFileStatus::Completed);
+        assert_eq!(batch.file_counts.completed, 1);
+
+        let files = SDK
+            .list_vector_store_files(ListVectorStoreFilesRequest::new(
+                &store.id,
+                ListParams::default(),
+            ))
+            .await?;
+        assert_eq!(files.data.len(), 1);
+
+        SDK.delete_vector_store(&store.id).await?;
+        SDK.delete_file(&file.id).await?;
+        Ok(())
+    }
+}
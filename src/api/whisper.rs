@@ -1,3 +1,5 @@
+use std::fmt;
+
 use derive_builder::Builder;
 use reqwest::multipart::{Form, Part};
 use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
@@ -53,11 +55,15 @@ pub enum WhisperResponseFormat {
     Vtt,
 }
 
-#[derive(Debug, EnumString, Display, Clone, Copy, Default)]
+#[derive(Debug, EnumString, Clone, PartialEq, Eq, Default)]
 pub enum WhisperModel {
     #[default]
     #[strum(serialize = "whisper-1")]
     Whisper1,
+
+    /// Any other model id, e.g. "gpt-4o-transcribe".
+    #[strum(default)]
+    Custom(String),
 }
 
 #[derive(Debug, EnumString, Display, Clone, Copy, Default)]
@@ -72,6 +78,10 @@ pub enum SpeechModel {
 #[derive(Debug, Deserialize, Clone)]
 pub struct WhisperResponse {
     pub text: String,
+
+    /// The duration of the input audio in seconds, only returned with the verbose_json format.
+    #[serde(default)]
+    pub duration: Option<f64>,
 }
 
 impl WhisperRequest {
@@ -92,7 +102,14 @@ impl WhisperRequest {
     }
 
     pub fn is_json(&self) -> bool {
-        self.response_format == WhisperResponseFormat::Json
+        matches!(
+            self.response_format,
+            WhisperResponseFormat::Json | WhisperResponseFormat::VerboseJson
+        )
+    }
+
+    pub(crate) fn model(&self) -> &WhisperModel {
+        &self.model
     }
 
     fn into_form(self) -> Form {
@@ -126,6 +143,15 @@ impl WhisperRequest {
     }
 }
 
+impl fmt::Display for WhisperModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Whisper1 => write!(f, "whisper-1"),
+            Self::Custom(model) => write!(f, "{model}"),
+        }
+    }
+}
+
 impl IntoRequest for WhisperRequest {
     fn into_request(self, base_url: &str, client: ClientWithMiddleware) -> RequestBuilder {
         let url = match self.request_type {
@@ -1,20 +1,31 @@
 mod api;
 mod middleware;
+#[cfg(test)]
+mod stub;
 
 pub use api::*;
 
 use anyhow::{anyhow, Result};
 use api::chat_completion::ChatCompletionResponse;
-use middleware::RetryMiddleware;
+use middleware::{
+    AttemptCounterMiddleware, CircuitBreakerMiddleware, KeyPoolMiddleware, RateLimitMiddleware,
+    RateLimiterMiddleware, RetryMiddleware,
+};
 use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
 use reqwest_tracing::TracingMiddleware;
 use schemars::{schema_for, JsonSchema};
 
 use bytes::Bytes;
-use std::time::Duration;
+use futures::{Stream, StreamExt, TryStreamExt};
+use serde::de::DeserializeOwned;
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use reqwest::{Client, Response};
-use reqwest_middleware::{ClientBuilder, ClientWithMiddleware, RequestBuilder};
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware, Middleware, RequestBuilder};
 
 const TIMEOUT: u64 = 30;
 
@@ -23,6 +34,20 @@ pub struct LlmSdk {
     pub(crate) base_url: String,
     pub(crate) token: String,
     pub(crate) client: ClientWithMiddleware,
+    /// A bare client for URLs outside the API, e.g. generated images, which shouldn't go
+    /// through the API's retries, key pool or rate limits.
+    pub(crate) download_client: Client,
+    pub(crate) usage: Option<Arc<usage::UsageTracker>>,
+    pub(crate) tag: Option<String>,
+    pub(crate) rate_limit: Arc<Mutex<Option<meta::RateLimit>>>,
+    pub(crate) key_pool: Option<Arc<key_pool::KeyPool>>,
+    pub(crate) rate_limiter: Option<Arc<rate_limiter::RateLimiter>>,
+    pub(crate) circuit_breaker: Option<Arc<circuit_breaker::CircuitBreaker>>,
+    pub(crate) response_cache: Option<Arc<dyn response_cache::ResponseCache>>,
+    pub(crate) cache_control: response_cache::CacheControl,
+    #[cfg(feature = "otel")]
+    pub(crate) telemetry: Option<Arc<telemetry::GenAiTelemetry>>,
+    pub(crate) metrics: Option<Arc<dyn metrics::Metrics>>,
 }
 
 pub trait IntoRequest {
@@ -31,69 +56,1456 @@ pub trait IntoRequest {
 
 impl LlmSdk {
     pub fn new(base_url: impl Into<String>, token: impl Into<String>, max_retries: u32) -> Self {
-        let retry_policy = ExponentialBackoff::builder().build_with_max_retries(max_retries);
-        let m = RetryTransientMiddleware::new_with_policy(retry_policy);
-        let client = ClientBuilder::new(Client::new())
-            .with(TracingMiddleware::default())
-            .with(RetryMiddleware::from(m))
-            .build();
+        Self::build(base_url.into(), token.into(), max_retries, None)
+    }
+
+    /// An SDK sending each request with the key of the pool that has the most headroom.
+    ///
+    /// A request answered with 429 is retried with another key (if `max_retries` allows), one
+    /// answered with 401 is sent again with another key right away.
+    pub fn new_with_key_pool(
+        base_url: impl Into<String>,
+        pool: Arc<key_pool::KeyPool>,
+        max_retries: u32,
+    ) -> Self {
+        Self::build(base_url.into(), String::new(), max_retries, Some(pool))
+    }
+
+    fn build(
+        base_url: String,
+        token: String,
+        max_retries: u32,
+        key_pool: Option<Arc<key_pool::KeyPool>>,
+    ) -> Self {
+        let rate_limit = Arc::new(Mutex::new(None));
+        let outer: Vec<Arc<dyn Middleware>> =
+            vec![Arc::new(RateLimitMiddleware::from(rate_limit.clone()))];
+        let mut inner: Vec<Arc<dyn Middleware>> = vec![
+            Arc::new(AttemptCounterMiddleware),
+            Arc::new(RateLimiterMiddleware),
+            Arc::new(CircuitBreakerMiddleware),
+        ];
+        if let Some(pool) = &key_pool {
+            inner.push(Arc::new(KeyPoolMiddleware::from(pool.clone())));
+        }
+        Self {
+            base_url,
+            token,
+            client: build_client(max_retries, &outer, &inner),
+            download_client: Client::new(),
+            usage: None,
+            tag: None,
+            rate_limit,
+            key_pool,
+            rate_limiter: None,
+            circuit_breaker: None,
+            response_cache: None,
+            cache_control: response_cache::CacheControl::Default,
+            #[cfg(feature = "otel")]
+            telemetry: None,
+            metrics: None,
+        }
+    }
+
+    /// The key pool of an SDK made with `new_with_key_pool`.
+    pub fn key_pool(&self) -> Option<&Arc<key_pool::KeyPool>> {
+        self.key_pool.as_ref()
+    }
+
+    /// The rate-limit state reported by the latest response that had one.
+    pub fn rate_limit(&self) -> Option<meta::RateLimit> {
+        *self.rate_limit.lock().unwrap()
+    }
+
+    /// Send any request and decode its JSON body, keeping the response metadata. Every JSON
+    /// endpoint also has a typed `*_with_meta` method built on it.
+    pub async fn send_with_meta<T: DeserializeOwned>(
+        &self,
+        req: impl IntoRequest,
+    ) -> Result<meta::WithMeta<T>> {
+        let observer = self.observe_path(String::new());
+        let res = self.send_json(req, &observer).await;
+        observer.finish(&res, |_| None);
+        res
+    }
+
+    async fn send_json<T: DeserializeOwned>(
+        &self,
+        req: impl IntoRequest,
+        observer: &metrics::Observer,
+    ) -> Result<meta::WithMeta<T>> {
+        let req = observer.attach(self.prepare_request(req));
+        let res = req.send_and_log().await?;
+        let meta = meta::ResponseMeta::from(&res);
+        Ok(meta::WithMeta::new(res.json::<T>().await?, meta))
+    }
+
+    /// Account the usage of chat, completion, embedding, image, speech and whisper requests,
+    /// and reject them once the tracker's budget is exceeded.
+    pub fn with_usage_tracker(mut self, tracker: Arc<usage::UsageTracker>) -> Self {
+        self.usage = Some(tracker);
+        self
+    }
+
+    /// A copy of the SDK whose usage is also accounted under `tag` (e.g. the feature making the calls).
+    pub fn with_tag(&self, tag: impl Into<String>) -> Self {
+        Self {
+            tag: Some(tag.into()),
+            ..self.clone()
+        }
+    }
+
+    pub fn usage_tracker(&self) -> Option<&Arc<usage::UsageTracker>> {
+        self.usage.as_ref()
+    }
+
+    /// Hold chat, completion and embedding requests until their model's limits have room. Each
+    /// retry takes a request slot too.
+    pub fn with_rate_limiter(mut self, limiter: Arc<rate_limiter::RateLimiter>) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Fail fast on endpoints whose circuit is open. Every attempt counts, so retries stop as soon
+    /// as the circuit opens.
+    pub fn with_circuit_breaker(mut self, breaker: Arc<circuit_breaker::CircuitBreaker>) -> Self {
+        self.circuit_breaker = Some(breaker);
+        self
+    }
+
+    /// Answer chat and completion requests (streamed or not) from the cache when an identical
+    /// request was sent before. Only worth it for deterministic requests, e.g. with
+    /// `temperature: 0` and a fixed `seed`.
+    pub fn with_response_cache(mut self, cache: Arc<dyn response_cache::ResponseCache>) -> Self {
+        self.response_cache = Some(cache);
+        self
+    }
+
+    /// A copy of the SDK whose calls use the response cache as told.
+    pub fn with_cache_control(&self, control: response_cache::CacheControl) -> Self {
         Self {
-            base_url: base_url.into(),
-            token: token.into(),
-            client,
+            cache_control: control,
+            ..self.clone()
         }
     }
 
+    /// Trace chat, completion and embedding calls with OpenTelemetry spans.
+    #[cfg(feature = "otel")]
+    pub fn with_telemetry(mut self, telemetry: Arc<telemetry::GenAiTelemetry>) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    /// Report the status, latency and retries of every call, and the token usage of chat,
+    /// completion and embedding calls.
+    pub fn with_metrics(mut self, metrics: Arc<dyn metrics::Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     pub async fn chat_completion(
         &self,
         req: chat_completion::ChatCompletionRequest,
     ) -> Result<chat_completion::ChatCompletionResponse> {
-        let req = self.prepare_request(req);
+        Ok(self.chat_completion_with_meta(req).await?.data)
+    }
+
+    pub async fn chat_completion_with_meta(
+        &self,
+        req: chat_completion::ChatCompletionRequest,
+    ) -> Result<meta::WithMeta<chat_completion::ChatCompletionResponse>> {
+        #[cfg(feature = "otel")]
+        let span = self
+            .telemetry
+            .as_ref()
+            .map(|t| t.chat_span(&self.base_url, &req));
+        let observer = self.observe("chat/completions", usage::model_id(&req.model));
+        let res = self.send_chat_completion(req, &observer).await;
+        #[cfg(feature = "otel")]
+        if let (Some(telemetry), Some(span)) = (&self.telemetry, span) {
+            telemetry.end_chat(span, &res);
+        }
+        observer.finish(&res, |res| {
+            res.usage
+                .as_ref()
+                .map(|u| (u.prompt_tokens, Some(u.completion_tokens)))
+        });
+        res
+    }
+
+    async fn send_chat_completion(
+        &self,
+        req: chat_completion::ChatCompletionRequest,
+        observer: &metrics::Observer,
+    ) -> Result<meta::WithMeta<chat_completion::ChatCompletionResponse>> {
+        let key = self.cache_key("chat/completions", &req)?;
+        if let Some(response_cache::CachedResponse::Body(body)) = self.cache_get(&key).await? {
+            observer.cached();
+            return Ok(meta::WithMeta::new(
+                serde_json::from_str(&body)?,
+                meta::ResponseMeta::cached(),
+            ));
+        }
+        self.check_budget()?;
+        let tokens = req.estimated_tokens();
+        let model = usage::model_id(&req.model);
+        let permit = self.acquire(&model, tokens).await;
+        let charge = key_pool::TokenCharge::new(tokens);
+        let req = self.limit_retries(self.prepare_request(req), &model);
+        let req = observer.attach(req.with_extension(charge.clone()));
+        let res = req.send_and_log().await?;
+        let meta = meta::ResponseMeta::from(&res);
+        let body = res.text().await?;
+        let res = serde_json::from_str::<ChatCompletionResponse>(&body)?;
+        self.cache_put(&key, response_cache::CachedResponse::Body(body))
+            .await;
+        if let Some(u) = &res.usage {
+            permit.settle(u.total_tokens);
+            charge.settle(u.total_tokens);
+            self.record_usage(
+                &res.model,
+                usage::Usage::tokens(u.prompt_tokens, u.completion_tokens),
+            );
+        }
+        Ok(meta::WithMeta::new(res, meta))
+    }
+
+    pub async fn completion(
+        &self,
+        req: completion::CompletionRequest,
+    ) -> Result<completion::CompletionResponse> {
+        Ok(self.completion_with_meta(req).await?.data)
+    }
+
+    pub async fn completion_with_meta(
+        &self,
+        req: completion::CompletionRequest,
+    ) -> Result<meta::WithMeta<completion::CompletionResponse>> {
+        #[cfg(feature = "otel")]
+        let span = self
+            .telemetry
+            .as_ref()
+            .map(|t| t.completion_span(&self.base_url, &req));
+        let observer = self.observe("completions", usage::model_id(req.model()));
+        let res = self.send_completion(req, &observer).await;
+        #[cfg(feature = "otel")]
+        if let (Some(telemetry), Some(span)) = (&self.telemetry, span) {
+            telemetry.end_completion(span, &res);
+        }
+        observer.finish(&res, |res| {
+            res.usage
+                .as_ref()
+                .map(|u| (u.prompt_tokens, Some(u.completion_tokens)))
+        });
+        res
+    }
+
+    async fn send_completion(
+        &self,
+        req: completion::CompletionRequest,
+        observer: &metrics::Observer,
+    ) -> Result<meta::WithMeta<completion::CompletionResponse>> {
+        let key = self.cache_key("completions", &req)?;
+        if let Some(response_cache::CachedResponse::Body(body)) = self.cache_get(&key).await? {
+            observer.cached();
+            return Ok(meta::WithMeta::new(
+                serde_json::from_str(&body)?,
+                meta::ResponseMeta::cached(),
+            ));
+        }
+        self.check_budget()?;
+        let tokens = req.estimated_tokens();
+        let model = usage::model_id(req.model());
+        let permit = self.acquire(&model, tokens).await;
+        let charge = key_pool::TokenCharge::new(tokens);
+        let req = self.limit_retries(self.prepare_request(req), &model);
+        let req = observer.attach(req.with_extension(charge.clone()));
+        let res = req.send_and_log().await?;
+        let meta = meta::ResponseMeta::from(&res);
+        let body = res.text().await?;
+        let res = serde_json::from_str::<completion::CompletionResponse>(&body)?;
+        self.cache_put(&key, response_cache::CachedResponse::Body(body))
+            .await;
+        if let Some(u) = &res.usage {
+            permit.settle(u.total_tokens);
+            charge.settle(u.total_tokens);
+            self.record_usage(
+                &res.model,
+                usage::Usage::tokens(u.prompt_tokens, u.completion_tokens),
+            );
+        }
+        Ok(meta::WithMeta::new(res, meta))
+    }
+
+    /// Stream the completion as it is generated, one chunk per server-sent event.
+    ///
+    /// Streamed chunks carry no usage, so the request is checked against the budget but not accounted.
+    /// With a response cache, the chunks of a stream read to its end are cached and replayed.
+    pub async fn completion_stream(
+        &self,
+        req: completion::CompletionRequest,
+    ) -> Result<impl Stream<Item = Result<completion::CompletionResponse>>> {
+        #[cfg(feature = "otel")]
+        let span = self
+            .telemetry
+            .as_ref()
+            .map(|t| t.completion_span(&self.base_url, &req));
+        let observer = self.observe("completions", usage::model_id(req.model()));
+        let res = self.send_completion_stream(req, &observer).await;
+        #[cfg(feature = "otel")]
+        let res = telemetry::trace_stream(span, res);
+        observer.observe_stream(res)
+    }
+
+    async fn send_completion_stream(
+        &self,
+        req: completion::CompletionRequest,
+        observer: &metrics::Observer,
+    ) -> Result<impl Stream<Item = Result<completion::CompletionResponse>>> {
+        let req = req.into_stream();
+        let key = self.cache_key("completions", &req)?;
+        if let Some(response_cache::CachedResponse::Chunks(chunks)) = self.cache_get(&key).await? {
+            observer.cached();
+            return Ok(response_cache::replay(chunks).left_stream());
+        }
+        self.check_budget()?;
+        let tokens = req.estimated_tokens();
+        let model = usage::model_id(req.model());
+        let _permit = self.acquire(&model, tokens).await;
+        let charge = key_pool::TokenCharge::new(tokens);
+        let req = self.limit_retries(self.prepare_request(req), &model);
+        let req = observer.attach(req.with_extension(charge.clone()));
         let res = req.send_and_log().await?;
-        Ok(res.json::<ChatCompletionResponse>().await?)
+        let events = sse::json_events(res.bytes_stream().map_err(anyhow::Error::from));
+        let cache = key.and_then(|key| Some((self.response_cache.clone()?, key)));
+        Ok(response_cache::record(events, cache).right_stream())
     }
 
     pub async fn create_image(
         &self,
         req: create_image::CreateImageRequest,
     ) -> Result<create_image::CreateImageResponse> {
-        let req = self.prepare_request(req);
+        Ok(self.create_image_with_meta(req).await?.data)
+    }
+
+    pub async fn create_image_with_meta(
+        &self,
+        req: create_image::CreateImageRequest,
+    ) -> Result<meta::WithMeta<create_image::CreateImageResponse>> {
+        let observer = self.observe_path(usage::model_id(req.model()));
+        let res = self.send_create_image(req, &observer).await;
+        observer.finish(&res, |_| None);
+        res
+    }
+
+    async fn send_create_image(
+        &self,
+        req: create_image::CreateImageRequest,
+        observer: &metrics::Observer,
+    ) -> Result<meta::WithMeta<create_image::CreateImageResponse>> {
+        self.check_budget()?;
+        let model = usage::model_id(req.model());
+        let req = observer.attach(self.prepare_request(req));
         let res = req.send_and_log().await?;
-        Ok(res.json::<create_image::CreateImageResponse>().await?)
+        let meta = meta::ResponseMeta::from(&res);
+        let res = res.json::<create_image::CreateImageResponse>().await?;
+        self.record_usage(&model, usage::Usage::images(res.data.len()));
+        Ok(meta::WithMeta::new(res, meta))
     }
 
     pub async fn speech(&self, req: speech::SpeechRequest) -> Result<Bytes> {
-        let req = self.prepare_request(req);
+        Ok(self.speech_with_meta(req).await?.data)
+    }
+
+    pub async fn speech_with_meta(
+        &self,
+        req: speech::SpeechRequest,
+    ) -> Result<meta::WithMeta<Bytes>> {
+        let observer = self.observe_path(usage::model_id(req.model()));
+        let res = self.send_speech(req, &observer).await;
+        observer.finish(&res, |_| None);
+        res
+    }
+
+    async fn send_speech(
+        &self,
+        req: speech::SpeechRequest,
+        observer: &metrics::Observer,
+    ) -> Result<meta::WithMeta<Bytes>> {
+        self.check_budget()?;
+        let model = usage::model_id(req.model());
+        let characters = req.input().chars().count();
+        let req = observer.attach(self.prepare_request(req));
         let res = req.send_and_log().await?;
-        Ok(res.bytes().await?)
+        let meta = meta::ResponseMeta::from(&res);
+        let res = res.bytes().await?;
+        self.record_usage(&model, usage::Usage::tts_characters(characters));
+        Ok(meta::WithMeta::new(res, meta))
     }
 
+    /// Audio seconds are only accounted with the verbose_json response format, the others don't return the duration.
     pub async fn whisper(&self, req: whisper::WhisperRequest) -> Result<whisper::WhisperResponse> {
+        Ok(self.whisper_with_meta(req).await?.data)
+    }
+
+    pub async fn whisper_with_meta(
+        &self,
+        req: whisper::WhisperRequest,
+    ) -> Result<meta::WithMeta<whisper::WhisperResponse>> {
+        let observer = self.observe_path(req.model().to_string());
+        let res = self.send_whisper(req, &observer).await;
+        observer.finish(&res, |_| None);
+        res
+    }
+
+    async fn send_whisper(
+        &self,
+        req: whisper::WhisperRequest,
+        observer: &metrics::Observer,
+    ) -> Result<meta::WithMeta<whisper::WhisperResponse>> {
+        self.check_budget()?;
         let is_json = req.is_json();
-        let req = self.prepare_request(req);
+        let model = req.model().to_string();
+        let req = observer.attach(self.prepare_request(req));
         let res = req.send_and_log().await?;
+        let meta = meta::ResponseMeta::from(&res);
         let ret = if is_json {
             res.json::<whisper::WhisperResponse>().await?
         } else {
             let text = res.text().await?;
-            whisper::WhisperResponse { text }
+            whisper::WhisperResponse {
+                text,
+                duration: None,
+            }
         };
-        Ok(ret)
+        let seconds = ret.duration.unwrap_or_default();
+        self.record_usage(&model, usage::Usage::audio_seconds(seconds));
+        Ok(meta::WithMeta::new(ret, meta))
     }
 
     pub async fn create_embedding(
         &self,
         req: create_embedding::CreateEmbeddingRequest,
     ) -> Result<create_embedding::CreateEmbeddingResponse> {
-        let req = self.prepare_request(req);
+        Ok(self.create_embedding_with_meta(req).await?.data)
+    }
+
+    pub async fn create_embedding_with_meta(
+        &self,
+        req: create_embedding::CreateEmbeddingRequest,
+    ) -> Result<meta::WithMeta<create_embedding::CreateEmbeddingResponse>> {
+        #[cfg(feature = "otel")]
+        let span = self
+            .telemetry
+            .as_ref()
+            .map(|t| t.embedding_span(&self.base_url, &req));
+        let observer = self.observe("embeddings", usage::model_id(&req.model));
+        let res = self.send_create_embedding(req, &observer).await;
+        #[cfg(feature = "otel")]
+        if let (Some(telemetry), Some(span)) = (&self.telemetry, span) {
+            telemetry.end_embedding(span, &res);
+        }
+        observer.finish(&res, |res| Some((res.usage.prompt_tokens, None)));
+        res
+    }
+
+    async fn send_create_embedding(
+        &self,
+        req: create_embedding::CreateEmbeddingRequest,
+        observer: &metrics::Observer,
+    ) -> Result<meta::WithMeta<create_embedding::CreateEmbeddingResponse>> {
+        self.check_budget()?;
+        let tokens = req.estimated_tokens();
+        let model = usage::model_id(&req.model);
+        let permit = self.acquire(&model, tokens).await;
+        let charge = key_pool::TokenCharge::new(tokens);
+        let req = self.limit_retries(self.prepare_request(req), &model);
+        let req = observer.attach(req.with_extension(charge.clone()));
         let res = req.send_and_log().await?;
-        Ok(res
+        let meta = meta::ResponseMeta::from(&res);
+        let res = res
             .json::<create_embedding::CreateEmbeddingResponse>()
-            .await?)
+            .await?;
+        permit.settle(res.usage.total_tokens);
+        charge.settle(res.usage.total_tokens);
+        self.record_usage(
+            &usage::model_id(&res.model),
+            usage::Usage::tokens(res.usage.prompt_tokens, 0),
+        );
+        Ok(meta::WithMeta::new(res, meta))
+    }
+
+    pub async fn create_moderation(
+        &self,
+        req: moderation::ModerationRequest,
+    ) -> Result<moderation::ModerationResponse> {
+        Ok(self.create_moderation_with_meta(req).await?.data)
+    }
+
+    pub async fn create_moderation_with_meta(
+        &self,
+        req: moderation::ModerationRequest,
+    ) -> Result<meta::WithMeta<moderation::ModerationResponse>> {
+        self.send_with_meta(req).await
+    }
+
+    /// Run the user messages through the moderation endpoint before sending them to a chat
+    /// completion. Returns an error listing the flagged categories if any message was flagged.
+    pub async fn screen_messages(
+        &self,
+        messages: &[chat_completion::ChatCompletionMessage],
+    ) -> Result<()> {
+        let Some(req) = moderation::ModerationRequest::from_messages(messages) else {
+            return Ok(());
+        };
+        let res = self.create_moderation(req).await?;
+        let categories: Vec<_> = res
+            .results
+            .iter()
+            .flat_map(|r| r.categories.flagged())
+            .collect();
+        if res.flagged() {
+            return Err(anyhow!("Moderation flagged: {:?}", categories));
+        }
+        Ok(())
+    }
+
+    pub async fn upload_file(&self, req: files::UploadFileRequest) -> Result<files::FileObject> {
+        Ok(self.upload_file_with_meta(req).await?.data)
+    }
+
+    pub async fn upload_file_with_meta(
+        &self,
+        req: files::UploadFileRequest,
+    ) -> Result<meta::WithMeta<files::FileObject>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn list_files(
+        &self,
+        req: files::ListFilesRequest,
+    ) -> Result<pagination::ListResponse<files::FileObject>> {
+        Ok(self.list_files_with_meta(req).await?.data)
+    }
+
+    pub async fn list_files_with_meta(
+        &self,
+        req: files::ListFilesRequest,
+    ) -> Result<meta::WithMeta<pagination::ListResponse<files::FileObject>>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn retrieve_file(&self, file_id: impl Into<String>) -> Result<files::FileObject> {
+        Ok(self.retrieve_file_with_meta(file_id).await?.data)
+    }
+
+    pub async fn retrieve_file_with_meta(
+        &self,
+        file_id: impl Into<String>,
+    ) -> Result<meta::WithMeta<files::FileObject>> {
+        self.send_with_meta(files::RetrieveFileRequest::new(file_id))
+            .await
+    }
+
+    pub async fn delete_file(
+        &self,
+        file_id: impl Into<String>,
+    ) -> Result<files::DeleteFileResponse> {
+        Ok(self.delete_file_with_meta(file_id).await?.data)
+    }
+
+    pub async fn delete_file_with_meta(
+        &self,
+        file_id: impl Into<String>,
+    ) -> Result<meta::WithMeta<files::DeleteFileResponse>> {
+        self.send_with_meta(files::DeleteFileRequest::new(file_id))
+            .await
+    }
+
+    /// Download the content of a file into memory.
+    pub async fn file_content(&self, file_id: impl Into<String>) -> Result<Bytes> {
+        Ok(self.file_content_with_meta(file_id).await?.data)
+    }
+
+    pub async fn file_content_with_meta(
+        &self,
+        file_id: impl Into<String>,
+    ) -> Result<meta::WithMeta<Bytes>> {
+        let observer = self.observe_path(String::new());
+        let res = self.send_file_content(file_id, &observer).await;
+        let res = match res {
+            Ok(meta::WithMeta { data, meta }) => data
+                .bytes()
+                .await
+                .map(|bytes| meta::WithMeta::new(bytes, meta))
+                .map_err(anyhow::Error::from),
+            Err(e) => Err(e),
+        };
+        observer.finish(&res, |_| None);
+        res
+    }
+
+    /// Download the content of a file as a stream of chunks, for files too large to keep in memory.
+    pub async fn file_content_stream(
+        &self,
+        file_id: impl Into<String>,
+    ) -> Result<impl Stream<Item = Result<Bytes>>> {
+        Ok(self.file_content_stream_with_meta(file_id).await?.data)
+    }
+
+    pub async fn file_content_stream_with_meta(
+        &self,
+        file_id: impl Into<String>,
+    ) -> Result<meta::WithMeta<impl Stream<Item = Result<Bytes>>>> {
+        let observer = self.observe_path(String::new());
+        let res = self.send_file_content(file_id, &observer).await;
+        let res = res.map(|res| res.map(|res| res.bytes_stream().map_err(anyhow::Error::from)));
+        // the stream is timed up to its headers, downloads of large files shouldn't skew latencies
+        observer.finish(&res, |_| None);
+        res
+    }
+
+    async fn send_file_content(
+        &self,
+        file_id: impl Into<String>,
+        observer: &metrics::Observer,
+    ) -> Result<meta::WithMeta<reqwest::Response>> {
+        let req = self.prepare_request(files::FileContentRequest::new(file_id));
+        let res = observer.attach(req).send_and_log().await?;
+        let meta = meta::ResponseMeta::from(&res);
+        Ok(meta::WithMeta::new(res, meta))
+    }
+
+    pub async fn create_batch(&self, req: batch::CreateBatchRequest) -> Result<batch::Batch> {
+        Ok(self.create_batch_with_meta(req).await?.data)
+    }
+
+    pub async fn create_batch_with_meta(
+        &self,
+        req: batch::CreateBatchRequest,
+    ) -> Result<meta::WithMeta<batch::Batch>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn retrieve_batch(&self, batch_id: impl Into<String>) -> Result<batch::Batch> {
+        Ok(self.retrieve_batch_with_meta(batch_id).await?.data)
+    }
+
+    pub async fn retrieve_batch_with_meta(
+        &self,
+        batch_id: impl Into<String>,
+    ) -> Result<meta::WithMeta<batch::Batch>> {
+        self.send_with_meta(batch::RetrieveBatchRequest::new(batch_id))
+            .await
+    }
+
+    pub async fn cancel_batch(&self, batch_id: impl Into<String>) -> Result<batch::Batch> {
+        Ok(self.cancel_batch_with_meta(batch_id).await?.data)
+    }
+
+    pub async fn cancel_batch_with_meta(
+        &self,
+        batch_id: impl Into<String>,
+    ) -> Result<meta::WithMeta<batch::Batch>> {
+        self.send_with_meta(batch::CancelBatchRequest::new(batch_id))
+            .await
+    }
+
+    pub async fn list_batches(
+        &self,
+        req: batch::ListBatchesRequest,
+    ) -> Result<pagination::ListResponse<batch::Batch>> {
+        Ok(self.list_batches_with_meta(req).await?.data)
+    }
+
+    pub async fn list_batches_with_meta(
+        &self,
+        req: batch::ListBatchesRequest,
+    ) -> Result<meta::WithMeta<pagination::ListResponse<batch::Batch>>> {
+        self.send_with_meta(req).await
+    }
+
+    /// Poll the batch with exponential backoff until it reaches a terminal status.
+    pub async fn wait_for_batch(
+        &self,
+        batch_id: impl Into<String>,
+        config: poll::PollConfig,
+    ) -> Result<batch::Batch> {
+        let batch_id = batch_id.into();
+        config
+            .run(
+                || self.retrieve_batch(&batch_id),
+                |batch| batch.status.is_terminal(),
+            )
+            .await
+    }
+
+    /// Download and parse the output and error files of a finished batch.
+    pub async fn batch_output<T: DeserializeOwned>(
+        &self,
+        batch: &batch::Batch,
+    ) -> Result<batch::BatchOutput<T>> {
+        let output = match &batch.output_file_id {
+            Some(id) => self.file_content(id).await?,
+            None => Bytes::new(),
+        };
+        let errors = match &batch.error_file_id {
+            Some(id) => self.file_content(id).await?,
+            None => Bytes::new(),
+        };
+        batch::BatchOutput::parse(&output, &errors)
+    }
+
+    /// Upload the requests, create a batch for them, wait for it to finish and return the parsed
+    /// responses keyed by custom_id.
+    pub async fn run_batch<R: batch::BatchRequestItem>(
+        &self,
+        input: batch::BatchInput<R>,
+        config: poll::PollConfig,
+    ) -> Result<batch::BatchOutput<R::Response>> {
+        let req = files::UploadFileRequest::new(
+            input.to_jsonl()?,
+            "batch.jsonl",
+            files::FilePurpose::Batch,
+        );
+        let file = self.upload_file(req).await?;
+        let batch = self
+            .create_batch(batch::CreateBatchRequest::new(file.id, R::ENDPOINT))
+            .await?;
+        let batch = self.wait_for_batch(batch.id, config).await?;
+        if batch.status != batch::BatchStatus::Completed {
+            return Err(anyhow!(
+                "batch {} finished with status {:?}: {:?}",
+                batch.id,
+                batch.status,
+                batch.errors
+            ));
+        }
+        self.batch_output(&batch).await
+    }
+
+    pub async fn create_fine_tuning_job(
+        &self,
+        req: fine_tuning::CreateFineTuningJobRequest,
+    ) -> Result<fine_tuning::FineTuningJob> {
+        Ok(self.create_fine_tuning_job_with_meta(req).await?.data)
+    }
+
+    pub async fn create_fine_tuning_job_with_meta(
+        &self,
+        req: fine_tuning::CreateFineTuningJobRequest,
+    ) -> Result<meta::WithMeta<fine_tuning::FineTuningJob>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn list_fine_tuning_jobs(
+        &self,
+        req: fine_tuning::ListFineTuningJobsRequest,
+    ) -> Result<pagination::ListResponse<fine_tuning::FineTuningJob>> {
+        Ok(self.list_fine_tuning_jobs_with_meta(req).await?.data)
+    }
+
+    pub async fn list_fine_tuning_jobs_with_meta(
+        &self,
+        req: fine_tuning::ListFineTuningJobsRequest,
+    ) -> Result<meta::WithMeta<pagination::ListResponse<fine_tuning::FineTuningJob>>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn retrieve_fine_tuning_job(
+        &self,
+        job_id: impl Into<String>,
+    ) -> Result<fine_tuning::FineTuningJob> {
+        Ok(self.retrieve_fine_tuning_job_with_meta(job_id).await?.data)
+    }
+
+    pub async fn retrieve_fine_tuning_job_with_meta(
+        &self,
+        job_id: impl Into<String>,
+    ) -> Result<meta::WithMeta<fine_tuning::FineTuningJob>> {
+        self.send_with_meta(fine_tuning::RetrieveFineTuningJobRequest::new(job_id))
+            .await
+    }
+
+    pub async fn cancel_fine_tuning_job(
+        &self,
+        job_id: impl Into<String>,
+    ) -> Result<fine_tuning::FineTuningJob> {
+        Ok(self.cancel_fine_tuning_job_with_meta(job_id).await?.data)
+    }
+
+    pub async fn cancel_fine_tuning_job_with_meta(
+        &self,
+        job_id: impl Into<String>,
+    ) -> Result<meta::WithMeta<fine_tuning::FineTuningJob>> {
+        self.send_with_meta(fine_tuning::CancelFineTuningJobRequest::new(job_id))
+            .await
+    }
+
+    pub async fn list_fine_tuning_events(
+        &self,
+        req: fine_tuning::ListFineTuningEventsRequest,
+    ) -> Result<pagination::ListResponse<fine_tuning::FineTuningJobEvent>> {
+        Ok(self.list_fine_tuning_events_with_meta(req).await?.data)
+    }
+
+    pub async fn list_fine_tuning_events_with_meta(
+        &self,
+        req: fine_tuning::ListFineTuningEventsRequest,
+    ) -> Result<meta::WithMeta<pagination::ListResponse<fine_tuning::FineTuningJobEvent>>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn list_fine_tuning_checkpoints(
+        &self,
+        req: fine_tuning::ListFineTuningCheckpointsRequest,
+    ) -> Result<pagination::ListResponse<fine_tuning::FineTuningJobCheckpoint>> {
+        Ok(self.list_fine_tuning_checkpoints_with_meta(req).await?.data)
+    }
+
+    pub async fn list_fine_tuning_checkpoints_with_meta(
+        &self,
+        req: fine_tuning::ListFineTuningCheckpointsRequest,
+    ) -> Result<meta::WithMeta<pagination::ListResponse<fine_tuning::FineTuningJobCheckpoint>>>
+    {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn create_assistant(
+        &self,
+        req: assistants::CreateAssistantRequest,
+    ) -> Result<assistants::Assistant> {
+        Ok(self.create_assistant_with_meta(req).await?.data)
+    }
+
+    pub async fn create_assistant_with_meta(
+        &self,
+        req: assistants::CreateAssistantRequest,
+    ) -> Result<meta::WithMeta<assistants::Assistant>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn retrieve_assistant(
+        &self,
+        assistant_id: impl Into<String>,
+    ) -> Result<assistants::Assistant> {
+        Ok(self.retrieve_assistant_with_meta(assistant_id).await?.data)
+    }
+
+    pub async fn retrieve_assistant_with_meta(
+        &self,
+        assistant_id: impl Into<String>,
+    ) -> Result<meta::WithMeta<assistants::Assistant>> {
+        self.send_with_meta(assistants::RetrieveAssistantRequest::new(assistant_id))
+            .await
+    }
+
+    pub async fn modify_assistant(
+        &self,
+        req: assistants::ModifyAssistantRequest,
+    ) -> Result<assistants::Assistant> {
+        Ok(self.modify_assistant_with_meta(req).await?.data)
+    }
+
+    pub async fn modify_assistant_with_meta(
+        &self,
+        req: assistants::ModifyAssistantRequest,
+    ) -> Result<meta::WithMeta<assistants::Assistant>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn delete_assistant(
+        &self,
+        assistant_id: impl Into<String>,
+    ) -> Result<assistants::DeletionStatus> {
+        Ok(self.delete_assistant_with_meta(assistant_id).await?.data)
+    }
+
+    pub async fn delete_assistant_with_meta(
+        &self,
+        assistant_id: impl Into<String>,
+    ) -> Result<meta::WithMeta<assistants::DeletionStatus>> {
+        self.send_with_meta(assistants::DeleteAssistantRequest::new(assistant_id))
+            .await
+    }
+
+    pub async fn list_assistants(
+        &self,
+        req: assistants::ListAssistantsRequest,
+    ) -> Result<pagination::ListResponse<assistants::Assistant>> {
+        Ok(self.list_assistants_with_meta(req).await?.data)
+    }
+
+    pub async fn list_assistants_with_meta(
+        &self,
+        req: assistants::ListAssistantsRequest,
+    ) -> Result<meta::WithMeta<pagination::ListResponse<assistants::Assistant>>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn create_thread(
+        &self,
+        req: assistants::CreateThreadRequest,
+    ) -> Result<assistants::Thread> {
+        Ok(self.create_thread_with_meta(req).await?.data)
+    }
+
+    pub async fn create_thread_with_meta(
+        &self,
+        req: assistants::CreateThreadRequest,
+    ) -> Result<meta::WithMeta<assistants::Thread>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn retrieve_thread(
+        &self,
+        thread_id: impl Into<String>,
+    ) -> Result<assistants::Thread> {
+        Ok(self.retrieve_thread_with_meta(thread_id).await?.data)
+    }
+
+    pub async fn retrieve_thread_with_meta(
+        &self,
+        thread_id: impl Into<String>,
+    ) -> Result<meta::WithMeta<assistants::Thread>> {
+        self.send_with_meta(assistants::RetrieveThreadRequest::new(thread_id))
+            .await
+    }
+
+    pub async fn modify_thread(
+        &self,
+        req: assistants::ModifyThreadRequest,
+    ) -> Result<assistants::Thread> {
+        Ok(self.modify_thread_with_meta(req).await?.data)
+    }
+
+    pub async fn modify_thread_with_meta(
+        &self,
+        req: assistants::ModifyThreadRequest,
+    ) -> Result<meta::WithMeta<assistants::Thread>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn delete_thread(
+        &self,
+        thread_id: impl Into<String>,
+    ) -> Result<assistants::DeletionStatus> {
+        Ok(self.delete_thread_with_meta(thread_id).await?.data)
+    }
+
+    pub async fn delete_thread_with_meta(
+        &self,
+        thread_id: impl Into<String>,
+    ) -> Result<meta::WithMeta<assistants::DeletionStatus>> {
+        self.send_with_meta(assistants::DeleteThreadRequest::new(thread_id))
+            .await
+    }
+
+    pub async fn create_message(
+        &self,
+        req: assistants::CreateMessageRequest,
+    ) -> Result<assistants::ThreadMessage> {
+        Ok(self.create_message_with_meta(req).await?.data)
+    }
+
+    pub async fn create_message_with_meta(
+        &self,
+        req: assistants::CreateMessageRequest,
+    ) -> Result<meta::WithMeta<assistants::ThreadMessage>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn list_messages(
+        &self,
+        req: assistants::ListMessagesRequest,
+    ) -> Result<pagination::ListResponse<assistants::ThreadMessage>> {
+        Ok(self.list_messages_with_meta(req).await?.data)
+    }
+
+    pub async fn list_messages_with_meta(
+        &self,
+        req: assistants::ListMessagesRequest,
+    ) -> Result<meta::WithMeta<pagination::ListResponse<assistants::ThreadMessage>>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn retrieve_message(
+        &self,
+        thread_id: impl Into<String>,
+        message_id: impl Into<String>,
+    ) -> Result<assistants::ThreadMessage> {
+        Ok(self
+            .retrieve_message_with_meta(thread_id, message_id)
+            .await?
+            .data)
+    }
+
+    pub async fn retrieve_message_with_meta(
+        &self,
+        thread_id: impl Into<String>,
+        message_id: impl Into<String>,
+    ) -> Result<meta::WithMeta<assistants::ThreadMessage>> {
+        self.send_with_meta(assistants::RetrieveMessageRequest::new(
+            thread_id, message_id,
+        ))
+        .await
+    }
+
+    pub async fn create_run(&self, req: assistants::CreateRunRequest) -> Result<assistants::Run> {
+        Ok(self.create_run_with_meta(req).await?.data)
+    }
+
+    pub async fn create_run_with_meta(
+        &self,
+        req: assistants::CreateRunRequest,
+    ) -> Result<meta::WithMeta<assistants::Run>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn retrieve_run(
+        &self,
+        thread_id: impl Into<String>,
+        run_id: impl Into<String>,
+    ) -> Result<assistants::Run> {
+        Ok(self.retrieve_run_with_meta(thread_id, run_id).await?.data)
+    }
+
+    pub async fn retrieve_run_with_meta(
+        &self,
+        thread_id: impl Into<String>,
+        run_id: impl Into<String>,
+    ) -> Result<meta::WithMeta<assistants::Run>> {
+        self.send_with_meta(assistants::RetrieveRunRequest::new(thread_id, run_id))
+            .await
+    }
+
+    pub async fn cancel_run(
+        &self,
+        thread_id: impl Into<String>,
+        run_id: impl Into<String>,
+    ) -> Result<assistants::Run> {
+        Ok(self.cancel_run_with_meta(thread_id, run_id).await?.data)
+    }
+
+    pub async fn cancel_run_with_meta(
+        &self,
+        thread_id: impl Into<String>,
+        run_id: impl Into<String>,
+    ) -> Result<meta::WithMeta<assistants::Run>> {
+        self.send_with_meta(assistants::CancelRunRequest::new(thread_id, run_id))
+            .await
+    }
+
+    pub async fn list_runs(
+        &self,
+        req: assistants::ListRunsRequest,
+    ) -> Result<pagination::ListResponse<assistants::Run>> {
+        Ok(self.list_runs_with_meta(req).await?.data)
+    }
+
+    pub async fn list_runs_with_meta(
+        &self,
+        req: assistants::ListRunsRequest,
+    ) -> Result<meta::WithMeta<pagination::ListResponse<assistants::Run>>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn submit_tool_outputs(
+        &self,
+        req: assistants::SubmitToolOutputsRequest,
+    ) -> Result<assistants::Run> {
+        Ok(self.submit_tool_outputs_with_meta(req).await?.data)
+    }
+
+    pub async fn submit_tool_outputs_with_meta(
+        &self,
+        req: assistants::SubmitToolOutputsRequest,
+    ) -> Result<meta::WithMeta<assistants::Run>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn list_run_steps(
+        &self,
+        req: assistants::ListRunStepsRequest,
+    ) -> Result<pagination::ListResponse<assistants::RunStep>> {
+        Ok(self.list_run_steps_with_meta(req).await?.data)
+    }
+
+    pub async fn list_run_steps_with_meta(
+        &self,
+        req: assistants::ListRunStepsRequest,
+    ) -> Result<meta::WithMeta<pagination::ListResponse<assistants::RunStep>>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn retrieve_run_step(
+        &self,
+        req: assistants::RetrieveRunStepRequest,
+    ) -> Result<assistants::RunStep> {
+        Ok(self.retrieve_run_step_with_meta(req).await?.data)
+    }
+
+    pub async fn retrieve_run_step_with_meta(
+        &self,
+        req: assistants::RetrieveRunStepRequest,
+    ) -> Result<meta::WithMeta<assistants::RunStep>> {
+        self.send_with_meta(req).await
+    }
+
+    /// Drive a run until it finishes. Whenever the run requires action, `handler` is called for
+    /// every tool call and its results are submitted as the tool outputs.
+    pub async fn run_to_completion<F, Fut>(
+        &self,
+        mut run: assistants::Run,
+        config: poll::PollConfig,
+        mut handler: F,
+    ) -> Result<assistants::Run>
+    where
+        F: FnMut(chat_completion::ToolCall) -> Fut,
+        Fut: Future<Output = Result<String>>,
+    {
+        loop {
+            if run.status.is_pending() {
+                let (thread_id, run_id) = (run.thread_id.clone(), run.id.clone());
+                run = config
+                    .run(
+                        || self.retrieve_run(&thread_id, &run_id),
+                        |run| !run.status.is_pending(),
+                    )
+                    .await?;
+            }
+            if run.status != assistants::RunStatus::RequiresAction {
+                return Ok(run);
+            }
+
+            let mut outputs = Vec::new();
+            for call in run.required_tool_calls() {
+                let output = handler(call.clone()).await?;
+                outputs.push(assistants::ToolOutput::new(call.id(), output));
+            }
+            let req = assistants::SubmitToolOutputsRequest::new(&run.thread_id, &run.id, outputs);
+            run = self.submit_tool_outputs(req).await?;
+        }
+    }
+
+    pub async fn create_vector_store(
+        &self,
+        req: vector_stores::CreateVectorStoreRequest,
+    ) -> Result<vector_stores::VectorStore> {
+        Ok(self.create_vector_store_with_meta(req).await?.data)
+    }
+
+    pub async fn create_vector_store_with_meta(
+        &self,
+        req: vector_stores::CreateVectorStoreRequest,
+    ) -> Result<meta::WithMeta<vector_stores::VectorStore>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn retrieve_vector_store(
+        &self,
+        vector_store_id: impl Into<String>,
+    ) -> Result<vector_stores::VectorStore> {
+        Ok(self
+            .retrieve_vector_store_with_meta(vector_store_id)
+            .await?
+            .data)
+    }
+
+    pub async fn retrieve_vector_store_with_meta(
+        &self,
+        vector_store_id: impl Into<String>,
+    ) -> Result<meta::WithMeta<vector_stores::VectorStore>> {
+        self.send_with_meta(vector_stores::RetrieveVectorStoreRequest::new(
+            vector_store_id,
+        ))
+        .await
+    }
+
+    pub async fn delete_vector_store(
+        &self,
+        vector_store_id: impl Into<String>,
+    ) -> Result<assistants::DeletionStatus> {
+        Ok(self
+            .delete_vector_store_with_meta(vector_store_id)
+            .await?
+            .data)
+    }
+
+    pub async fn delete_vector_store_with_meta(
+        &self,
+        vector_store_id: impl Into<String>,
+    ) -> Result<meta::WithMeta<assistants::DeletionStatus>> {
+        self.send_with_meta(vector_stores::DeleteVectorStoreRequest::new(
+            vector_store_id,
+        ))
+        .await
+    }
+
+    pub async fn list_vector_stores(
+        &self,
+        req: vector_stores::ListVectorStoresRequest,
+    ) -> Result<pagination::ListResponse<vector_stores::VectorStore>> {
+        Ok(self.list_vector_stores_with_meta(req).await?.data)
+    }
+
+    pub async fn list_vector_stores_with_meta(
+        &self,
+        req: vector_stores::ListVectorStoresRequest,
+    ) -> Result<meta::WithMeta<pagination::ListResponse<vector_stores::VectorStore>>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn create_vector_store_file(
+        &self,
+        req: vector_stores::CreateVectorStoreFileRequest,
+    ) -> Result<vector_stores::VectorStoreFile> {
+        Ok(self.create_vector_store_file_with_meta(req).await?.data)
+    }
+
+    pub async fn create_vector_store_file_with_meta(
+        &self,
+        req: vector_stores::CreateVectorStoreFileRequest,
+    ) -> Result<meta::WithMeta<vector_stores::VectorStoreFile>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn list_vector_store_files(
+        &self,
+        req: vector_stores::ListVectorStoreFilesRequest,
+    ) -> Result<pagination::ListResponse<vector_stores::VectorStoreFile>> {
+        Ok(self.list_vector_store_files_with_meta(req).await?.data)
+    }
+
+    pub async fn list_vector_store_files_with_meta(
+        &self,
+        req: vector_stores::ListVectorStoreFilesRequest,
+    ) -> Result<meta::WithMeta<pagination::ListResponse<vector_stores::VectorStoreFile>>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn delete_vector_store_file(
+        &self,
+        vector_store_id: impl Into<String>,
+        file_id: impl Into<String>,
+    ) -> Result<assistants::DeletionStatus> {
+        Ok(self
+            .delete_vector_store_file_with_meta(vector_store_id, file_id)
+            .await?
+            .data)
+    }
+
+    pub async fn delete_vector_store_file_with_meta(
+        &self,
+        vector_store_id: impl Into<String>,
+        file_id: impl Into<String>,
+    ) -> Result<meta::WithMeta<assistants::DeletionStatus>> {
+        self.send_with_meta(vector_stores::DeleteVectorStoreFileRequest::new(
+            vector_store_id,
+            file_id,
+        ))
+        .await
+    }
+
+    pub async fn create_file_batch(
+        &self,
+        req: vector_stores::CreateFileBatchRequest,
+    ) -> Result<vector_stores::VectorStoreFileBatch> {
+        Ok(self.create_file_batch_with_meta(req).await?.data)
+    }
+
+    pub async fn create_file_batch_with_meta(
+        &self,
+        req: vector_stores::CreateFileBatchRequest,
+    ) -> Result<meta::WithMeta<vector_stores::VectorStoreFileBatch>> {
+        self.send_with_meta(req).await
+    }
+
+    pub async fn retrieve_file_batch(
+        &self,
+        vector_store_id: impl Into<String>,
+        batch_id: impl Into<String>,
+    ) -> Result<vector_stores::VectorStoreFileBatch> {
+        Ok(self
+            .retrieve_file_batch_with_meta(vector_store_id, batch_id)
+            .await?
+            .data)
+    }
+
+    pub async fn retrieve_file_batch_with_meta(
+        &self,
+        vector_store_id: impl Into<String>,
+        batch_id: impl Into<String>,
+    ) -> Result<meta::WithMeta<vector_stores::VectorStoreFileBatch>> {
+        self.send_with_meta(vector_stores::RetrieveFileBatchRequest::new(
+            vector_store_id,
+            batch_id,
+        ))
+        .await
+    }
+
+    pub async fn cancel_file_batch(
+        &self,
+        vector_store_id: impl Into<String>,
+        batch_id: impl Into<String>,
+    ) -> Result<vector_stores::VectorStoreFileBatch> {
+        Ok(self
+            .cancel_file_batch_with_meta(vector_store_id, batch_id)
+            .await?
+            .data)
+    }
+
+    pub async fn cancel_file_batch_with_meta(
+        &self,
+        vector_store_id: impl Into<String>,
+        batch_id: impl Into<String>,
+    ) -> Result<meta::WithMeta<vector_stores::VectorStoreFileBatch>> {
+        self.send_with_meta(vector_stores::CancelFileBatchRequest::new(
+            vector_store_id,
+            batch_id,
+        ))
+        .await
+    }
+
+    /// Poll the file batch with exponential backoff until its files are ingested (or failed / cancelled).
+    pub async fn wait_for_file_batch(
+        &self,
+        vector_store_id: impl Into<String>,
+        batch_id: impl Into<String>,
+        config: poll::PollConfig,
+    ) -> Result<vector_stores::VectorStoreFileBatch> {
+        let vector_store_id = vector_store_id.into();
+        let batch_id = batch_id.into();
+        config
+            .run(
+                || self.retrieve_file_batch(&vector_store_id, &batch_id),
+                |batch| batch.status.is_terminal(),
+            )
+            .await
+    }
+
+    pub async fn list_models(&self) -> Result<models::ListModelsResponse> {
+        Ok(self.list_models_with_meta().await?.data)
+    }
+
+    pub async fn list_models_with_meta(
+        &self,
+    ) -> Result<meta::WithMeta<models::ListModelsResponse>> {
+        self.send_with_meta(models::ListModelsRequest).await
+    }
+
+    pub async fn retrieve_model(&self, model: impl Into<String>) -> Result<models::Model> {
+        Ok(self.retrieve_model_with_meta(model).await?.data)
+    }
+
+    pub async fn retrieve_model_with_meta(
+        &self,
+        model: impl Into<String>,
+    ) -> Result<meta::WithMeta<models::Model>> {
+        self.send_with_meta(models::RetrieveModelRequest::new(model))
+            .await
+    }
+
+    pub async fn delete_model(
+        &self,
+        model: impl Into<String>,
+    ) -> Result<models::DeleteModelResponse> {
+        Ok(self.delete_model_with_meta(model).await?.data)
+    }
+
+    pub async fn delete_model_with_meta(
+        &self,
+        model: impl Into<String>,
+    ) -> Result<meta::WithMeta<models::DeleteModelResponse>> {
+        self.send_with_meta(models::DeleteModelRequest::new(model))
+            .await
+    }
+
+    /// The cache key of the request, None if the call doesn't use a response cache.
+    fn cache_key(&self, endpoint: &str, req: &impl serde::Serialize) -> Result<Option<String>> {
+        match (&self.response_cache, self.cache_control) {
+            (None, _) | (_, response_cache::CacheControl::Bypass) => Ok(None),
+            _ => Ok(Some(response_cache::cache_key(
+                &format!("{}/{endpoint}", self.base_url),
+                req,
+            )?)),
+        }
+    }
+
+    async fn cache_get(
+        &self,
+        key: &Option<String>,
+    ) -> Result<Option<response_cache::CachedResponse>> {
+        let (Some(cache), Some(key)) = (&self.response_cache, key) else {
+            return Ok(None);
+        };
+        if self.cache_control == response_cache::CacheControl::Refresh {
+            return Ok(None);
+        }
+        let cached = cache.get(key).await.unwrap_or_else(|e| {
+            tracing::warn!("failed to read response cache: {e:#}");
+            None
+        });
+        if cached.is_none() && self.cache_control == response_cache::CacheControl::OnlyIfCached {
+            return Err(response_cache::CacheMissError { key: key.clone() }.into());
+        }
+        Ok(cached)
+    }
+
+    async fn cache_put(&self, key: &Option<String>, response: response_cache::CachedResponse) {
+        if let (Some(cache), Some(key)) = (&self.response_cache, key) {
+            if let Err(e) = cache.put(key, response).await {
+                tracing::warn!("failed to cache response: {e:#}");
+            }
+        }
+    }
+
+    fn observe(&self, endpoint: &'static str, model: String) -> metrics::Observer {
+        metrics::Observer::new(self.metrics.clone(), endpoint, model)
+    }
+
+    /// An observer naming the endpoint after the path the request is sent to.
+    fn observe_path(&self, model: String) -> metrics::Observer {
+        metrics::Observer::for_path(self.metrics.clone(), &self.base_url, model)
+    }
+
+    fn check_budget(&self) -> Result<()> {
+        match &self.usage {
+            Some(tracker) => tracker.check_budget(self.tag.as_deref()),
+            None => Ok(()),
+        }
+    }
+
+    async fn acquire(&self, model: &str, tokens: usize) -> rate_limiter::Permit {
+        match &self.rate_limiter {
+            Some(limiter) => limiter.acquire(model, tokens).await,
+            None => rate_limiter::Permit::none(),
+        }
+    }
+
+    /// Make each retry of the request take a request slot of the model, as the first attempt did.
+    fn limit_retries(&self, req: RequestBuilder, model: &str) -> RequestBuilder {
+        match &self.rate_limiter {
+            Some(limiter) => {
+                req.with_extension(rate_limiter::RetrySlots::new(limiter.clone(), model))
+            }
+            None => req,
+        }
+    }
+
+    fn record_usage(&self, model: &str, usage: usage::Usage) {
+        if let Some(tracker) = &self.usage {
+            tracker.record(model, self.tag.as_deref(), usage);
+        }
     }
 
     fn prepare_request(&self, req: impl IntoRequest) -> RequestBuilder {
-        let req = req.into_request(&self.base_url, self.client.clone());
+        let mut req = req
+            .into_request(&self.base_url, self.client.clone())
+            .with_extension(key_pool::ApiRequest);
+        if let Some(breaker) = &self.circuit_breaker {
+            req = req.with_extension(breaker.clone());
+        }
         let req = if self.token.is_empty() {
             req
         } else {
@@ -104,18 +1516,52 @@ impl LlmSdk {
     }
 }
 
+/// A client that traces requests and retries transient failures. `outer` middlewares see the
+/// final response of each request, `inner` ones every attempt.
+pub(crate) fn build_client(
+    max_retries: u32,
+    outer: &[Arc<dyn Middleware>],
+    inner: &[Arc<dyn Middleware>],
+) -> ClientWithMiddleware {
+    let retry_policy = ExponentialBackoff::builder().build_with_max_retries(max_retries);
+    let m = RetryTransientMiddleware::new_with_policy(retry_policy);
+    let mut builder = ClientBuilder::new(Client::new()).with(TracingMiddleware::default());
+    for middleware in outer {
+        builder = builder.with_arc(middleware.clone());
+    }
+    builder = builder.with(RetryMiddleware::from(m));
+    for middleware in inner {
+        builder = builder.with_arc(middleware.clone());
+    }
+    builder.build()
+}
+
 trait SendAndLog {
     async fn send_and_log(self) -> Result<Response>;
 }
 
 impl SendAndLog for RequestBuilder {
     async fn send_and_log(self) -> Result<Response> {
-        let res = self.send().await?;
+        let res = self.send().await.map_err(|e| match e {
+            // surface it as is, so callers can tell it from a failed request
+            reqwest_middleware::Error::Middleware(e)
+                if e.is::<circuit_breaker::CircuitOpenError>() =>
+            {
+                e
+            }
+            e => e.into(),
+        })?;
         let status = res.status();
         if status.is_client_error() || status.is_server_error() {
-            let text = res.text().await?;
-            tracing::error!("API failed: {:#?}", text);
-            return Err(anyhow!("API failed: {:#?}", text));
+            let request_id = meta::ResponseMeta::from(&res).request_id;
+            let body = res.text().await?;
+            tracing::error!(?request_id, "API failed: {:#?}", body);
+            return Err(meta::ApiError {
+                status,
+                request_id,
+                body,
+            }
+            .into());
         }
         Ok(res)
     }
@@ -1,12 +1,43 @@
-use reqwest::{header, Request, Response};
+use std::sync::{Arc, Mutex};
+
+use reqwest::{header, Request, Response, StatusCode};
 use reqwest_middleware::{Middleware, Next, Result};
 use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
 use task_local_extensions::Extensions;
 
+use crate::{
+    circuit_breaker::{self, CircuitBreaker},
+    key_pool::{ApiRequest, KeyPool, TokenCharge},
+    meta::RateLimit,
+    metrics::AttemptCounter,
+    rate_limiter::RetrySlots,
+};
+
 pub(crate) struct RetryMiddleware {
     inner: RetryTransientMiddleware<ExponentialBackoff>,
 }
 
+/// Keeps the rate-limit state reported by the latest response that had one.
+pub(crate) struct RateLimitMiddleware {
+    latest: Arc<Mutex<Option<RateLimit>>>,
+}
+
+/// Sends each attempt of the SDK's API requests with a key of the pool and reports the response
+/// back to it.
+pub(crate) struct KeyPoolMiddleware {
+    pool: Arc<KeyPool>,
+}
+
+/// Counts the attempts of requests carrying an attempt counter in their extensions.
+pub(crate) struct AttemptCounterMiddleware;
+
+/// Holds the retries of requests carrying retry slots in their extensions until the rate limiter
+/// has room for them.
+pub(crate) struct RateLimiterMiddleware;
+
+/// Lets each attempt through the circuit breaker the request carries in its extensions, if any.
+pub(crate) struct CircuitBreakerMiddleware;
+
 #[async_trait::async_trait]
 impl Middleware for RetryMiddleware {
     async fn handle(
@@ -34,6 +65,126 @@ impl Middleware for RetryMiddleware {
     }
 }
 
+#[async_trait::async_trait]
+impl Middleware for RateLimitMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        let res = next.run(req, extensions).await?;
+        if let Some(rate_limit) = RateLimit::from_headers(res.headers()) {
+            *self.latest.lock().unwrap() = Some(rate_limit);
+        }
+        Ok(res)
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for KeyPoolMiddleware {
+    async fn handle(
+        &self,
+        mut req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        // never send a key to other hosts, e.g. the CDN of generated images
+        if extensions.get::<ApiRequest>().is_none() {
+            return next.run(req, extensions).await;
+        }
+        let charge = extensions.get::<TokenCharge>().cloned();
+        let tokens = charge
+            .as_ref()
+            .map(TokenCharge::estimated)
+            .unwrap_or_default();
+        loop {
+            let (index, key) = self.pool.pick(tokens)?;
+            let value = header::HeaderValue::from_str(&format!("Bearer {key}"))
+                .map_err(|e| reqwest_middleware::Error::Middleware(e.into()))?;
+            req.headers_mut().insert(header::AUTHORIZATION, value);
+            // a rejected key says nothing about the request: send it again with another key
+            let retry = req.try_clone();
+            let res = next.clone().run(req, extensions).await?;
+            let reported = self.pool.observe(index, res.status(), res.headers());
+            if let Some(charge) = &charge {
+                charge.charged((!reported).then(|| (self.pool.clone(), index)));
+            }
+            match retry {
+                Some(retry)
+                    if res.status() == StatusCode::UNAUTHORIZED && self.pool.has_usable_key() =>
+                {
+                    req = retry;
+                }
+                _ => return Ok(res),
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for AttemptCounterMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        if let Some(counter) = extensions.get::<AttemptCounter>() {
+            counter.increment(req.url().path());
+        }
+        next.run(req, extensions).await
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for RateLimiterMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        if let Some(slots) = extensions.get::<RetrySlots>().cloned() {
+            slots.next_attempt().await;
+        }
+        next.run(req, extensions).await
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for CircuitBreakerMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        let Some(breaker) = extensions.get::<Arc<CircuitBreaker>>().cloned() else {
+            return next.run(req, extensions).await;
+        };
+        let endpoint = circuit_breaker::endpoint(req.method(), req.url());
+        let attempt = breaker
+            .try_attempt(&endpoint)
+            .map_err(anyhow::Error::from)?;
+        let res = next.run(req, extensions).await;
+        attempt.finish(res.as_ref().ok().map(|res| res.status()));
+        res
+    }
+}
+
+impl From<Arc<KeyPool>> for KeyPoolMiddleware {
+    fn from(pool: Arc<KeyPool>) -> Self {
+        Self { pool }
+    }
+}
+
+impl From<Arc<Mutex<Option<RateLimit>>>> for RateLimitMiddleware {
+    fn from(latest: Arc<Mutex<Option<RateLimit>>>) -> Self {
+        Self { latest }
+    }
+}
+
 impl From<RetryTransientMiddleware<ExponentialBackoff>> for RetryMiddleware {
     fn from(inner: RetryTransientMiddleware<ExponentialBackoff>) -> Self {
         Self { inner }